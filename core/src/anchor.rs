@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Handle to a buffer location that is kept up to date through edits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AnchorId(u64);
+
+/// Which side of an insertion an anchor sticks to
+///
+/// Gravity only matters when text is inserted exactly at the anchor, or when the
+/// text around the anchor is replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Gravity {
+    /// Stay before text inserted at the anchor (like a bookmark)
+    #[default]
+    Left,
+    /// Move after text inserted at the anchor (like a cursor)
+    Right,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Anchor {
+    offset: usize,
+    gravity: Gravity,
+}
+
+/// A set of anchors, stored as char offsets
+#[derive(Debug, Clone, Default)]
+pub struct AnchorSet {
+    anchors: HashMap<AnchorId, Anchor>,
+    next_id: u64,
+}
+
+impl AnchorSet {
+    /// Add an anchor at a char offset
+    pub fn insert(&mut self, offset: usize, gravity: Gravity) -> AnchorId {
+        let id = AnchorId(self.next_id);
+        self.next_id += 1;
+        self.anchors.insert(id, Anchor { offset, gravity });
+        id
+    }
+
+    /// Remove an anchor, returning whether it existed
+    pub fn remove(&mut self, id: AnchorId) -> bool {
+        self.anchors.remove(&id).is_some()
+    }
+
+    /// Get the current char offset of an anchor
+    pub fn offset(&self, id: AnchorId) -> Option<usize> {
+        self.anchors.get(&id).map(|anchor| anchor.offset)
+    }
+
+    /// Get the gravity of an anchor
+    pub fn gravity(&self, id: AnchorId) -> Option<Gravity> {
+        self.anchors.get(&id).map(|anchor| anchor.gravity)
+    }
+
+    /// Number of anchors in the set
+    pub fn len(&self) -> usize {
+        self.anchors.len()
+    }
+
+    /// Check if the set has no anchors
+    pub fn is_empty(&self) -> bool {
+        self.anchors.is_empty()
+    }
+
+    /// Adjust all anchors for `old_range` being replaced by `new_len` chars
+    ///
+    /// Anchors inside a deleted or replaced range collapse to the start of the
+    /// inserted text (left gravity) or its end (right gravity), so they never point
+    /// into text that no longer exists.
+    pub fn apply_edit(&mut self, old_range: Range<usize>, new_len: usize) {
+        let Range { start, end } = old_range;
+
+        for anchor in self.anchors.values_mut() {
+            let offset = anchor.offset;
+
+            anchor.offset =
+                if offset < start || (offset == start && anchor.gravity == Gravity::Left) {
+                    offset
+                } else if offset >= end && offset > start {
+                    offset - (end - start) + new_len
+                } else {
+                    match anchor.gravity {
+                        Gravity::Left => start,
+                        Gravity::Right => start + new_len,
+                    }
+                };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_respects_gravity() {
+        let mut anchors = AnchorSet::default();
+        let before = anchors.insert(2, Gravity::Left);
+        let left = anchors.insert(5, Gravity::Left);
+        let right = anchors.insert(5, Gravity::Right);
+        let after = anchors.insert(8, Gravity::Left);
+
+        // Insert three chars at offset 5
+        anchors.apply_edit(5..5, 3);
+
+        assert_eq!(anchors.offset(before), Some(2));
+        assert_eq!(anchors.offset(left), Some(5));
+        assert_eq!(anchors.offset(right), Some(8));
+        assert_eq!(anchors.offset(after), Some(11));
+    }
+
+    #[test]
+    fn test_deleted_text_collapses_anchors() {
+        let mut anchors = AnchorSet::default();
+        let inside = anchors.insert(4, Gravity::Left);
+        let inside_right = anchors.insert(4, Gravity::Right);
+        let at_end = anchors.insert(6, Gravity::Left);
+        let after = anchors.insert(10, Gravity::Right);
+
+        // Delete chars 2..6
+        anchors.apply_edit(2..6, 0);
+
+        assert_eq!(anchors.offset(inside), Some(2));
+        assert_eq!(anchors.offset(inside_right), Some(2));
+        assert_eq!(anchors.offset(at_end), Some(2));
+        assert_eq!(anchors.offset(after), Some(6));
+
+        // Replacing text moves anchors inside it according to gravity
+        anchors.apply_edit(1..4, 2);
+        assert_eq!(anchors.offset(inside), Some(1));
+        assert_eq!(anchors.offset(inside_right), Some(3));
+
+        assert!(anchors.remove(inside));
+        assert_eq!(anchors.offset(inside), None);
+    }
+}
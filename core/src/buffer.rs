@@ -1,11 +1,69 @@
+use crate::anchor::{AnchorId, AnchorSet, Gravity};
+use crate::decoration::{
+    Decoration, DecorationId, DecorationKind, DecorationSet, DecorationSpan, DecorationStyle,
+};
+use crate::diagnostic::{self, AnchoredDiagnostic, Diagnostic, DiagnosticSeverity};
+use crate::line_widths::LineWidths;
+use crate::virtual_text::{
+    VirtualText, VirtualTextId, VirtualTextKind, VirtualTextPlacement, VirtualTextSet,
+    VirtualTextSpan,
+};
 use crate::{Cursor, Position, Selection};
 use ropey::Rope;
+use std::collections::VecDeque;
+use std::ops::Range;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum BufferError {
     #[error("Invalid position: line {line}, column {column}")]
     InvalidPosition { line: usize, column: usize },
+    #[error("Edits overlap")]
+    OverlappingEdits,
+}
+
+/// Maximum number of changes kept for [`Buffer::changes_since`]
+const MAX_CHANGE_HISTORY: usize = 1000;
+
+/// A single edit to the buffer text
+///
+/// Ranges are char offsets. Applying the changes returned by
+/// [`Buffer::changes_since`] in order to the old text yields the current text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextChange {
+    /// Revision the buffer had after this change
+    pub revision: u64,
+    /// Replaced range in the text before the change
+    pub old_range: Range<usize>,
+    /// Range of the inserted text in the text after the change
+    pub new_range: Range<usize>,
+    /// The inserted text
+    pub text: String,
+}
+
+/// A replacement of a range of text, e.g. from a formatter or language server
+///
+/// Positions use char columns. All edits in a batch refer to the text before any
+/// of them is applied, and must not overlap.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TextEdit {
+    pub range: Range<Position>,
+    pub new_text: String,
+}
+
+impl TextEdit {
+    pub fn new(range: Range<Position>, new_text: impl Into<String>) -> Self {
+        Self {
+            range,
+            new_text: new_text.into(),
+        }
+    }
+
+    /// Create an edit inserting text at a position
+    pub fn insert(position: Position, text: impl Into<String>) -> Self {
+        Self::new(position..position, text)
+    }
 }
 
 /// Represents a text buffer with undo/redo capabilities
@@ -13,9 +71,23 @@ pub enum BufferError {
 pub struct Buffer {
     rope: Rope,
     is_modified: bool,
+    revision: u64,
+    changes: VecDeque<TextChange>,
+    /// Oldest revision the change history can still answer for
+    changes_base: u64,
+    line_widths: LineWidths,
+    anchors: AnchorSet,
+    decorations: DecorationSet,
+    diagnostics: Vec<AnchoredDiagnostic>,
+    diagnostics_generation: u64,
+    virtual_text: VirtualTextSet,
     undo_stack: Vec<BufferState>,
     redo_stack: Vec<BufferState>,
     max_undo_levels: usize,
+    /// Nesting depth of open undo groups
+    undo_group_depth: usize,
+    /// Whether the open undo group already saved its undo state
+    undo_group_saved: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -30,23 +102,56 @@ impl Buffer {
         Self {
             rope: Rope::new(),
             is_modified: false,
+            revision: 0,
+            changes: VecDeque::new(),
+            changes_base: 0,
+            line_widths: LineWidths::from_rope(&Rope::new()),
+            anchors: AnchorSet::default(),
+            decorations: DecorationSet::default(),
+            diagnostics: Vec::new(),
+            diagnostics_generation: 0,
+            virtual_text: VirtualTextSet::default(),
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
             max_undo_levels: 100,
+            undo_group_depth: 0,
+            undo_group_saved: false,
         }
     }
 
     /// Create a buffer from text content
     pub fn from_text(text: &str) -> Self {
+        let rope = Rope::from_str(text);
         Self {
-            rope: Rope::from_str(text),
+            line_widths: LineWidths::from_rope(&rope),
+            anchors: AnchorSet::default(),
+            decorations: DecorationSet::default(),
+            diagnostics: Vec::new(),
+            diagnostics_generation: 0,
+            virtual_text: VirtualTextSet::default(),
+            rope,
             is_modified: false,
+            revision: 0,
+            changes: VecDeque::new(),
+            changes_base: 0,
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
             max_undo_levels: 100,
+            undo_group_depth: 0,
+            undo_group_saved: false,
         }
     }
 
+    /// Replace the whole content, discarding undo history
+    ///
+    /// Unlike creating a new buffer, this keeps the revision counter increasing.
+    pub fn reset(&mut self, text: &str) {
+        self.replace_rope(Rope::from_str(text));
+        self.is_modified = false;
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+
     /// Get the rope reference
     pub fn rope(&self) -> &Rope {
         &self.rope
@@ -57,6 +162,30 @@ impl Buffer {
         self.is_modified
     }
 
+    /// Get the current revision number
+    ///
+    /// The revision is bumped on every change to the text, including undo and redo,
+    /// so consumers can cheaply tell whether anything changed since they last looked.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Get the edits made after `revision`, oldest first
+    ///
+    /// Returns `None` if the history no longer reaches back that far, in which case
+    /// the consumer should re-read the whole buffer.
+    pub fn changes_since(&self, revision: u64) -> Option<impl Iterator<Item = &TextChange>> {
+        if revision < self.changes_base {
+            return None;
+        }
+
+        Some(
+            self.changes
+                .iter()
+                .filter(move |change| change.revision > revision),
+        )
+    }
+
     /// Get the entire text content
     pub fn text(&self) -> String {
         self.rope.to_string()
@@ -80,8 +209,429 @@ impl Buffer {
         self.rope.len_chars()
     }
 
+    /// Get the visual width of the longest line in columns
+    ///
+    /// Widths are tracked incrementally as the buffer is edited, so this is cheap to
+    /// call on every scroll or draw. Tabs count up to the next tab stop.
+    pub fn max_line_width(&self) -> usize {
+        self.line_widths.max()
+    }
+
+    /// Get the visual width of a line in columns
+    pub fn line_width(&self, line: usize) -> Option<usize> {
+        self.line_widths.get(line)
+    }
+
+    /// Create an anchor that follows a position through edits
+    pub fn create_anchor(&mut self, position: Position, gravity: Gravity) -> AnchorId {
+        let offset = position
+            .to_byte_offset(&self.rope)
+            .min(self.rope.len_bytes());
+        self.anchors.insert(self.rope.byte_to_char(offset), gravity)
+    }
+
+    /// Create an anchor at a char offset
+    pub fn create_anchor_at_offset(&mut self, offset: usize, gravity: Gravity) -> AnchorId {
+        self.anchors
+            .insert(offset.min(self.rope.len_chars()), gravity)
+    }
+
+    /// Remove an anchor, returning whether it existed
+    pub fn remove_anchor(&mut self, id: AnchorId) -> bool {
+        self.anchors.remove(id)
+    }
+
+    /// Get the current position of an anchor
+    pub fn anchor_position(&self, id: AnchorId) -> Option<Position> {
+        let offset = self.anchor_offset(id)?;
+        let line = self.rope.char_to_line(offset);
+        Some(Position::new(line, offset - self.rope.line_to_char(line)))
+    }
+
+    /// Get the current char offset of an anchor
+    pub fn anchor_offset(&self, id: AnchorId) -> Option<usize> {
+        self.anchors
+            .offset(id)
+            .map(|offset| offset.min(self.rope.len_chars()))
+    }
+
+    /// Get the anchors of this buffer
+    pub fn anchors(&self) -> &AnchorSet {
+        &self.anchors
+    }
+
+    /// Add a decoration over a range
+    ///
+    /// The range is tracked through edits like an anchor pair. Text typed right at
+    /// either edge does not extend the decoration.
+    pub fn add_decoration(
+        &mut self,
+        range: Range<Position>,
+        kind: DecorationKind,
+        style: DecorationStyle,
+    ) -> DecorationId {
+        let start = self.create_anchor(range.start, Gravity::Right);
+        let end = self.create_anchor(range.end, Gravity::Left);
+        self.decorations.insert(Decoration {
+            kind,
+            style,
+            start,
+            end,
+        })
+    }
+
+    /// Remove a decoration, returning whether it existed
+    pub fn remove_decoration(&mut self, id: DecorationId) -> bool {
+        match self.decorations.remove(id) {
+            Some(decoration) => {
+                self.anchors.remove(decoration.start);
+                self.anchors.remove(decoration.end);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove all decorations of a kind, returning how many were removed
+    pub fn clear_decorations(&mut self, kind: &DecorationKind) -> usize {
+        let removed = self
+            .decorations
+            .drain_where(|decoration| &decoration.kind == kind);
+        for decoration in &removed {
+            self.anchors.remove(decoration.start);
+            self.anchors.remove(decoration.end);
+        }
+        removed.len()
+    }
+
+    /// Get all decorations with their current ranges, in the order they were added
+    pub fn decorations(&self) -> impl Iterator<Item = DecorationSpan<'_>> {
+        self.decorations.iter().filter_map(|(id, decoration)| {
+            Some(DecorationSpan {
+                id,
+                kind: &decoration.kind,
+                style: decoration.style,
+                range: self.anchor_range(decoration.start, decoration.end)?,
+            })
+        })
+    }
+
+    /// Counter bumped whenever decorations are added or removed
+    ///
+    /// Together with [`Buffer::revision`] this tells renderers when to repaint decorations.
+    pub fn decorations_generation(&self) -> u64 {
+        self.decorations.generation()
+    }
+
+    /// Replace all diagnostics, e.g. with a fresh report from a language server
+    pub fn set_diagnostics(&mut self, diagnostics: Vec<Diagnostic>) {
+        self.clear_diagnostics();
+        for diagnostic in diagnostics {
+            let anchored = self.anchor_diagnostic(diagnostic);
+            self.diagnostics.push(anchored);
+        }
+
+        let mut diagnostics = std::mem::take(&mut self.diagnostics);
+        diagnostics.sort_by_cached_key(|diagnostic| self.diagnostic_order(diagnostic));
+        self.diagnostics = diagnostics;
+    }
+
+    /// Add a diagnostic, tracked through edits like a decoration
+    pub fn add_diagnostic(&mut self, diagnostic: Diagnostic) {
+        let anchored = self.anchor_diagnostic(diagnostic);
+        let order = self.diagnostic_order(&anchored);
+        let index = self
+            .diagnostics
+            .partition_point(|diagnostic| self.diagnostic_order(diagnostic) <= order);
+        self.diagnostics.insert(index, anchored);
+        self.diagnostics_generation += 1;
+    }
+
+    fn anchor_diagnostic(&mut self, diagnostic: Diagnostic) -> AnchoredDiagnostic {
+        AnchoredDiagnostic {
+            severity: diagnostic.severity,
+            message: diagnostic.message,
+            source: diagnostic.source,
+            start: self.create_anchor(diagnostic.range.start, Gravity::Right),
+            end: self.create_anchor(diagnostic.range.end, Gravity::Left),
+        }
+    }
+
+    /// Sort key of a diagnostic: its start position, then its severity
+    ///
+    /// Edits move anchors but never reorder them, so diagnostics sorted once when
+    /// they are added stay sorted.
+    fn diagnostic_order(
+        &self,
+        diagnostic: &AnchoredDiagnostic,
+    ) -> (usize, usize, DiagnosticSeverity) {
+        let (line, column) = self
+            .anchor_position(diagnostic.start)
+            .map(|start| (start.line, start.column))
+            .unwrap_or_default();
+        (line, column, diagnostic.severity)
+    }
+
+    fn resolve_diagnostic(&self, diagnostic: &AnchoredDiagnostic) -> Option<Diagnostic> {
+        Some(Diagnostic {
+            range: self.anchor_range(diagnostic.start, diagnostic.end)?,
+            severity: diagnostic.severity,
+            message: diagnostic.message.clone(),
+            source: diagnostic.source.clone(),
+        })
+    }
+
+    /// Remove all diagnostics
+    pub fn clear_diagnostics(&mut self) {
+        for diagnostic in std::mem::take(&mut self.diagnostics) {
+            self.anchors.remove(diagnostic.start);
+            self.anchors.remove(diagnostic.end);
+        }
+        self.diagnostics_generation += 1;
+    }
+
+    /// Get all diagnostics with their current ranges, ordered by position
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter_map(|diagnostic| self.resolve_diagnostic(diagnostic))
+            .collect()
+    }
+
+    /// Get the most severe diagnostic covering a position
+    pub fn diagnostic_at(&self, position: Position) -> Option<Diagnostic> {
+        // Only diagnostics starting at or before the position can cover it
+        let candidates = self.diagnostics.partition_point(|diagnostic| {
+            let (line, column, _) = self.diagnostic_order(diagnostic);
+            (line, column) <= (position.line, position.column)
+        });
+
+        self.diagnostics[..candidates]
+            .iter()
+            .filter(|diagnostic| {
+                self.anchor_range(diagnostic.start, diagnostic.end)
+                    .is_some_and(|range| diagnostic::range_contains(&range, position))
+            })
+            .min_by_key(|diagnostic| diagnostic.severity)
+            .and_then(|diagnostic| self.resolve_diagnostic(diagnostic))
+    }
+
+    /// Counter bumped whenever diagnostics are added or removed
+    pub fn diagnostics_generation(&self) -> u64 {
+        self.diagnostics_generation
+    }
+
+    /// Attach non-editable text to a position, e.g. an inlay hint
+    ///
+    /// The text follows the position through edits. Text typed right at the position
+    /// goes before inline virtual text.
+    pub fn add_virtual_text(
+        &mut self,
+        position: Position,
+        text: impl Into<String>,
+        kind: VirtualTextKind,
+        placement: VirtualTextPlacement,
+    ) -> VirtualTextId {
+        let anchor = self.create_anchor(position, Gravity::Right);
+        let anchors = &self.anchors;
+        self.virtual_text.insert(
+            VirtualText {
+                kind,
+                text: text.into(),
+                placement,
+                anchor,
+            },
+            |anchor| anchors.offset(anchor).unwrap_or_default(),
+        )
+    }
+
+    /// Remove a piece of virtual text, returning whether it existed
+    pub fn remove_virtual_text(&mut self, id: VirtualTextId) -> bool {
+        match self.virtual_text.remove(id) {
+            Some(text) => {
+                self.anchors.remove(text.anchor);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove all virtual text of a kind, returning how many were removed
+    pub fn clear_virtual_text(&mut self, kind: &VirtualTextKind) -> usize {
+        let removed = self.virtual_text.drain_where(|text| &text.kind == kind);
+        for text in &removed {
+            self.anchors.remove(text.anchor);
+        }
+        removed.len()
+    }
+
+    /// Get all virtual text with its current position, in the order it was added
+    pub fn virtual_text(&self) -> impl Iterator<Item = VirtualTextSpan<'_>> {
+        self.virtual_text.iter().filter_map(|(id, text)| {
+            Some(VirtualTextSpan {
+                id,
+                kind: &text.kind,
+                text: &text.text,
+                placement: text.placement,
+                position: self.anchor_position(text.anchor)?,
+            })
+        })
+    }
+
+    /// Get the virtual text on a line, ordered by column
+    pub fn virtual_text_on_line(&self, line: usize) -> Vec<VirtualTextSpan<'_>> {
+        if line >= self.rope.len_lines() {
+            return Vec::new();
+        }
+        let start = self.rope.line_to_char(line);
+        // Text anchored at the very end of the buffer belongs to the last line
+        let end = if line + 1 < self.rope.len_lines() {
+            self.rope.line_to_char(line + 1)
+        } else {
+            usize::MAX
+        };
+
+        self.virtual_text
+            .in_range(start..end, |anchor| {
+                self.anchor_offset(anchor).unwrap_or_default()
+            })
+            .map(|(id, text)| VirtualTextSpan {
+                id,
+                kind: &text.kind,
+                text: &text.text,
+                placement: text.placement,
+                position: Position::new(
+                    line,
+                    self.anchor_offset(text.anchor).unwrap_or(start) - start,
+                ),
+            })
+            .collect()
+    }
+
+    /// Get the width of the widest line including its inline virtual text
+    ///
+    /// Text after the end of a line is not counted, so long hints there do not
+    /// stretch the scrollable area.
+    pub fn max_line_width_with_virtual_text(&self) -> usize {
+        let offset = |anchor| self.anchor_offset(anchor).unwrap_or_default();
+        let mut max = self.max_line_width();
+        let mut line_width: Option<(usize, usize)> = None;
+
+        // Spans are ordered by offset, so the spans of a line are adjacent
+        for (_, text) in self.virtual_text.in_range(0..usize::MAX, offset) {
+            if text.placement != VirtualTextPlacement::Inline {
+                continue;
+            }
+            let line = self.rope.char_to_line(offset(text.anchor));
+            let width = match line_width {
+                Some((previous, width)) if previous == line => width,
+                _ => self.line_width(line).unwrap_or_default(),
+            } + text.text.chars().count();
+            line_width = Some((line, width));
+            max = max.max(width);
+        }
+        max
+    }
+
+    /// Counter bumped whenever virtual text is added or removed
+    pub fn virtual_text_generation(&self) -> u64 {
+        self.virtual_text.generation()
+    }
+
+    /// Resolve a pair of anchors into a range
+    fn anchor_range(&self, start: AnchorId, end: AnchorId) -> Option<Range<Position>> {
+        let start = self.anchor_position(start)?;
+        let end = self.anchor_position(end)?;
+
+        // An insertion inside an empty range can push the edges past each other
+        if (end.line, end.column) < (start.line, start.column) {
+            Some(start..start)
+        } else {
+            Some(start..end)
+        }
+    }
+
+    /// Record that the text changed
+    fn mark_modified(&mut self) {
+        self.is_modified = true;
+        self.revision += 1;
+    }
+
+    /// Replace a char range with new text
+    fn edit(&mut self, range: Range<usize>, text: &str) {
+        let old_lines = self.rope.char_to_line(range.start)..self.rope.char_to_line(range.end) + 1;
+
+        self.rope.remove(range.clone());
+        self.rope.insert(range.start, text);
+
+        self.record_edit(range, old_lines, text.to_string());
+    }
+
+    /// Replace the whole rope, recording only the part that actually differs
+    fn replace_rope(&mut self, rope: Rope) {
+        let (old_range, new_len) = diff_ropes(&self.rope, &rope);
+        let old_lines =
+            self.rope.char_to_line(old_range.start)..self.rope.char_to_line(old_range.end) + 1;
+        let text = rope
+            .slice(old_range.start..old_range.start + new_len)
+            .to_string();
+
+        self.rope = rope;
+        self.record_edit(old_range, old_lines, text);
+    }
+
+    /// Update line widths and anchors after the rope was edited and log the change
+    fn record_edit(&mut self, old_range: Range<usize>, old_lines: Range<usize>, text: String) {
+        let new_range = old_range.start..old_range.start + text.chars().count();
+
+        self.anchors.apply_edit(old_range.clone(), new_range.len());
+
+        let new_end_line = self.rope.char_to_line(new_range.end);
+        self.line_widths.splice(
+            &self.rope,
+            old_lines.clone(),
+            new_end_line - old_lines.start + 1,
+        );
+        self.mark_modified();
+
+        self.changes.push_back(TextChange {
+            revision: self.revision,
+            old_range,
+            new_range,
+            text,
+        });
+        if self.changes.len() > MAX_CHANGE_HISTORY {
+            if let Some(oldest) = self.changes.pop_front() {
+                self.changes_base = oldest.revision;
+            }
+        }
+    }
+
+    /// Start grouping edits into a single undo step
+    ///
+    /// Groups can be nested; the step is closed by the outermost [`Buffer::end_undo_group`].
+    pub fn begin_undo_group(&mut self) {
+        self.undo_group_depth += 1;
+    }
+
+    /// Close the undo group opened by [`Buffer::begin_undo_group`]
+    pub fn end_undo_group(&mut self) {
+        self.undo_group_depth = self.undo_group_depth.saturating_sub(1);
+        if self.undo_group_depth == 0 {
+            self.undo_group_saved = false;
+        }
+    }
+
     /// Save current state for undo
     fn save_state(&mut self, cursor_position: Position) {
+        // Inside an undo group only the state before the first edit is kept
+        if self.undo_group_depth > 0 {
+            if self.undo_group_saved {
+                return;
+            }
+            self.undo_group_saved = true;
+        }
+
         let state = BufferState {
             rope: self.rope.clone(),
             cursor_position,
@@ -96,6 +646,63 @@ impl Buffer {
         self.redo_stack.clear();
     }
 
+    /// Apply a batch of edits as a single undo step
+    ///
+    /// The cursor keeps its place relative to the surrounding text.
+    pub fn apply_edits(
+        &mut self,
+        edits: &[TextEdit],
+        cursor: &mut Cursor,
+    ) -> Result<(), BufferError> {
+        if edits.is_empty() {
+            return Ok(());
+        }
+
+        // Resolve every range against the original text before anything moves
+        let mut ranges: Vec<(Range<usize>, &str)> = edits
+            .iter()
+            .map(|edit| {
+                let start = self.char_offset(edit.range.start);
+                let end = self.char_offset(edit.range.end).max(start);
+                (start..end, edit.new_text.as_str())
+            })
+            .collect();
+        ranges.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+
+        if ranges
+            .windows(2)
+            .any(|pair| pair[1].0.end > pair[0].0.start)
+        {
+            return Err(BufferError::OverlappingEdits);
+        }
+
+        self.begin_undo_group();
+        self.save_state(cursor.position());
+        let cursor_anchor = self.create_anchor(cursor.position(), Gravity::Right);
+
+        // Apply from the end so earlier offsets stay valid
+        for (range, text) in ranges {
+            self.edit(range, text);
+        }
+
+        if let Some(position) = self.anchor_position(cursor_anchor) {
+            cursor.set_position(position);
+        }
+        self.remove_anchor(cursor_anchor);
+        self.end_undo_group();
+
+        Ok(())
+    }
+
+    /// Convert a position with a char column into a char offset
+    pub fn char_offset(&self, position: Position) -> usize {
+        self.rope.byte_to_char(
+            position
+                .to_byte_offset(&self.rope)
+                .min(self.rope.len_bytes()),
+        )
+    }
+
     /// Insert character at position
     pub fn insert_char(
         &mut self,
@@ -106,8 +713,8 @@ impl Buffer {
         self.save_state(cursor.position());
 
         let offset = position.to_byte_offset(&self.rope);
-        self.rope.insert_char(offset, ch);
-        self.is_modified = true;
+        let char_idx = self.rope.byte_to_char(offset);
+        self.edit(char_idx..char_idx, ch.encode_utf8(&mut [0; 4]));
 
         // Move cursor after inserted character
         cursor.set_position(Position::from_byte_offset(
@@ -132,8 +739,8 @@ impl Buffer {
         self.save_state(cursor.position());
 
         let offset = position.to_byte_offset(&self.rope);
-        self.rope.insert(offset, text);
-        self.is_modified = true;
+        let char_idx = self.rope.byte_to_char(offset);
+        self.edit(char_idx..char_idx, text);
 
         // Move cursor after inserted text
         cursor.set_position(Position::from_byte_offset(&self.rope, offset + text.len()));
@@ -154,9 +761,8 @@ impl Buffer {
 
         self.save_state(cursor.position());
 
-        let ch = self.rope.char(offset);
-        self.rope.remove(offset..offset + ch.len_utf8());
-        self.is_modified = true;
+        let char_idx = self.rope.byte_to_char(offset);
+        self.edit(char_idx..char_idx + 1, "");
 
         Ok(true)
     }
@@ -181,9 +787,9 @@ impl Buffer {
             char_idx -= 1;
         }
         let prev_offset = text.char_to_byte(char_idx);
+        let end_idx = text.byte_to_char(offset);
 
-        self.rope.remove(prev_offset..offset);
-        self.is_modified = true;
+        self.edit(char_idx..end_idx, "");
 
         // Move cursor to deletion point
         cursor.set_position(Position::from_byte_offset(&self.rope, prev_offset));
@@ -199,15 +805,14 @@ impl Buffer {
 
         self.save_state(cursor.position());
 
-        let line_start = self.rope.line_to_byte(line);
+        let line_start = self.rope.line_to_char(line);
         let line_end = if line + 1 < self.rope.len_lines() {
-            self.rope.line_to_byte(line + 1)
+            self.rope.line_to_char(line + 1)
         } else {
-            self.rope.len_bytes()
+            self.rope.len_chars()
         };
 
-        self.rope.remove(line_start..line_end);
-        self.is_modified = true;
+        self.edit(line_start..line_end, "");
 
         // Move cursor to start of line (or previous line if deleted last line)
         let new_line = if line < self.rope.len_lines() {
@@ -233,10 +838,11 @@ impl Buffer {
         self.save_state(cursor.position());
 
         let (start_offset, end_offset) = selection.to_byte_range(&self.rope);
-        let deleted_text = self.rope.slice(start_offset..end_offset).to_string();
+        let start_idx = self.rope.byte_to_char(start_offset);
+        let end_idx = self.rope.byte_to_char(end_offset);
+        let deleted_text = self.rope.slice(start_idx..end_idx).to_string();
 
-        self.rope.remove(start_offset..end_offset);
-        self.is_modified = true;
+        self.edit(start_idx..end_idx, "");
 
         // Move cursor to start of deleted selection
         cursor.set_position(selection.start);
@@ -247,9 +853,11 @@ impl Buffer {
     /// Delete word forward (from cursor position to end of current word)
     pub fn delete_word_forward(&mut self, cursor: &mut Cursor) -> Result<bool, BufferError> {
         let current_pos = cursor.position();
-        let current_offset = current_pos.to_byte_offset(&self.rope);
+        let current_offset = self
+            .rope
+            .byte_to_char(current_pos.to_byte_offset(&self.rope));
 
-        if current_offset >= self.rope.len_bytes() {
+        if current_offset >= self.rope.len_chars() {
             return Ok(false);
         }
 
@@ -259,7 +867,7 @@ impl Buffer {
         let mut end_offset = current_offset;
 
         // Skip current word (non-boundary characters)
-        while end_offset < self.rope.len_bytes() {
+        while end_offset < self.rope.len_chars() {
             let ch = text.char(end_offset);
             if crate::text_utils::is_word_boundary(ch) {
                 break;
@@ -268,7 +876,7 @@ impl Buffer {
         }
 
         // Skip boundaries (whitespace and punctuation) until we find a word character or end
-        while end_offset < self.rope.len_bytes() {
+        while end_offset < self.rope.len_chars() {
             let ch = text.char(end_offset);
             if !crate::text_utils::is_word_boundary(ch) {
                 break;
@@ -277,8 +885,7 @@ impl Buffer {
         }
 
         if end_offset > current_offset {
-            self.rope.remove(current_offset..end_offset);
-            self.is_modified = true;
+            self.edit(current_offset..end_offset, "");
             Ok(true)
         } else {
             Ok(false)
@@ -288,7 +895,9 @@ impl Buffer {
     /// Delete word backward (from cursor position to beginning of current word)
     pub fn delete_word_backward(&mut self, cursor: &mut Cursor) -> Result<bool, BufferError> {
         let current_pos = cursor.position();
-        let current_offset = current_pos.to_byte_offset(&self.rope);
+        let current_offset = self
+            .rope
+            .byte_to_char(current_pos.to_byte_offset(&self.rope));
 
         if current_offset == 0 {
             return Ok(false);
@@ -318,10 +927,10 @@ impl Buffer {
         }
 
         if start_offset < current_offset {
-            self.rope.remove(start_offset..current_offset);
-            self.is_modified = true;
+            self.edit(start_offset..current_offset, "");
             // Move cursor to deletion point
-            cursor.set_position(Position::from_byte_offset(&self.rope, start_offset));
+            let start_byte = self.rope.char_to_byte(start_offset);
+            cursor.set_position(Position::from_byte_offset(&self.rope, start_byte));
             Ok(true)
         } else {
             Ok(false)
@@ -339,18 +948,18 @@ impl Buffer {
 
         self.save_state(current_pos);
 
-        let _line_start = self.rope.line_to_byte(line);
         let line_end = if line + 1 < self.rope.len_lines() {
-            self.rope.line_to_byte(line + 1) - 1 // Don't include the newline
+            self.rope.line_to_char(line + 1) - 1 // Don't include the newline
         } else {
-            self.rope.len_bytes()
+            self.rope.len_chars()
         };
 
-        let current_offset = current_pos.to_byte_offset(&self.rope);
+        let current_offset = self
+            .rope
+            .byte_to_char(current_pos.to_byte_offset(&self.rope));
 
         if current_offset < line_end {
-            self.rope.remove(current_offset..line_end);
-            self.is_modified = true;
+            self.edit(current_offset..line_end, "");
             Ok(true)
         } else {
             Ok(false)
@@ -368,12 +977,13 @@ impl Buffer {
 
         self.save_state(current_pos);
 
-        let line_start = self.rope.line_to_byte(line);
-        let current_offset = current_pos.to_byte_offset(&self.rope);
+        let line_start = self.rope.line_to_char(line);
+        let current_offset = self
+            .rope
+            .byte_to_char(current_pos.to_byte_offset(&self.rope));
 
         if current_offset > line_start {
-            self.rope.remove(line_start..current_offset);
-            self.is_modified = true;
+            self.edit(line_start..current_offset, "");
             // Move cursor to beginning of line
             cursor.set_position(Position::new(line, 0));
             Ok(true)
@@ -393,9 +1003,8 @@ impl Buffer {
             self.redo_stack.push(current_state);
 
             // Restore previous state
-            self.rope = state.rope;
+            self.replace_rope(state.rope);
             cursor.set_position(state.cursor_position);
-            self.is_modified = true;
 
             Ok(true)
         } else {
@@ -414,9 +1023,8 @@ impl Buffer {
             self.undo_stack.push(current_state);
 
             // Restore redo state
-            self.rope = state.rope;
+            self.replace_rope(state.rope);
             cursor.set_position(state.cursor_position);
-            self.is_modified = true;
 
             Ok(true)
         } else {
@@ -424,16 +1032,28 @@ impl Buffer {
         }
     }
 
-    /// Find all occurrences of a pattern
+    /// Find all occurrences of a pattern, as char positions
     pub fn find(&self, pattern: &str) -> Vec<Position> {
+        self.find_in_lines(pattern, 0..self.rope.len_lines())
+    }
+
+    /// Find all occurrences of a pattern on a range of lines
+    pub(crate) fn find_in_lines(&self, pattern: &str, lines: Range<usize>) -> Vec<Position> {
         let mut positions = Vec::new();
-        let text = self.rope.to_string();
+        if pattern.is_empty() {
+            return positions;
+        }
 
-        for (line_idx, line) in text.lines().enumerate() {
+        for line_idx in lines.start..lines.end.min(self.rope.len_lines()) {
+            let text = self.rope.line(line_idx).to_string();
+            let line = text.strip_suffix('\n').unwrap_or(&text);
+            let line = line.strip_suffix('\r').unwrap_or(line);
             let mut start = 0;
             while let Some(pos) = line[start..].find(pattern) {
-                positions.push(Position::new(line_idx, start + pos));
-                start += pos + 1;
+                let byte = start + pos;
+                positions.push(Position::new(line_idx, line[..byte].chars().count()));
+                // Overlapping matches are found too
+                start = byte + line[byte..].chars().next().map_or(1, char::len_utf8);
             }
         }
 
@@ -452,8 +1072,7 @@ impl Buffer {
 
         if text != new_text {
             self.save_state(cursor.position());
-            self.rope = Rope::from_str(&new_text);
-            self.is_modified = true;
+            self.replace_rope(Rope::from_str(&new_text));
 
             // Count replacements
             let count = text.matches(pattern).count();
@@ -464,6 +1083,31 @@ impl Buffer {
     }
 }
 
+/// Find the smallest char range that differs between two ropes
+///
+/// Returns the changed range in `old` and the length of its replacement in `new`.
+fn diff_ropes(old: &Rope, new: &Rope) -> (Range<usize>, usize) {
+    let prefix = old
+        .chars()
+        .zip(new.chars())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let max_suffix = (old.len_chars() - prefix).min(new.len_chars() - prefix);
+    let suffix = old
+        .chars_at(old.len_chars())
+        .reversed()
+        .zip(new.chars_at(new.len_chars()).reversed())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    (
+        prefix..old.len_chars() - suffix,
+        new.len_chars() - prefix - suffix,
+    )
+}
+
 impl Default for Buffer {
     fn default() -> Self {
         Self::new()
@@ -0,0 +1,309 @@
+use crate::{CursorMovement, EditorMessage};
+
+/// What running a command does
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandAction<A> {
+    /// Send a message to the editor
+    Editor(EditorMessage),
+    /// Run an app-defined action, e.g. an app message or callback
+    App(A),
+}
+
+/// A named command, independent of the keys that run it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Command<A> {
+    /// Stable identifier such as `editor.undo`
+    pub id: String,
+    /// Human-readable name such as `Undo`
+    pub title: String,
+    /// Group shown before the title, such as `Edit`
+    pub category: Option<String>,
+    pub action: CommandAction<A>,
+}
+
+impl<A> Command<A> {
+    /// Create a command sending a message to the editor
+    pub fn editor(id: impl Into<String>, title: impl Into<String>, message: EditorMessage) -> Self {
+        Self {
+            id: id.into(),
+            title: title.into(),
+            category: None,
+            action: CommandAction::Editor(message),
+        }
+    }
+
+    /// Create a command running an app-defined action
+    pub fn app(id: impl Into<String>, title: impl Into<String>, action: A) -> Self {
+        Self {
+            id: id.into(),
+            title: title.into(),
+            category: None,
+            action: CommandAction::App(action),
+        }
+    }
+
+    pub fn category(mut self, category: impl Into<String>) -> Self {
+        self.category = Some(category.into());
+        self
+    }
+
+    /// Get the text commands are searched and listed by, e.g. `Edit: Undo`
+    pub fn label(&self) -> String {
+        match &self.category {
+            Some(category) => format!("{}: {}", category, self.title),
+            None => self.title.clone(),
+        }
+    }
+}
+
+/// A command matching a search query
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandMatch<'a, A> {
+    pub command: &'a Command<A>,
+    /// Higher is better
+    pub score: i32,
+    /// Char indices of the query chars in the command's label
+    pub positions: Vec<usize>,
+}
+
+/// Commands available to users, in the order they were registered
+#[derive(Debug, Clone)]
+pub struct CommandRegistry<A> {
+    commands: Vec<Command<A>>,
+}
+
+impl<A> CommandRegistry<A> {
+    /// Create a registry without commands
+    pub fn new() -> Self {
+        Self {
+            commands: Vec::new(),
+        }
+    }
+
+    /// Create a registry with the standard editor commands
+    pub fn standard() -> Self {
+        let mut registry = Self::new();
+        let commands = [
+            ("editor.undo", "Undo", "Edit", EditorMessage::Undo),
+            ("editor.redo", "Redo", "Edit", EditorMessage::Redo),
+            ("editor.cut", "Cut", "Edit", EditorMessage::Cut),
+            ("editor.copy", "Copy", "Edit", EditorMessage::Copy),
+            ("editor.paste", "Paste", "Edit", EditorMessage::Paste),
+            (
+                "editor.deleteLine",
+                "Delete Line",
+                "Edit",
+                EditorMessage::DeleteLine,
+            ),
+            (
+                "editor.selectAll",
+                "Select All",
+                "Selection",
+                EditorMessage::SelectAll,
+            ),
+            (
+                "editor.selectLine",
+                "Select Line",
+                "Selection",
+                EditorMessage::SelectLine,
+            ),
+            (
+                "editor.selectWord",
+                "Select Word",
+                "Selection",
+                EditorMessage::SelectWord,
+            ),
+            (
+                "editor.documentStart",
+                "Go to Start of Document",
+                "Go",
+                EditorMessage::MoveCursor(CursorMovement::DocumentStart),
+            ),
+            (
+                "editor.documentEnd",
+                "Go to End of Document",
+                "Go",
+                EditorMessage::MoveCursor(CursorMovement::DocumentEnd),
+            ),
+            (
+                "editor.nextDiagnostic",
+                "Go to Next Problem",
+                "Go",
+                EditorMessage::NextDiagnostic,
+            ),
+            (
+                "editor.previousDiagnostic",
+                "Go to Previous Problem",
+                "Go",
+                EditorMessage::PreviousDiagnostic,
+            ),
+            (
+                "editor.triggerCompletion",
+                "Show Completions",
+                "Suggest",
+                EditorMessage::TriggerCompletion,
+            ),
+            (
+                "editor.triggerInlineSuggestion",
+                "Show Inline Suggestion",
+                "Suggest",
+                EditorMessage::TriggerInlineSuggestion,
+            ),
+        ];
+        for (id, title, category, message) in commands {
+            registry.register(Command::editor(id, title, message).category(category));
+        }
+        registry
+    }
+
+    /// Add a command, replacing one with the same id in place
+    pub fn register(&mut self, command: Command<A>) {
+        match self.commands.iter_mut().find(|c| c.id == command.id) {
+            Some(existing) => *existing = command,
+            None => self.commands.push(command),
+        }
+    }
+
+    pub fn unregister(&mut self, id: &str) -> Option<Command<A>> {
+        let index = self.commands.iter().position(|command| command.id == id)?;
+        Some(self.commands.remove(index))
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Command<A>> {
+        self.commands.iter().find(|command| command.id == id)
+    }
+
+    pub fn commands(&self) -> &[Command<A>] {
+        &self.commands
+    }
+
+    /// Find the commands whose label contains the query's chars in order, best first
+    ///
+    /// An empty query lists every command in registration order.
+    pub fn search(&self, query: &str) -> Vec<CommandMatch<'_, A>> {
+        let mut matches: Vec<CommandMatch<'_, A>> = self
+            .commands
+            .iter()
+            .filter_map(|command| {
+                let (score, positions) = fuzzy_match(query, &command.label())?;
+                Some(CommandMatch {
+                    command,
+                    score,
+                    positions,
+                })
+            })
+            .collect();
+        // Stable, so equal scores keep registration order
+        matches.sort_by_key(|m| std::cmp::Reverse(m.score));
+        matches
+    }
+}
+
+impl<A> Default for CommandRegistry<A> {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+/// Match a query against a text, ignoring case and spaces in the query
+///
+/// Query chars must appear in the text in order. Matches at word starts and
+/// runs of consecutive chars score higher; skipped chars before the first
+/// match score lower. Returns the score and the char indices of the matches.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<(i32, Vec<usize>)> {
+    let text: Vec<char> = text.chars().collect();
+    let mut positions = Vec::new();
+    let mut score = 0;
+    let mut index = 0;
+
+    for query_char in query.chars().filter(|ch| !ch.is_whitespace()) {
+        let matches_at = |i: usize| text[i].to_lowercase().eq(query_char.to_lowercase());
+        // Prefer continuing the current run, then a word start, then any occurrence
+        let found = if index > 0 && index < text.len() && matches_at(index) {
+            index
+        } else {
+            (index..text.len())
+                .find(|&i| matches_at(i) && is_word_start(&text, i))
+                .or_else(|| (index..text.len()).find(|&i| matches_at(i)))?
+        };
+
+        score += 1;
+        if is_word_start(&text, found) {
+            score += 8;
+        }
+        if found > 0 && positions.last() == Some(&(found - 1)) {
+            score += 5;
+        }
+        positions.push(found);
+        index = found + 1;
+    }
+
+    if let Some(&first) = positions.first() {
+        score -= first.min(10) as i32;
+    }
+    Some((score, positions))
+}
+
+/// Check if a char starts a word, including camelCase humps
+fn is_word_start(text: &[char], index: usize) -> bool {
+    let Some(previous) = index.checked_sub(1).map(|i| text[i]) else {
+        return true;
+    };
+    let ch = text[index];
+    (!previous.is_alphanumeric() && ch.is_alphanumeric())
+        || (previous.is_lowercase() && ch.is_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_match() {
+        let (_, positions) = fuzzy_match("gnp", "Go: Go to Next Problem").unwrap();
+        assert_eq!(positions, vec![0, 10, 15]);
+        assert!(fuzzy_match("xyz", "Edit: Undo").is_none());
+
+        // Word starts beat scattered chars
+        let (word_starts, _) = fuzzy_match("sa", "Selection: Select All").unwrap();
+        let (scattered, _) = fuzzy_match("sa", "Edit: Base Case").unwrap();
+        assert!(word_starts > scattered);
+    }
+
+    #[test]
+    fn test_search_commands() {
+        let mut registry: CommandRegistry<&str> = CommandRegistry::standard();
+        registry.register(Command::app("app.save", "Save File", "save").category("File"));
+
+        let matches = registry.search("undo");
+        assert_eq!(matches[0].command.id, "editor.undo");
+
+        let matches = registry.search("save");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].command.action, CommandAction::App("save"));
+
+        assert_eq!(registry.search("").len(), registry.commands().len());
+
+        // Registering an existing id replaces the command in place
+        registry.register(Command::editor(
+            "editor.undo",
+            "Undo Edit",
+            EditorMessage::Undo,
+        ));
+        assert_eq!(registry.commands()[0].title, "Undo Edit");
+        assert!(registry.unregister("editor.undo").is_some());
+        assert!(registry.get("editor.undo").is_none());
+    }
+
+    #[test]
+    fn test_keys_for_command() {
+        use crate::{Key, Shortcut, ShortcutManager};
+
+        let shortcut_manager = ShortcutManager::new();
+        let keys = shortcut_manager.keys_for(&EditorMessage::Undo);
+        assert!(keys.contains(&vec![Shortcut::ctrl(Key::Character('z'))]));
+        assert!(shortcut_manager
+            .keys_for(&EditorMessage::StopRecording)
+            .is_empty());
+    }
+}
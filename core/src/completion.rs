@@ -0,0 +1,320 @@
+use crate::{Buffer, Cursor, Position, TextEdit};
+use ropey::Rope;
+use std::collections::BTreeSet;
+
+/// What a completion item represents, used to pick an icon or label in the UI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CompletionKind {
+    Text,
+    Keyword,
+    Function,
+    Method,
+    Variable,
+    Field,
+    Type,
+    Module,
+    Snippet,
+}
+
+/// A candidate offered to the user while completing
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompletionItem {
+    pub label: String,
+    pub kind: CompletionKind,
+    /// Extra information shown next to the label, e.g. a type signature
+    pub detail: Option<String>,
+    /// Edit applied when the item is accepted
+    pub edit: TextEdit,
+}
+
+impl CompletionItem {
+    pub fn new(label: impl Into<String>, kind: CompletionKind, edit: TextEdit) -> Self {
+        Self {
+            label: label.into(),
+            kind,
+            detail: None,
+            edit,
+        }
+    }
+
+    /// Set the extra information shown next to the label
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
+
+/// Source of completion items
+///
+/// Providers are asked when completion is triggered. Items are then filtered by
+/// the editor as the user keeps typing, without asking the providers again.
+pub trait CompletionProvider {
+    fn complete(&self, buffer: &Buffer, cursor: &Cursor) -> Vec<CompletionItem>;
+}
+
+/// Check if a char can be part of a completed word
+pub fn is_completion_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+/// Find where the word ending at `position` starts
+pub fn word_start(rope: &Rope, position: Position) -> Position {
+    let Some(line) = rope.get_line(position.line) else {
+        return position;
+    };
+
+    let mut column = position.column.min(line.len_chars());
+    while column > 0 && is_completion_char(line.char(column - 1)) {
+        column -= 1;
+    }
+    Position::new(position.line, column)
+}
+
+/// Completes words that already appear in the buffer
+#[derive(Debug, Clone)]
+pub struct WordCompletionProvider {
+    /// Words shorter than this are not offered
+    pub min_word_len: usize,
+    /// Maximum number of items returned
+    pub max_items: usize,
+}
+
+impl Default for WordCompletionProvider {
+    fn default() -> Self {
+        Self {
+            min_word_len: 3,
+            max_items: 200,
+        }
+    }
+}
+
+impl CompletionProvider for WordCompletionProvider {
+    fn complete(&self, buffer: &Buffer, cursor: &Cursor) -> Vec<CompletionItem> {
+        let rope = buffer.rope();
+        let position = cursor.position();
+        let Some(line) = rope.get_line(position.line) else {
+            return Vec::new();
+        };
+        let start = word_start(rope, position);
+        let prefix: String = line
+            .chars()
+            .skip(start.column)
+            .take(position.column - start.column)
+            .collect();
+        let prefix_lower = prefix.to_lowercase();
+
+        let mut words = BTreeSet::new();
+        let mut word = String::new();
+        for ch in rope.chars().chain(std::iter::once(' ')) {
+            if is_completion_char(ch) {
+                word.push(ch);
+                continue;
+            }
+
+            if word.chars().count() >= self.min_word_len
+                && word != prefix
+                && word.to_lowercase().starts_with(&prefix_lower)
+            {
+                words.insert(std::mem::take(&mut word));
+            }
+            word.clear();
+        }
+
+        words
+            .into_iter()
+            .take(self.max_items)
+            .map(|word| {
+                let edit = TextEdit::new(start..position, word.clone());
+                CompletionItem::new(word, CompletionKind::Text, edit)
+            })
+            .collect()
+    }
+}
+
+/// An open completion popup: the items offered and the one selected
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompletionSession {
+    items: Vec<CompletionItem>,
+    /// Indices into `items` matching the query, best match first
+    matches: Vec<usize>,
+    selected: usize,
+    /// Start of the word being completed
+    word_start: Position,
+    /// Cursor position when completion was triggered
+    trigger: Position,
+    query: String,
+}
+
+impl CompletionSession {
+    /// Open a session; `query` is the word typed so far, between `word_start` and `trigger`
+    pub fn new(
+        items: Vec<CompletionItem>,
+        word_start: Position,
+        trigger: Position,
+        query: &str,
+    ) -> Self {
+        let mut session = Self {
+            items,
+            matches: Vec::new(),
+            selected: 0,
+            word_start,
+            trigger,
+            query: String::new(),
+        };
+        session.filter(query);
+        session
+    }
+
+    /// Narrow the items down to those matching the typed word
+    ///
+    /// Items starting with the query come first, then items containing it. Matching
+    /// ignores case.
+    pub fn filter(&mut self, query: &str) {
+        let query_lower = query.to_lowercase();
+        let (mut prefixed, mut containing) = (Vec::new(), Vec::new());
+        for (index, item) in self.items.iter().enumerate() {
+            let label = item.label.to_lowercase();
+            if label.starts_with(&query_lower) {
+                prefixed.push(index);
+            } else if label.contains(&query_lower) {
+                containing.push(index);
+            }
+        }
+
+        prefixed.append(&mut containing);
+        self.matches = prefixed;
+        if self.query != query {
+            self.query = query.to_string();
+            self.selected = 0;
+        }
+    }
+
+    /// Items matching the query, best match first
+    pub fn visible_items(&self) -> impl Iterator<Item = &CompletionItem> {
+        self.matches.iter().map(|&index| &self.items[index])
+    }
+
+    /// Number of items matching the query
+    pub fn len(&self) -> usize {
+        self.matches.len()
+    }
+
+    /// Check if no item matches the query
+    pub fn is_empty(&self) -> bool {
+        self.matches.is_empty()
+    }
+
+    /// Index of the selected item among the visible items
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected_item(&self) -> Option<&CompletionItem> {
+        self.matches
+            .get(self.selected)
+            .map(|&index| &self.items[index])
+    }
+
+    /// Select the next item, wrapping around
+    pub fn select_next(&mut self) {
+        if !self.matches.is_empty() {
+            self.selected = (self.selected + 1) % self.matches.len();
+        }
+    }
+
+    /// Select the previous item, wrapping around
+    pub fn select_previous(&mut self) {
+        if !self.matches.is_empty() {
+            self.selected = (self.selected + self.matches.len() - 1) % self.matches.len();
+        }
+    }
+
+    /// Start of the word being completed, where the popup is anchored
+    pub fn word_start(&self) -> Position {
+        self.word_start
+    }
+
+    /// The word typed so far
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Get the edit accepting the selected item
+    ///
+    /// Edits ending where completion was triggered are extended to `cursor`, so text
+    /// typed while the popup was open is replaced too.
+    pub fn accept_edit(&self, cursor: Position) -> Option<TextEdit> {
+        let mut edit = self.selected_item()?.edit.clone();
+        if edit.range.end == self.trigger {
+            edit.range.end = cursor;
+        }
+        Some(edit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_provider_completes_buffer_words() {
+        let buffer = Buffer::from_text("format formula for_each\nfo");
+        let mut cursor = Cursor::new();
+        cursor.set_position(Position::new(1, 2));
+
+        let items = WordCompletionProvider::default().complete(&buffer, &cursor);
+        let labels: Vec<&str> = items.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels, vec!["for_each", "format", "formula"]);
+        assert_eq!(
+            items[0].edit,
+            TextEdit::new(Position::new(1, 0)..Position::new(1, 2), "for_each")
+        );
+
+        // A cursor left past the end of the buffer gets no items
+        cursor.set_position(Position::new(5, 0));
+        assert!(WordCompletionProvider::default()
+            .complete(&buffer, &cursor)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_session_filtering_and_selection() {
+        let edit = |label: &str| TextEdit::new(Position::new(0, 0)..Position::new(0, 1), label);
+        let items = ["format", "reform", "formula", "print"]
+            .into_iter()
+            .map(|label| CompletionItem::new(label, CompletionKind::Text, edit(label)))
+            .collect();
+        let mut session =
+            CompletionSession::new(items, Position::new(0, 0), Position::new(0, 1), "f");
+
+        let labels = |session: &CompletionSession| {
+            session
+                .visible_items()
+                .map(|item| item.label.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(labels(&session), vec!["format", "formula", "reform"]);
+
+        session.select_previous();
+        assert_eq!(session.selected_item().unwrap().label, "reform");
+        session.select_next();
+        assert_eq!(session.selected_item().unwrap().label, "format");
+
+        // Typing more narrows the list; prefix matches rank above substring matches
+        session.filter("FORM");
+        assert_eq!(labels(&session), vec!["format", "formula", "reform"]);
+        session.filter("formu");
+        assert_eq!(labels(&session), vec!["formula"]);
+
+        // The edit covers the text typed after triggering
+        let accepted = session.accept_edit(Position::new(0, 5)).unwrap();
+        assert_eq!(accepted.range, Position::new(0, 0)..Position::new(0, 5));
+        assert_eq!(accepted.new_text, "formula");
+
+        session.filter("xyz");
+        assert!(session.is_empty());
+        assert_eq!(session.selected_item(), None);
+    }
+}
@@ -58,6 +58,7 @@ fn visual_column_to_char(line: &str, visual_column: usize, tab_width: usize) ->
 
 /// Represents a position in the text buffer
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Position {
     pub line: usize,
     pub column: usize,
@@ -109,6 +110,18 @@ impl Position {
     }
 }
 
+/// How the cursor is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CursorStyle {
+    /// A thin bar between chars
+    #[default]
+    Line,
+    /// A box covering the char after the cursor
+    Block,
+    /// A bar under the char after the cursor
+    Underline,
+}
+
 /// Manages cursor state and movement
 #[derive(Debug, Clone, PartialEq)]
 pub struct Cursor {
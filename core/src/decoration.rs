@@ -0,0 +1,166 @@
+use crate::anchor::AnchorId;
+use crate::Position;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// RGBA color with components in the `0.0..=1.0` range
+///
+/// The core crate has no UI toolkit dependency, so front-ends convert this into
+/// their own color type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rgba {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Rgba {
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Create an opaque color
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Self::new(r, g, b, 1.0)
+    }
+}
+
+/// What a decoration represents, so related decorations can be cleared together
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DecorationKind {
+    Error,
+    Warning,
+    Info,
+    Hint,
+    BracketMatch,
+    SearchMatch,
+    Changed,
+    /// App-defined kind
+    Custom(String),
+}
+
+/// How a decorated range is painted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecorationShape {
+    Background,
+    Underline,
+    WavyUnderline,
+    Strikethrough,
+    Border,
+}
+
+/// Whether a decoration is painted beneath or above the text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecorationLayer {
+    #[default]
+    BelowText,
+    AboveText,
+}
+
+/// Visual style of a decoration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecorationStyle {
+    pub shape: DecorationShape,
+    pub color: Rgba,
+    pub layer: DecorationLayer,
+}
+
+impl DecorationStyle {
+    /// Create a style painted beneath the text
+    pub fn new(shape: DecorationShape, color: Rgba) -> Self {
+        Self {
+            shape,
+            color,
+            layer: DecorationLayer::BelowText,
+        }
+    }
+
+    /// Paint the decoration above the text instead
+    pub fn above_text(mut self) -> Self {
+        self.layer = DecorationLayer::AboveText;
+        self
+    }
+}
+
+/// Identifier of a decoration within its buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DecorationId(u64);
+
+/// A decoration whose range is tracked by a pair of buffer anchors
+#[derive(Debug, Clone)]
+pub(crate) struct Decoration {
+    pub kind: DecorationKind,
+    pub style: DecorationStyle,
+    pub start: AnchorId,
+    pub end: AnchorId,
+}
+
+/// A decoration resolved to its current range
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecorationSpan<'a> {
+    pub id: DecorationId,
+    pub kind: &'a DecorationKind,
+    pub style: DecorationStyle,
+    /// Range in char columns; the end is exclusive
+    pub range: Range<Position>,
+}
+
+impl DecorationSpan<'_> {
+    /// Check if the decoration covers any part of a line
+    pub fn touches_line(&self, line: usize) -> bool {
+        self.range.start.line <= line && line <= self.range.end.line
+    }
+}
+
+/// Decorations of a buffer, in the order they were added
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DecorationSet {
+    decorations: BTreeMap<DecorationId, Decoration>,
+    next_id: u64,
+    generation: u64,
+}
+
+impl DecorationSet {
+    pub fn insert(&mut self, decoration: Decoration) -> DecorationId {
+        let id = DecorationId(self.next_id);
+        self.next_id += 1;
+        self.generation += 1;
+        self.decorations.insert(id, decoration);
+        id
+    }
+
+    pub fn remove(&mut self, id: DecorationId) -> Option<Decoration> {
+        let removed = self.decorations.remove(&id);
+        if removed.is_some() {
+            self.generation += 1;
+        }
+        removed
+    }
+
+    /// Remove every decoration matching a predicate
+    pub fn drain_where(
+        &mut self,
+        mut predicate: impl FnMut(&Decoration) -> bool,
+    ) -> Vec<Decoration> {
+        let ids: Vec<DecorationId> = self
+            .decorations
+            .iter()
+            .filter(|(_, decoration)| predicate(decoration))
+            .map(|(id, _)| *id)
+            .collect();
+
+        ids.into_iter().filter_map(|id| self.remove(id)).collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (DecorationId, &Decoration)> {
+        self.decorations
+            .iter()
+            .map(|(id, decoration)| (*id, decoration))
+    }
+
+    /// Counter bumped whenever decorations are added or removed
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
@@ -0,0 +1,70 @@
+use crate::anchor::AnchorId;
+use crate::Position;
+use std::ops::Range;
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+/// A problem reported for a range of the buffer, e.g. by a compiler or linter
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Diagnostic {
+    /// Range in char columns; the end is exclusive
+    pub range: Range<Position>,
+    pub severity: DiagnosticSeverity,
+    pub message: String,
+    /// Tool that produced the diagnostic, e.g. `rustc`
+    pub source: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(
+        range: Range<Position>,
+        severity: DiagnosticSeverity,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            range,
+            severity,
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    /// Set the tool that produced the diagnostic
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Check if a position lies within the diagnostic
+    ///
+    /// Empty ranges contain the position they sit on, so they can still be hovered.
+    pub fn contains(&self, position: Position) -> bool {
+        range_contains(&self.range, position)
+    }
+}
+
+/// Check if a position lies within a diagnostic range, see [`Diagnostic::contains`]
+pub(crate) fn range_contains(range: &Range<Position>, position: Position) -> bool {
+    let after_start = (position.line, position.column) >= (range.start.line, range.start.column);
+    let before_end = (position.line, position.column) < (range.end.line, range.end.column);
+    after_start && (before_end || range.start == position)
+}
+
+/// A diagnostic whose range is tracked by a pair of buffer anchors
+#[derive(Debug, Clone)]
+pub(crate) struct AnchoredDiagnostic {
+    pub severity: DiagnosticSeverity,
+    pub message: String,
+    pub source: Option<String>,
+    pub start: AnchorId,
+    pub end: AnchorId,
+}
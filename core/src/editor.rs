@@ -1,7 +1,22 @@
+#[cfg(feature = "scripting")]
+use crate::scripting::{ScriptError, ScriptHost};
 use crate::{
+    completion::{
+        self, CompletionItem, CompletionProvider, CompletionSession, WordCompletionProvider,
+    },
+    inline_suggestion::{InlineSuggestion, InlineSuggestionProvider},
+    macros::{self, Macro},
     messages::{CursorMovement, EditorEvent, EditorResponse},
-    Buffer, Cursor, EditorMessage, Position, Selection,
+    middleware::{Middleware, MiddlewareAction},
+    snippet::{AnchoredRange, Snippet, SnippetSession},
+    tooltip::Tooltip,
+    virtual_text::{VirtualTextId, VirtualTextKind, VirtualTextPlacement},
+    Buffer, Cursor, EditorMessage, Gravity, Position, Selection, TextChange, TextEdit,
 };
+use std::collections::HashMap;
+use std::ops::Range;
+#[cfg(feature = "scripting")]
+use std::{cell::RefCell, rc::Rc};
 
 /// Main editor state and logic
 pub struct Editor {
@@ -9,6 +24,37 @@ pub struct Editor {
     cursor: Cursor,
     selection: Option<Selection>,
     clipboard: String,
+    search_results: Vec<Position>,
+    /// Char offsets of the search results, which edits move along with the text
+    search_offsets: Vec<usize>,
+    /// Buffer revision the search results are up to date with
+    search_revision: u64,
+    /// Pattern of the last search, whose matches `FindNext` selects
+    search_pattern: String,
+    /// Where the region started, while cursor movements extend it
+    mark: Option<Position>,
+    /// Whether the last message killed text, so the next kill appends to the clipboard
+    last_kill: bool,
+    /// Whether typed chars replace the char under the cursor
+    overwrite: bool,
+    completion_providers: Vec<Box<dyn CompletionProvider>>,
+    completion: Option<CompletionSession>,
+    snippet: Option<SnippetSession>,
+    inline_suggestion_provider: Option<Box<dyn InlineSuggestionProvider>>,
+    /// The shown suggestion and the virtual text drawing its first line
+    inline_suggestion: Option<(InlineSuggestion, VirtualTextId)>,
+    tooltip: Option<Tooltip>,
+    macros: HashMap<String, Macro>,
+    /// Name of the macro being recorded and the messages recorded so far
+    recording: Option<(String, Macro)>,
+    /// Macros being played, innermost last, so a macro cannot play itself
+    playing: Vec<String>,
+    middleware: Vec<Box<dyn Middleware>>,
+    /// Created when the first script is added; taken out while a script runs
+    #[cfg(feature = "scripting")]
+    scripts: Option<ScriptHost>,
+    #[cfg(feature = "scripting")]
+    max_script_operations: u64,
 }
 
 impl Editor {
@@ -19,6 +65,27 @@ impl Editor {
             cursor: Cursor::new(),
             selection: None,
             clipboard: String::new(),
+            search_results: Vec::new(),
+            search_offsets: Vec::new(),
+            search_revision: 0,
+            search_pattern: String::new(),
+            mark: None,
+            last_kill: false,
+            overwrite: false,
+            completion_providers: vec![Box::new(WordCompletionProvider::default())],
+            completion: None,
+            snippet: None,
+            inline_suggestion_provider: None,
+            inline_suggestion: None,
+            tooltip: None,
+            macros: HashMap::new(),
+            recording: None,
+            playing: Vec::new(),
+            middleware: Vec::new(),
+            #[cfg(feature = "scripting")]
+            scripts: None,
+            #[cfg(feature = "scripting")]
+            max_script_operations: crate::scripting::DEFAULT_MAX_SCRIPT_OPERATIONS,
         }
     }
 
@@ -29,6 +96,27 @@ impl Editor {
             cursor: Cursor::new(),
             selection: None,
             clipboard: String::new(),
+            search_results: Vec::new(),
+            search_offsets: Vec::new(),
+            search_revision: 0,
+            search_pattern: String::new(),
+            mark: None,
+            last_kill: false,
+            overwrite: false,
+            completion_providers: vec![Box::new(WordCompletionProvider::default())],
+            completion: None,
+            snippet: None,
+            inline_suggestion_provider: None,
+            inline_suggestion: None,
+            tooltip: None,
+            macros: HashMap::new(),
+            recording: None,
+            playing: Vec::new(),
+            middleware: Vec::new(),
+            #[cfg(feature = "scripting")]
+            scripts: None,
+            #[cfg(feature = "scripting")]
+            max_script_operations: crate::scripting::DEFAULT_MAX_SCRIPT_OPERATIONS,
         }
     }
 
@@ -37,6 +125,11 @@ impl Editor {
         &self.buffer
     }
 
+    /// Get the current buffer mutably, e.g. to create or remove anchors
+    pub fn current_buffer_mut(&mut self) -> &mut Buffer {
+        &mut self.buffer
+    }
+
     /// Get the current cursor
     pub fn current_cursor(&self) -> &Cursor {
         &self.cursor
@@ -47,6 +140,121 @@ impl Editor {
         self.selection.as_ref()
     }
 
+    /// Get where the mark was set, while it is active
+    pub fn mark(&self) -> Option<Position> {
+        self.mark
+    }
+
+    /// Get the matches found by the last search
+    pub fn search_results(&self) -> &[Position] {
+        &self.search_results
+    }
+
+    /// Get the open completion popup, if any
+    pub fn completion(&self) -> Option<&CompletionSession> {
+        self.completion.as_ref()
+    }
+
+    /// Add a source of completion items, asked after the existing ones
+    pub fn add_completion_provider(&mut self, provider: impl CompletionProvider + 'static) {
+        self.completion_providers.push(Box::new(provider));
+    }
+
+    /// Remove all completion providers, including the built-in word provider
+    pub fn clear_completion_providers(&mut self) {
+        self.completion_providers.clear();
+    }
+
+    /// Check if a snippet is being filled in, so Tab moves between its tab stops
+    pub fn is_snippet_active(&self) -> bool {
+        self.snippet.is_some()
+    }
+
+    /// Get the inline suggestion shown after the cursor, if any
+    pub fn inline_suggestion(&self) -> Option<&InlineSuggestion> {
+        self.inline_suggestion
+            .as_ref()
+            .map(|(suggestion, _)| suggestion)
+    }
+
+    /// Set the source of inline suggestions, asked after each typed char
+    pub fn set_inline_suggestion_provider(
+        &mut self,
+        provider: impl InlineSuggestionProvider + 'static,
+    ) {
+        self.inline_suggestion_provider = Some(Box::new(provider));
+    }
+
+    /// Check if typed chars overwrite the char under the cursor instead of inserting
+    pub fn is_overwrite(&self) -> bool {
+        self.overwrite
+    }
+
+    /// Get the name of the macro being recorded, if any
+    pub fn recording(&self) -> Option<&str> {
+        self.recording.as_ref().map(|(name, _)| name.as_str())
+    }
+
+    pub fn get_macro(&self, name: &str) -> Option<&Macro> {
+        self.macros.get(name)
+    }
+
+    /// Get every recorded or loaded macro by name, e.g. to save them
+    pub fn macros(&self) -> &HashMap<String, Macro> {
+        &self.macros
+    }
+
+    /// Add a macro, e.g. one loaded from a file, replacing one with the same name
+    pub fn set_macro(&mut self, name: impl Into<String>, recorded: Macro) {
+        self.macros.insert(name.into(), recorded);
+    }
+
+    /// Add hooks run around every handled message, after the existing ones
+    pub fn add_middleware(&mut self, middleware: impl Middleware + 'static) {
+        self.middleware.push(Box::new(middleware));
+    }
+
+    pub fn clear_middleware(&mut self) {
+        self.middleware.clear();
+    }
+
+    /// Compile a script that `RunScript` runs by name, replacing one with the same name
+    ///
+    /// Scripts see the editor as the `editor` constant: `text`, `line_count`,
+    /// `line(n)`, `cursor`, `selection` and `selected_text` read it, and `send`,
+    /// `insert`, `move_to` and `select` change it.
+    #[cfg(feature = "scripting")]
+    pub fn add_script(&mut self, name: &str, source: &str) -> Result<(), ScriptError> {
+        let max_operations = self.max_script_operations;
+        let host = self.scripts.get_or_insert_with(|| {
+            let mut host = ScriptHost::new();
+            host.set_max_operations(max_operations);
+            host
+        });
+        host.compile(name, source)
+    }
+
+    #[cfg(feature = "scripting")]
+    pub fn remove_script(&mut self, name: &str) -> bool {
+        self.scripts
+            .as_mut()
+            .is_some_and(|scripts| scripts.remove(name))
+    }
+
+    /// Set how many operations a script may run before it is stopped
+    #[cfg(feature = "scripting")]
+    pub fn set_max_script_operations(&mut self, operations: u64) {
+        self.max_script_operations = operations;
+        if let Some(scripts) = &mut self.scripts {
+            scripts.set_max_operations(operations);
+        }
+    }
+
+    /// Get the open tooltip, if any
+    pub fn tooltip(&self) -> Option<&Tooltip> {
+        self.tooltip.as_ref()
+    }
+
     /// Add an event handler (simplified version)
     pub fn add_event_handler<F>(&mut self, _handler: F)
     where
@@ -57,8 +265,116 @@ impl Editor {
     }
 
     /// Process an editor message and return the response
+    ///
+    /// The message goes through the middleware first, which may rewrite or veto it.
     pub fn handle_message(&mut self, message: EditorMessage) -> EditorResponse {
-        match message {
+        // Macros keep the original message, so replaying runs the middleware again
+        if let Some((_, recorded)) = &mut self.recording {
+            if macros::is_recordable(&message) {
+                recorded.messages.push(message.clone());
+            }
+        }
+
+        if self.middleware.is_empty() {
+            return self.dispatch_message(message);
+        }
+
+        // Hooks read the editor, so the middleware is taken out while they run. It is
+        // back in place while the message is handled, so replayed macros use it too.
+        let mut middleware = std::mem::take(&mut self.middleware);
+        let mut message = message;
+        for hooks in &mut middleware {
+            match hooks.before(self, message) {
+                MiddlewareAction::Continue(next) => message = next,
+                MiddlewareAction::Veto(reason) => {
+                    self.middleware = middleware;
+                    return EditorResponse::Error(reason);
+                }
+            }
+        }
+        self.middleware = middleware;
+
+        let revision_before = self.buffer.revision();
+        let response = self.dispatch_message(message.clone());
+        let changes: Vec<TextChange> = self
+            .buffer
+            .changes_since(revision_before)
+            .map(|changes| changes.cloned().collect())
+            .unwrap_or_default();
+
+        let mut middleware = std::mem::take(&mut self.middleware);
+        for hooks in &mut middleware {
+            hooks.after(self, &message, &response, &changes);
+        }
+        self.middleware = middleware;
+        response
+    }
+
+    fn dispatch_message(&mut self, message: EditorMessage) -> EditorResponse {
+        let is_completion_message = matches!(
+            message,
+            EditorMessage::TriggerCompletion
+                | EditorMessage::ShowCompletions(_)
+                | EditorMessage::SelectNextCompletion
+                | EditorMessage::SelectPreviousCompletion
+                | EditorMessage::AcceptCompletion
+                | EditorMessage::CancelCompletion
+        );
+
+        let is_snippet_message = matches!(
+            message,
+            EditorMessage::InsertSnippet(_)
+                | EditorMessage::NextTabStop
+                | EditorMessage::PreviousTabStop
+                | EditorMessage::EndSnippet
+        );
+        let is_inline_suggestion_message = matches!(
+            message,
+            EditorMessage::TriggerInlineSuggestion
+                | EditorMessage::ShowInlineSuggestion(_)
+                | EditorMessage::AcceptInlineSuggestion
+                | EditorMessage::AcceptInlineSuggestionWord
+                | EditorMessage::DismissInlineSuggestion
+        );
+        let typed = match &message {
+            EditorMessage::InsertChar(ch) => Some(ch.to_string()),
+            EditorMessage::InsertText(text) => Some(text.clone()),
+            _ => None,
+        };
+        let is_kill = matches!(
+            message,
+            EditorMessage::KillToLineEnd | EditorMessage::KillRegion
+        );
+        // Choosing a selection some other way ends the region
+        let clears_mark = matches!(
+            message,
+            EditorMessage::MoveCursorTo(_)
+                | EditorMessage::MoveCursorWithSelection(_)
+                | EditorMessage::StartSelection
+                | EditorMessage::SetSelection(_, _)
+                | EditorMessage::SelectAll
+                | EditorMessage::SelectLine
+                | EditorMessage::SelectWord
+                | EditorMessage::ClearSelection
+                | EditorMessage::Copy
+                | EditorMessage::FindNext
+                | EditorMessage::FindPrevious
+        );
+        let revision_before = self.buffer.revision();
+        let cursor_before = self.cursor.position();
+
+        // Undo and redo restore whole texts, which mirrors must not be re-applied over
+        if matches!(message, EditorMessage::Undo | EditorMessage::Redo) {
+            self.end_snippet();
+        }
+
+        // Typing in a tab stop and updating its mirrors is one undo step
+        let tracks_snippet = self.snippet.is_some() && !is_snippet_message;
+        if tracks_snippet {
+            self.buffer.begin_undo_group();
+        }
+
+        let response = match message {
             EditorMessage::InsertChar(ch) => self.handle_insert_char(ch),
             EditorMessage::InsertText(text) => self.handle_insert_text(text),
             EditorMessage::DeleteChar => self.handle_delete_char(),
@@ -69,6 +385,11 @@ impl Editor {
             EditorMessage::DeleteWordBackward => self.handle_delete_word_backward(),
             EditorMessage::DeleteToLineEnd => self.handle_delete_to_line_end(),
             EditorMessage::DeleteToLineStart => self.handle_delete_to_line_start(),
+            EditorMessage::ApplyEdits(edits) => self.handle_apply_edits(edits),
+            EditorMessage::ToggleOverwrite => {
+                self.overwrite = !self.overwrite;
+                EditorResponse::Success
+            }
 
             EditorMessage::MoveCursor(movement) => self.handle_cursor_movement(movement),
             EditorMessage::MoveCursorTo(position) => self.handle_move_cursor_to(position),
@@ -84,6 +405,10 @@ impl Editor {
             EditorMessage::SelectWord => self.handle_select_word(),
             EditorMessage::ClearSelection => self.handle_clear_selection(),
 
+            EditorMessage::SetMark => self.handle_set_mark(),
+            EditorMessage::KillToLineEnd => self.handle_kill_to_line_end(),
+            EditorMessage::KillRegion => self.handle_kill_region(),
+
             EditorMessage::Undo => self.handle_undo(),
             EditorMessage::Redo => self.handle_redo(),
             EditorMessage::Cut => self.handle_cut(),
@@ -91,37 +416,171 @@ impl Editor {
             EditorMessage::Paste => self.handle_paste(),
 
             EditorMessage::Find(pattern) => self.handle_find(pattern),
-            EditorMessage::FindNext => EditorResponse::Success,
-            EditorMessage::FindPrevious => EditorResponse::Success,
+            EditorMessage::FindNext => self.handle_find_next(true),
+            EditorMessage::FindPrevious => self.handle_find_next(false),
             EditorMessage::Replace(_, _) => EditorResponse::Success,
             EditorMessage::ReplaceAll(pattern, replacement) => {
                 self.handle_replace_all(pattern, replacement)
             }
 
+            EditorMessage::SetDiagnostics(diagnostics) => {
+                self.buffer.set_diagnostics(diagnostics);
+                EditorResponse::Success
+            }
+            EditorMessage::NextDiagnostic => self.handle_jump_to_diagnostic(true),
+            EditorMessage::PreviousDiagnostic => self.handle_jump_to_diagnostic(false),
+
+            EditorMessage::TriggerCompletion => self.handle_trigger_completion(),
+            EditorMessage::ShowCompletions(items) => self.show_completions(items),
+            EditorMessage::SelectNextCompletion => {
+                if let Some(session) = &mut self.completion {
+                    session.select_next();
+                }
+                EditorResponse::Success
+            }
+            EditorMessage::SelectPreviousCompletion => {
+                if let Some(session) = &mut self.completion {
+                    session.select_previous();
+                }
+                EditorResponse::Success
+            }
+            EditorMessage::AcceptCompletion => self.handle_accept_completion(),
+            EditorMessage::CancelCompletion => {
+                self.completion = None;
+                EditorResponse::Success
+            }
+
+            EditorMessage::InsertSnippet(source) => self.handle_insert_snippet(&source),
+            EditorMessage::NextTabStop => self.handle_jump_to_tab_stop(true),
+            EditorMessage::PreviousTabStop => self.handle_jump_to_tab_stop(false),
+            EditorMessage::EndSnippet => {
+                self.end_snippet();
+                EditorResponse::Success
+            }
+
+            EditorMessage::TriggerInlineSuggestion => {
+                self.request_inline_suggestion();
+                EditorResponse::Success
+            }
+            EditorMessage::ShowInlineSuggestion(text) => {
+                self.show_inline_suggestion(text);
+                EditorResponse::Success
+            }
+            EditorMessage::AcceptInlineSuggestion => self.handle_accept_inline_suggestion(false),
+            EditorMessage::AcceptInlineSuggestionWord => self.handle_accept_inline_suggestion(true),
+            EditorMessage::DismissInlineSuggestion => {
+                self.dismiss_inline_suggestion();
+                EditorResponse::Success
+            }
+
+            EditorMessage::ShowTooltip(tooltip) => {
+                self.tooltip = Some(tooltip);
+                EditorResponse::Success
+            }
+            EditorMessage::HideTooltip => {
+                self.tooltip = None;
+                EditorResponse::Success
+            }
+
+            EditorMessage::StartRecording(name) => {
+                self.recording = Some((name, Macro::default()));
+                EditorResponse::Success
+            }
+            EditorMessage::StopRecording => {
+                if let Some((name, recorded)) = self.recording.take() {
+                    self.macros.insert(name, recorded);
+                }
+                EditorResponse::Success
+            }
+            EditorMessage::PlayMacro { name, count } => self.handle_play_macro(name, count),
+
+            EditorMessage::RunScript(name) => self.handle_run_script(&name),
+
             EditorMessage::ScrollToLine(line) => self.handle_scroll_to_line(line),
+        };
+
+        if tracks_snippet {
+            self.refresh_snippet();
+            self.buffer.end_undo_group();
+        }
+
+        // Keep the completion popup in step with typing and cursor movement
+        if !is_completion_message {
+            self.refresh_completion();
+        }
+
+        let edited = self.buffer.revision() != revision_before;
+        // Matches of the last search follow the text, so they never point past it
+        if edited {
+            self.refresh_search_results();
         }
+        if clears_mark || edited {
+            self.mark = None;
+        }
+
+        let edited_or_moved = edited || self.cursor.position() != cursor_before;
+
+        // Only an unbroken run of kills collects into the clipboard
+        if is_kill {
+            self.last_kill = true;
+        } else if edited_or_moved || clears_mark {
+            self.last_kill = false;
+        }
+        if edited_or_moved {
+            self.tooltip = None;
+        }
+
+        // Any other edit or cursor movement dismisses the inline suggestion
+        if !is_inline_suggestion_message {
+            match typed {
+                Some(typed) => self.refresh_inline_suggestion(&typed),
+                None if edited_or_moved => self.dismiss_inline_suggestion(),
+                None => {}
+            }
+        }
+
+        response
     }
 
     // Text manipulation handlers
     fn handle_insert_char(&mut self, ch: char) -> EditorResponse {
-        let position = self.cursor.position();
-
+        let mut overwrite = self.overwrite;
         // Delete selection if exists
         if let Some(selection) = self.selection.take() {
             if !selection.is_empty() {
                 let _ = self.buffer.delete_selection(&selection, &mut self.cursor);
+                // Typing over a selection only replaces the selection
+                overwrite = false;
             }
         }
 
-        match self.buffer.insert_char(position, ch, &mut self.cursor) {
+        let position = self.cursor.position();
+
+        // Overwriting replaces the char under the cursor, but never a line break
+        let overwrites = overwrite
+            && ch != '\n'
+            && self
+                .buffer
+                .rope()
+                .get_line(position.line)
+                .and_then(|line| line.get_char(position.column))
+                .is_some_and(|next| next != '\n' && next != '\r');
+
+        // Undo restores the overwritten char together with removing the typed one
+        self.buffer.begin_undo_group();
+        if overwrites {
+            let _ = self.buffer.delete_char(position, &mut self.cursor);
+        }
+        let result = self.buffer.insert_char(position, ch, &mut self.cursor);
+        self.buffer.end_undo_group();
+
+        match result {
             Ok(_) => EditorResponse::TextChanged,
             Err(e) => EditorResponse::Error(e.to_string()),
         }
     }
 
     fn handle_insert_text(&mut self, text: String) -> EditorResponse {
-        let position = self.cursor.position();
-
         // Delete selection if exists
         if let Some(selection) = self.selection.take() {
             if !selection.is_empty() {
@@ -129,6 +588,8 @@ impl Editor {
             }
         }
 
+        let position = self.cursor.position();
+
         match self.buffer.insert_text(position, &text, &mut self.cursor) {
             Ok(_) => EditorResponse::TextChanged,
             Err(e) => EditorResponse::Error(e.to_string()),
@@ -307,6 +768,13 @@ impl Editor {
         };
 
         if moved {
+            // With the mark set, movements select from it
+            if let Some(mark) = self.mark {
+                let selection = Selection::from_positions(mark, self.cursor.position());
+                self.selection = Some(selection.clone());
+                return EditorResponse::SelectionChanged(Some(selection));
+            }
+
             // Clear any existing selection when moving cursor without extending selection
             let selection_cleared = self.selection.is_some();
             if selection_cleared {
@@ -481,6 +949,71 @@ impl Editor {
         EditorResponse::SelectionChanged(None)
     }
 
+    // Mark and kill handlers
+    fn handle_set_mark(&mut self) -> EditorResponse {
+        let position = self.cursor.position();
+        self.mark = Some(position);
+        let selection = Selection::new(position, position);
+        self.selection = Some(selection.clone());
+        EditorResponse::SelectionChanged(Some(selection))
+    }
+
+    fn handle_kill_to_line_end(&mut self) -> EditorResponse {
+        let rope = self.buffer.rope();
+        let position = self.cursor.position();
+        let line_start = rope.line_to_char(position.line);
+        let start = line_start + position.column;
+        let next_line = if position.line + 1 < rope.len_lines() {
+            rope.line_to_char(position.line + 1)
+        } else {
+            rope.len_chars()
+        };
+        // The text of the line stops before its `\n` or `\r\n` line break
+        let mut line_end = next_line;
+        for line_break in ['\n', '\r'] {
+            if line_end > line_start && rope.char(line_end - 1) == line_break {
+                line_end -= 1;
+            }
+        }
+        // At the end of a line, the line break is killed instead
+        let (end, end_position) = if start < line_end {
+            (
+                line_end,
+                Position::new(position.line, line_end - line_start),
+            )
+        } else if start < next_line {
+            (next_line, Position::new(position.line + 1, 0))
+        } else {
+            return EditorResponse::Success;
+        };
+
+        let text = rope.slice(start..end).to_string();
+        self.kill(text);
+        self.handle_apply_edits(vec![TextEdit::new(position..end_position, "")])
+    }
+
+    fn handle_kill_region(&mut self) -> EditorResponse {
+        match self.selection.take() {
+            Some(selection) if !selection.is_empty() => {
+                self.kill(selection.get_text(self.buffer.rope()));
+                match self.buffer.delete_selection(&selection, &mut self.cursor) {
+                    Ok(_) => EditorResponse::TextChanged,
+                    Err(e) => EditorResponse::Error(e.to_string()),
+                }
+            }
+            _ => EditorResponse::Success,
+        }
+    }
+
+    /// Put killed text in the clipboard, after the text of the kill right before it
+    fn kill(&mut self, text: String) {
+        if self.last_kill {
+            self.clipboard.push_str(&text);
+        } else {
+            self.clipboard = text;
+        }
+    }
+
     // Edit operation handlers
     fn handle_undo(&mut self) -> EditorResponse {
         match self.buffer.undo(&mut self.cursor) {
@@ -535,9 +1068,122 @@ impl Editor {
     // Search handlers
     fn handle_find(&mut self, pattern: String) -> EditorResponse {
         let results = self.buffer.find(&pattern);
+        let rope = self.buffer.rope();
+        self.search_offsets = results
+            .iter()
+            .map(|position| rope.line_to_char(position.line) + position.column)
+            .collect();
+        self.search_results = results.clone();
+        self.search_revision = self.buffer.revision();
+        self.search_pattern = pattern;
         EditorResponse::SearchResult(results)
     }
 
+    /// Bring the matches of the last search up to date with the buffer
+    ///
+    /// Matches away from the changes move along with the text; only the lines the
+    /// changes touched are searched again.
+    fn refresh_search_results(&mut self) {
+        let revision = self.buffer.revision();
+        if self.search_pattern.is_empty() || self.search_revision == revision {
+            return;
+        }
+        let since = std::mem::replace(&mut self.search_revision, revision);
+
+        let Some(changes) = self.buffer.changes_since(since) else {
+            // Too many changes to follow, so search the whole text
+            let pattern = std::mem::take(&mut self.search_pattern);
+            self.handle_find(pattern);
+            return;
+        };
+        let pattern_len = self.search_pattern.chars().count();
+        let mut offsets = std::mem::take(&mut self.search_offsets);
+        let mut touched: Vec<Range<usize>> = Vec::new();
+        for change in changes {
+            let old = change.old_range.clone();
+            let new_end = change.new_range.end;
+            let moved = |offset: usize| {
+                if offset <= old.start {
+                    offset
+                } else if offset >= old.end {
+                    offset - old.end + new_end
+                } else {
+                    old.start
+                }
+            };
+
+            // Matches overlapping the replaced text are gone
+            offsets.retain(|&start| start + pattern_len <= old.start || start >= old.end);
+            for start in &mut offsets {
+                *start = moved(*start);
+            }
+            for range in &mut touched {
+                *range = moved(range.start)..moved(range.end);
+            }
+            touched.push(change.new_range.clone());
+        }
+
+        // Matches never span lines, so searching the touched lines finds every new one
+        let rope = self.buffer.rope();
+        for range in touched {
+            let lines = rope.char_to_line(range.start)..rope.char_to_line(range.end) + 1;
+            let start = rope.line_to_char(lines.start);
+            let end = rope.line_to_char(lines.end.min(rope.len_lines()));
+            offsets.retain(|&offset| offset < start || offset >= end);
+            offsets.extend(
+                self.buffer
+                    .find_in_lines(&self.search_pattern, lines)
+                    .into_iter()
+                    .map(|position| rope.line_to_char(position.line) + position.column),
+            );
+        }
+        offsets.sort_unstable();
+        offsets.dedup();
+
+        self.search_results = offsets
+            .iter()
+            .map(|&offset| {
+                let line = rope.char_to_line(offset);
+                Position::new(line, offset - rope.line_to_char(line))
+            })
+            .collect();
+        self.search_offsets = offsets;
+    }
+
+    /// Select the next or previous match of the last search, wrapping around
+    fn handle_find_next(&mut self, forward: bool) -> EditorResponse {
+        if self.search_pattern.is_empty() {
+            return EditorResponse::Success;
+        }
+        // The buffer can be edited without going through the editor
+        self.refresh_search_results();
+
+        let cursor = self.cursor.position();
+        let cursor_key = (cursor.line, cursor.column);
+        let key = |position: &&Position| (position.line, position.column);
+        let found = if forward {
+            let mut after = self.search_results.iter().filter(|m| key(m) >= cursor_key);
+            after.next().or_else(|| self.search_results.first())
+        } else {
+            let mut before = self.search_results.iter().filter(|m| key(m) < cursor_key);
+            before.next_back().or_else(|| self.search_results.last())
+        };
+        let Some(&start) = found else {
+            return EditorResponse::Success;
+        };
+
+        let end = Position::new(
+            start.line,
+            start.column + self.search_pattern.chars().count(),
+        );
+        // Searching backwards leaves the cursor at the start of the match
+        if forward {
+            self.handle_set_selection(start, end)
+        } else {
+            self.handle_set_selection(end, start)
+        }
+    }
+
     fn handle_replace_all(&mut self, pattern: String, replacement: String) -> EditorResponse {
         match self
             .buffer
@@ -548,12 +1194,424 @@ impl Editor {
         }
     }
 
+    fn handle_apply_edits(&mut self, edits: Vec<TextEdit>) -> EditorResponse {
+        if edits.is_empty() {
+            return EditorResponse::Success;
+        }
+
+        self.selection = None;
+        match self.buffer.apply_edits(&edits, &mut self.cursor) {
+            Ok(_) => EditorResponse::TextChanged,
+            Err(e) => EditorResponse::Error(e.to_string()),
+        }
+    }
+
+    fn handle_jump_to_diagnostic(&mut self, forward: bool) -> EditorResponse {
+        let cursor = self.cursor.position();
+        let cursor_key = (cursor.line, cursor.column);
+        let starts: Vec<Position> = self
+            .buffer
+            .diagnostics()
+            .into_iter()
+            .map(|diagnostic| diagnostic.range.start)
+            .collect();
+
+        let target = if forward {
+            starts
+                .iter()
+                .find(|start| (start.line, start.column) > cursor_key)
+                .or(starts.first())
+        } else {
+            starts
+                .iter()
+                .rev()
+                .find(|start| (start.line, start.column) < cursor_key)
+                .or(starts.last())
+        };
+
+        match target {
+            Some(&position) => self.handle_move_cursor_to(position),
+            None => EditorResponse::Success,
+        }
+    }
+
+    fn handle_play_macro(&mut self, name: String, count: usize) -> EditorResponse {
+        let Some(recorded) = self.macros.get(&name).cloned() else {
+            return EditorResponse::Error(format!("Unknown macro {name:?}"));
+        };
+        if self.playing.contains(&name) {
+            return EditorResponse::Error(format!("Macro {name:?} plays itself"));
+        }
+
+        self.playing.push(name);
+        self.buffer.begin_undo_group();
+        let mut response = EditorResponse::Success;
+        // Stop at the first failing message, as later ones may depend on it
+        'replay: for _ in 0..count {
+            for message in &recorded.messages {
+                if let EditorResponse::Error(error) = self.handle_message(message.clone()) {
+                    response = EditorResponse::Error(error);
+                    break 'replay;
+                }
+            }
+        }
+        self.buffer.end_undo_group();
+        self.playing.pop();
+        response
+    }
+
+    #[cfg(feature = "scripting")]
+    fn handle_run_script(&mut self, name: &str) -> EditorResponse {
+        // Missing while a script runs, so scripts cannot start other scripts
+        let Some(scripts) = self.scripts.take() else {
+            return EditorResponse::Error(format!("Unknown script {name:?}"));
+        };
+
+        // Scripts keep a handle to the editor, so it moves behind one while they run
+        let revision_before = self.buffer.revision();
+        let editor = Rc::new(RefCell::new(std::mem::take(self)));
+        editor.borrow_mut().buffer.begin_undo_group();
+        let result = scripts.run(name, editor.clone());
+        *self = std::mem::take(&mut *editor.borrow_mut());
+        self.buffer.end_undo_group();
+        self.scripts = Some(scripts);
+
+        match result {
+            Err(error) => EditorResponse::Error(error),
+            Ok(()) if self.buffer.revision() != revision_before => EditorResponse::TextChanged,
+            Ok(()) => EditorResponse::Success,
+        }
+    }
+
+    #[cfg(not(feature = "scripting"))]
+    fn handle_run_script(&mut self, name: &str) -> EditorResponse {
+        EditorResponse::Error(format!(
+            "Cannot run script {name:?} without the scripting feature"
+        ))
+    }
+
     fn handle_scroll_to_line(&mut self, line: usize) -> EditorResponse {
         let position = Position::new(line, 0);
         self.cursor.set_position(position);
         EditorResponse::CursorMoved(position)
     }
 
+    fn handle_trigger_completion(&mut self) -> EditorResponse {
+        let items = self
+            .completion_providers
+            .iter()
+            .flat_map(|provider| provider.complete(&self.buffer, &self.cursor))
+            .collect();
+        self.show_completions(items)
+    }
+
+    /// Open the completion popup for the word at the cursor
+    fn show_completions(&mut self, items: Vec<CompletionItem>) -> EditorResponse {
+        let cursor = self.cursor.position();
+        let word_start = completion::word_start(self.buffer.rope(), cursor);
+        let query = self.completion_query(word_start).unwrap_or_default();
+
+        let session = CompletionSession::new(items, word_start, cursor, &query);
+        self.completion = (!session.is_empty()).then_some(session);
+        EditorResponse::Success
+    }
+
+    fn handle_accept_completion(&mut self) -> EditorResponse {
+        let edit = self
+            .completion
+            .take()
+            .and_then(|session| session.accept_edit(self.cursor.position()));
+
+        match edit {
+            Some(edit) => self.handle_apply_edits(vec![edit]),
+            None => EditorResponse::Success,
+        }
+    }
+
+    /// Re-filter the completion popup, closing it once the cursor leaves the word
+    fn refresh_completion(&mut self) {
+        let Some(word_start) = self.completion.as_ref().map(CompletionSession::word_start) else {
+            return;
+        };
+
+        match self.completion_query(word_start) {
+            Some(query) if query.chars().all(completion::is_completion_char) => {
+                if let Some(session) = &mut self.completion {
+                    session.filter(&query);
+                    if session.is_empty() {
+                        self.completion = None;
+                    }
+                }
+            }
+            _ => self.completion = None,
+        }
+    }
+
+    /// Get the text between `word_start` and the cursor, if the cursor is after it on the same line
+    fn completion_query(&self, word_start: Position) -> Option<String> {
+        let cursor = self.cursor.position();
+        if cursor.line != word_start.line || cursor.column < word_start.column {
+            return None;
+        }
+
+        let line = self.buffer.line_text(cursor.line)?;
+        Some(
+            line.chars()
+                .skip(word_start.column)
+                .take(cursor.column - word_start.column)
+                .collect(),
+        )
+    }
+
+    /// Ask the provider for a suggestion at the cursor, replacing the shown one
+    fn request_inline_suggestion(&mut self) {
+        let text = self
+            .inline_suggestion_provider
+            .as_ref()
+            .and_then(|provider| provider.suggest(&self.buffer, &self.cursor));
+
+        match text {
+            Some(text) => self.show_inline_suggestion(text),
+            None => self.dismiss_inline_suggestion(),
+        }
+    }
+
+    fn show_inline_suggestion(&mut self, text: String) {
+        self.dismiss_inline_suggestion();
+        if text.is_empty() {
+            return;
+        }
+
+        let position = self.cursor.position();
+        let suggestion = InlineSuggestion::new(position, text);
+        let first_line = suggestion.lines().next().unwrap_or_default().to_string();
+        let id = self.buffer.add_virtual_text(
+            position,
+            first_line,
+            VirtualTextKind::InlineSuggestion,
+            VirtualTextPlacement::Inline,
+        );
+        self.inline_suggestion = Some((suggestion, id));
+    }
+
+    fn dismiss_inline_suggestion(&mut self) {
+        if let Some((_, id)) = self.inline_suggestion.take() {
+            self.buffer.remove_virtual_text(id);
+        }
+    }
+
+    /// Insert all of the inline suggestion, or its next word, as one undo step
+    fn handle_accept_inline_suggestion(&mut self, word_only: bool) -> EditorResponse {
+        let Some(suggestion) = self.inline_suggestion().cloned() else {
+            return EditorResponse::Success;
+        };
+
+        let accepted = if word_only {
+            suggestion.next_word().to_string()
+        } else {
+            suggestion.text.clone()
+        };
+        self.dismiss_inline_suggestion();
+
+        self.selection = None;
+        self.cursor.set_position(suggestion.position);
+        let response = self.handle_insert_text(accepted.clone());
+
+        // The rest of a partly accepted suggestion stays up at the new cursor
+        if let Some(rest) = suggestion.text.strip_prefix(&accepted) {
+            self.show_inline_suggestion(rest.to_string());
+        }
+        response
+    }
+
+    /// Keep the inline suggestion while the user types what it suggests
+    ///
+    /// Typing anything else asks the provider for a new suggestion.
+    fn refresh_inline_suggestion(&mut self, typed: &str) {
+        let continued = self.inline_suggestion().and_then(|suggestion| {
+            let rest = suggestion.text.strip_prefix(typed)?;
+            let typed_end = self
+                .buffer
+                .rope()
+                .len_chars()
+                .min(self.buffer.char_offset(suggestion.position) + typed.chars().count());
+            (self.buffer.char_offset(self.cursor.position()) == typed_end).then(|| rest.to_string())
+        });
+
+        match continued {
+            Some(rest) => self.show_inline_suggestion(rest),
+            None => self.request_inline_suggestion(),
+        }
+    }
+
+    fn handle_insert_snippet(&mut self, source: &str) -> EditorResponse {
+        let snippet = match Snippet::parse(source) {
+            Ok(snippet) => snippet,
+            Err(e) => return EditorResponse::Error(e.to_string()),
+        };
+        self.end_snippet();
+
+        self.buffer.begin_undo_group();
+        if let Some(selection) = self.selection.take() {
+            let _ = self.buffer.delete_selection(&selection, &mut self.cursor);
+        }
+
+        // Continuation lines get the indentation of the line the snippet starts on
+        let position = self.cursor.position();
+        let indent: String = self
+            .buffer
+            .line_text(position.line)
+            .unwrap_or_default()
+            .chars()
+            .take(position.column)
+            .take_while(|ch| *ch == ' ' || *ch == '\t')
+            .collect();
+        let snippet = snippet.indented(&indent);
+
+        let base = self.buffer.char_offset(position);
+        if let Err(e) = self
+            .buffer
+            .insert_text(position, &snippet.text, &mut self.cursor)
+        {
+            self.buffer.end_undo_group();
+            return EditorResponse::Error(e.to_string());
+        }
+
+        let stops = snippet
+            .tab_stops
+            .iter()
+            .map(|stop| {
+                stop.ranges
+                    .iter()
+                    .map(|range| AnchoredRange {
+                        start: self
+                            .buffer
+                            .create_anchor_at_offset(base + range.start, Gravity::Left),
+                        end: self
+                            .buffer
+                            .create_anchor_at_offset(base + range.end, Gravity::Right),
+                    })
+                    .collect()
+            })
+            .collect();
+        self.snippet = Some(SnippetSession { stops, current: 0 });
+
+        // Fill in the mirrors of every tab stop from their first occurrence
+        let stop_count = self
+            .snippet
+            .as_ref()
+            .map_or(0, |session| session.stops.len());
+        for stop in 0..stop_count {
+            self.sync_snippet_mirrors(stop);
+        }
+        self.buffer.end_undo_group();
+
+        self.select_tab_stop();
+        EditorResponse::TextChanged
+    }
+
+    fn handle_jump_to_tab_stop(&mut self, forward: bool) -> EditorResponse {
+        let Some(session) = &mut self.snippet else {
+            return EditorResponse::Success;
+        };
+
+        if forward {
+            session.current = (session.current + 1).min(session.stops.len() - 1);
+        } else {
+            session.current = session.current.saturating_sub(1);
+        }
+        self.select_tab_stop();
+        EditorResponse::CursorMoved(self.cursor.position())
+    }
+
+    /// Select the text of the current tab stop, ending the snippet once `$0` is reached
+    fn select_tab_stop(&mut self) {
+        let Some(session) = &self.snippet else {
+            return;
+        };
+        let is_last = session.is_at_last_stop();
+        let Some(range) = self.snippet_range(session.current_stop()[0]) else {
+            self.end_snippet();
+            return;
+        };
+
+        self.cursor.set_position(range.end);
+        self.selection = (range.start != range.end).then(|| Selection::new(range.start, range.end));
+
+        if is_last {
+            self.end_snippet();
+        }
+    }
+
+    /// Keep mirrors in step with the current tab stop, ending the snippet once the cursor leaves it
+    fn refresh_snippet(&mut self) {
+        let Some(session) = &self.snippet else {
+            return;
+        };
+        let current = session.current;
+        let Some(range) = self.snippet_range(session.current_stop()[0]) else {
+            self.end_snippet();
+            return;
+        };
+
+        let cursor = self.cursor.position();
+        let cursor_key = (cursor.line, cursor.column);
+        if cursor_key < (range.start.line, range.start.column)
+            || cursor_key > (range.end.line, range.end.column)
+        {
+            self.end_snippet();
+            return;
+        }
+
+        self.sync_snippet_mirrors(current);
+    }
+
+    /// Copy the text of a tab stop's first occurrence into its other occurrences
+    fn sync_snippet_mirrors(&mut self, stop: usize) {
+        let Some(ranges) = self
+            .snippet
+            .as_ref()
+            .and_then(|session| session.stops.get(stop))
+        else {
+            return;
+        };
+
+        let rope = self.buffer.rope();
+        let text_of = |range: &Range<Position>| {
+            let start = self.buffer.char_offset(range.start);
+            let end = self.buffer.char_offset(range.end).max(start);
+            rope.slice(start..end).to_string()
+        };
+        let Some(primary) = self.snippet_range(ranges[0]).map(|range| text_of(&range)) else {
+            return;
+        };
+
+        let edits: Vec<TextEdit> = ranges[1..]
+            .iter()
+            .filter_map(|mirror| self.snippet_range(*mirror))
+            .filter(|range| text_of(range) != primary)
+            .map(|range| TextEdit::new(range, primary.clone()))
+            .collect();
+        if !edits.is_empty() {
+            let _ = self.buffer.apply_edits(&edits, &mut self.cursor);
+        }
+    }
+
+    /// Resolve a tab stop occurrence into positions
+    fn snippet_range(&self, range: AnchoredRange) -> Option<Range<Position>> {
+        let start = self.buffer.anchor_position(range.start)?;
+        let end = self.buffer.anchor_position(range.end)?;
+        Some(start..end)
+    }
+
+    fn end_snippet(&mut self) {
+        if let Some(session) = self.snippet.take() {
+            for anchor in session.anchors() {
+                self.buffer.remove_anchor(anchor);
+            }
+        }
+    }
+
     /// Get clipboard content
     pub fn clipboard(&self) -> &str {
         &self.clipboard
@@ -561,16 +1619,30 @@ impl Editor {
 
     /// Clear the editor content
     pub fn clear(&mut self) {
-        self.buffer = Buffer::new();
+        self.buffer.reset("");
         self.cursor = Cursor::new();
         self.selection = None;
+        self.search_results.clear();
+        self.search_offsets.clear();
+        self.search_pattern.clear();
+        self.completion = None;
+        self.end_snippet();
+        self.dismiss_inline_suggestion();
+        self.tooltip = None;
     }
 
     /// Set the editor content
     pub fn set_text(&mut self, text: &str) {
-        self.buffer = Buffer::from_text(text);
+        self.buffer.reset(text);
         self.cursor = Cursor::new();
         self.selection = None;
+        self.search_results.clear();
+        self.search_offsets.clear();
+        self.search_pattern.clear();
+        self.completion = None;
+        self.end_snippet();
+        self.dismiss_inline_suggestion();
+        self.tooltip = None;
     }
 
     /// Handle key input from widgets - simplified interface
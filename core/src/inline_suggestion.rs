@@ -0,0 +1,75 @@
+use crate::completion::is_completion_char;
+use crate::{Buffer, Cursor, Position};
+
+/// Source of ghost text suggestions shown after the cursor
+///
+/// Providers are asked after each typed char and when a suggestion is triggered
+/// explicitly. Suggestions may span several lines.
+pub trait InlineSuggestionProvider {
+    fn suggest(&self, buffer: &Buffer, cursor: &Cursor) -> Option<String>;
+}
+
+/// A suggestion shown as faded text after the cursor until it is accepted or dismissed
+#[derive(Debug, Clone, PartialEq)]
+pub struct InlineSuggestion {
+    /// Where the suggestion would be inserted
+    pub position: Position,
+    pub text: String,
+}
+
+impl InlineSuggestion {
+    pub fn new(position: Position, text: impl Into<String>) -> Self {
+        Self {
+            position,
+            text: text.into(),
+        }
+    }
+
+    /// Get the part of the suggestion up to the end of its next word
+    ///
+    /// Leading whitespace is included, so accepting word by word also accepts
+    /// indentation and line breaks.
+    pub fn next_word(&self) -> &str {
+        let word_start = self
+            .text
+            .find(|ch: char| !ch.is_whitespace())
+            .unwrap_or(self.text.len());
+        let rest = &self.text[word_start..];
+
+        let word_len = match rest.chars().next() {
+            Some(first) if is_completion_char(first) => rest
+                .find(|ch: char| !is_completion_char(ch))
+                .unwrap_or(rest.len()),
+            // Punctuation is accepted one char at a time
+            Some(first) => first.len_utf8(),
+            None => 0,
+        };
+        &self.text[..word_start + word_len]
+    }
+
+    /// Get the lines of the suggestion; the first one continues the cursor line
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.text.split('\n')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_word() {
+        let suggestion = InlineSuggestion::new(Position::new(0, 0), "count + 1)\n    done");
+        assert_eq!(suggestion.next_word(), "count");
+
+        let suggestion = InlineSuggestion::new(Position::new(0, 0), " + 1)");
+        assert_eq!(suggestion.next_word(), " +");
+
+        let suggestion = InlineSuggestion::new(Position::new(0, 0), "\n    done()");
+        assert_eq!(suggestion.next_word(), "\n    done");
+        assert_eq!(
+            suggestion.lines().collect::<Vec<_>>(),
+            vec!["", "    done()"]
+        );
+    }
+}
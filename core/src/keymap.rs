@@ -0,0 +1,228 @@
+use crate::{EditorMessage, KeyBinding, KeyCondition, Shortcut, ShortcutParseError};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use thiserror::Error;
+
+/// Key bindings read from a file, to be merged over the defaults
+///
+/// Any self-describing serde format works, e.g. JSON:
+///
+/// ```json
+/// { "bindings": [
+///     { "keys": "ctrl+k ctrl+u", "command": "DeleteLine" },
+///     { "keys": "alt+down", "command": { "MoveCursor": "PageDown" }, "when": "!completionOpen" }
+/// ] }
+/// ```
+///
+/// or TOML with `[[bindings]]` tables.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Keymap {
+    #[serde(default)]
+    pub bindings: Vec<KeymapEntry>,
+}
+
+/// A binding as written in a keymap file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeymapEntry {
+    /// Shortcuts separated by spaces, e.g. `ctrl+k ctrl+u`
+    pub keys: String,
+    /// An [`EditorMessage`], e.g. `"Undo"` or `{ "MoveCursor": "WordLeft" }`
+    pub command: serde_json::Value,
+    /// Condition such as `hasSelection && !readOnly`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// What is wrong with a keymap entry
+#[derive(Debug, Error, PartialEq)]
+pub enum KeymapErrorKind {
+    #[error(transparent)]
+    Keys(#[from] ShortcutParseError),
+    #[error("Unknown command {command}: {reason}")]
+    UnknownCommand { command: String, reason: String },
+    #[error("Invalid condition {0:?}")]
+    InvalidCondition(String),
+}
+
+/// An invalid keymap entry
+#[derive(Debug, Error, PartialEq)]
+#[error("Keymap entry {index} ({keys:?}): {kind}")]
+pub struct KeymapError {
+    /// Index of the entry in the keymap
+    pub index: usize,
+    pub keys: String,
+    pub kind: KeymapErrorKind,
+}
+
+/// A valid keymap entry that may not behave as intended
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeymapWarning {
+    /// Two entries bind the same keys under the same condition; the later one wins
+    Duplicate {
+        keys: Vec<Shortcut>,
+        first: usize,
+        second: usize,
+    },
+    /// A binding is the start of a longer chord, so it only runs once the chord
+    /// times out or is interrupted
+    ShadowedByChord {
+        keys: Vec<Shortcut>,
+        chord: Vec<Shortcut>,
+    },
+}
+
+impl fmt::Display for KeymapWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Duplicate {
+                keys,
+                first,
+                second,
+            } => write!(
+                f,
+                "{} is bound by entries {} and {}; the later one wins",
+                describe_keys(keys),
+                first,
+                second
+            ),
+            Self::ShadowedByChord { keys, chord } => write!(
+                f,
+                "{} waits for the chord {} before running",
+                describe_keys(keys),
+                describe_keys(chord)
+            ),
+        }
+    }
+}
+
+fn describe_keys(keys: &[Shortcut]) -> String {
+    let keys: Vec<String> = keys.iter().map(ToString::to_string).collect();
+    keys.join(" ")
+}
+
+impl Keymap {
+    /// Parse a keymap from JSON
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Validate every entry, turning them into key bindings
+    pub fn bindings(&self) -> Result<Vec<KeyBinding>, Vec<KeymapError>> {
+        let mut bindings = Vec::new();
+        let mut errors = Vec::new();
+
+        for (index, entry) in self.bindings.iter().enumerate() {
+            match entry.binding() {
+                Ok(binding) => bindings.push(binding),
+                Err(kind) => errors.push(KeymapError {
+                    index,
+                    keys: entry.keys.clone(),
+                    kind,
+                }),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(bindings)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl KeymapEntry {
+    fn binding(&self) -> Result<KeyBinding, KeymapErrorKind> {
+        let keys = Shortcut::parse_sequence(&self.keys)?;
+        let message: EditorMessage =
+            serde_json::from_value(self.command.clone()).map_err(|error| {
+                KeymapErrorKind::UnknownCommand {
+                    command: self.command.to_string(),
+                    reason: error.to_string(),
+                }
+            })?;
+        let when = match &self.when {
+            Some(when) => when
+                .parse()
+                .map_err(|_| KeymapErrorKind::InvalidCondition(when.clone()))?,
+            None => KeyCondition::Always,
+        };
+
+        let description = self.description.as_deref().unwrap_or_default();
+        Ok(KeyBinding::chord(keys, message, description).when(when))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CursorMovement, ShortcutManager};
+
+    #[test]
+    fn test_load_keymap() {
+        let keymap = Keymap::from_json(
+            r#"{ "bindings": [
+                { "keys": "ctrl+k ctrl+c", "command": "DeleteLine" },
+                { "keys": "alt+down", "command": { "MoveCursor": "PageDown" }, "when": "!completionOpen" },
+                { "keys": "alt+down", "command": { "MoveCursor": "DocumentEnd" }, "when": "!completionOpen" }
+            ] }"#,
+        )
+        .unwrap();
+
+        let mut shortcut_manager = ShortcutManager::new();
+        let warnings = shortcut_manager.load_keymap(&keymap).unwrap();
+        let alt_down: Shortcut = "alt+down".parse().unwrap();
+        assert!(warnings.contains(&KeymapWarning::Duplicate {
+            keys: vec![alt_down.clone()],
+            first: 1,
+            second: 2,
+        }));
+        // Ctrl+K is bound on its own by default
+        assert!(warnings.contains(&KeymapWarning::ShadowedByChord {
+            keys: vec!["ctrl+k".parse().unwrap()],
+            chord: Shortcut::parse_sequence("ctrl+k ctrl+c").unwrap(),
+        }));
+        assert!(shortcut_manager.get_bindings().iter().any(|binding| {
+            binding.keys == [alt_down.clone()]
+                && binding.message == EditorMessage::MoveCursor(CursorMovement::DocumentEnd)
+        }));
+    }
+
+    #[test]
+    fn test_invalid_keymap_entries() {
+        let keymap = Keymap::from_json(
+            r#"{ "bindings": [
+                { "keys": "ctrl+k", "command": "DeleteLine" },
+                { "keys": "hyper+k", "command": "DeleteLine" },
+                { "keys": "ctrl+j", "command": "Teleport" },
+                { "keys": "ctrl+j", "command": "Undo", "when": "a && " }
+            ] }"#,
+        )
+        .unwrap();
+
+        let mut shortcut_manager = ShortcutManager::new();
+        let errors = shortcut_manager.load_keymap(&keymap).unwrap_err();
+        let kinds: Vec<_> = errors
+            .iter()
+            .map(|error| (error.index, &error.kind))
+            .collect();
+        assert!(matches!(
+            kinds[..],
+            [
+                (
+                    1,
+                    KeymapErrorKind::Keys(ShortcutParseError::UnknownModifier(_))
+                ),
+                (2, KeymapErrorKind::UnknownCommand { .. }),
+                (3, KeymapErrorKind::InvalidCondition(_)),
+            ]
+        ));
+        // Nothing was bound
+        let ctrl_k: Shortcut = "ctrl+k".parse().unwrap();
+        assert_eq!(
+            shortcut_manager.get_message(&ctrl_k),
+            Some(&EditorMessage::DeleteToLineEnd)
+        );
+    }
+}
@@ -1,20 +1,63 @@
+pub mod anchor;
 pub mod buffer;
+pub mod commands;
+pub mod completion;
 pub mod cursor;
+pub mod decoration;
+pub mod diagnostic;
 pub mod editor;
+pub mod inline_suggestion;
+#[cfg(feature = "serde")]
+pub mod keymap;
 pub mod keys;
+pub mod line_widths;
+pub mod macros;
 pub mod messages;
+pub mod middleware;
+#[cfg(feature = "scripting")]
+pub mod scripting;
 pub mod selection;
 pub mod shortcuts;
+pub mod snippet;
 pub mod text_utils;
+pub mod tooltip;
+pub mod vim;
+pub mod virtual_text;
 
-pub use buffer::Buffer;
-pub use cursor::{Cursor, Position};
+pub use anchor::{AnchorId, AnchorSet, Gravity};
+pub use buffer::{Buffer, TextChange, TextEdit};
+pub use commands::{fuzzy_match, Command, CommandAction, CommandMatch, CommandRegistry};
+pub use completion::{
+    CompletionItem, CompletionKind, CompletionProvider, CompletionSession, WordCompletionProvider,
+};
+pub use cursor::{Cursor, CursorStyle, Position};
+pub use decoration::{
+    DecorationId, DecorationKind, DecorationLayer, DecorationShape, DecorationSpan,
+    DecorationStyle, Rgba,
+};
+pub use diagnostic::{Diagnostic, DiagnosticSeverity};
 pub use editor::Editor;
+pub use inline_suggestion::{InlineSuggestion, InlineSuggestionProvider};
+#[cfg(feature = "serde")]
+pub use keymap::{Keymap, KeymapEntry, KeymapError, KeymapErrorKind, KeymapWarning};
 pub use keys::{Key, KeyEvent, Modifiers, NamedKey};
+pub use line_widths::{line_width, LineWidths, TAB_WIDTH};
+pub use macros::Macro;
 pub use messages::{CursorMovement, EditorEvent, EditorMessage, EditorResponse};
+pub use middleware::{Middleware, MiddlewareAction};
+pub use ropey::Rope;
+#[cfg(feature = "scripting")]
+pub use scripting::{ScriptError, DEFAULT_MAX_SCRIPT_OPERATIONS};
 pub use selection::Selection;
-pub use shortcuts::{KeyBinding, Shortcut, ShortcutManager};
+pub use shortcuts::{
+    ChordState, KeyBinding, KeyCondition, KeyConditionParseError, KeyContext, Shortcut,
+    ShortcutManager, ShortcutParseError,
+};
+pub use snippet::{Snippet, SnippetError, TabStop};
 pub use text_utils::is_word_boundary;
+pub use tooltip::{Tooltip, TooltipBlock};
+pub use vim::{Register, Vim, VimMode, VimOutput};
+pub use virtual_text::{VirtualTextId, VirtualTextKind, VirtualTextPlacement, VirtualTextSpan};
 
 /// Key event for widget integration
 #[derive(Debug, Clone)]
@@ -66,6 +109,521 @@ mod tests {
         assert!(matches!(response, EditorResponse::Success));
     }
 
+    #[test]
+    fn test_buffer_revision() {
+        let mut editor = Editor::with_text("abc");
+        let initial = editor.current_buffer().revision();
+
+        // Cursor movement doesn't touch the text
+        editor.handle_message(EditorMessage::MoveCursor(CursorMovement::Right));
+        assert_eq!(editor.current_buffer().revision(), initial);
+
+        editor.handle_message(EditorMessage::InsertChar('x'));
+        let after_insert = editor.current_buffer().revision();
+        assert!(after_insert > initial);
+
+        editor.handle_message(EditorMessage::Undo);
+        assert!(editor.current_buffer().revision() > after_insert);
+
+        // Replacing the content keeps revisions increasing
+        let before_reset = editor.current_buffer().revision();
+        editor.set_text("new content");
+        assert!(editor.current_buffer().revision() > before_reset);
+    }
+
+    #[test]
+    fn test_max_line_width_tracking() {
+        let mut editor = Editor::with_text("short\n\tindented\nend");
+        assert_eq!(editor.current_buffer().max_line_width(), 12);
+
+        // Typing on a shorter line makes it the longest
+        editor.handle_message(EditorMessage::MoveCursorTo(Position::new(2, 3)));
+        editor.handle_message(EditorMessage::InsertText(" of the file".to_string()));
+        assert_eq!(editor.current_buffer().max_line_width(), 15);
+
+        // Deleting the longest line falls back to the next one
+        editor.handle_message(EditorMessage::DeleteLine);
+        assert_eq!(editor.current_buffer().max_line_width(), 12);
+
+        editor.handle_message(EditorMessage::Undo);
+        assert_eq!(editor.current_buffer().max_line_width(), 15);
+    }
+
+    #[test]
+    fn test_changes_since() {
+        let mut editor = Editor::with_text("hello world");
+        let start = editor.current_buffer().revision();
+
+        editor.handle_message(EditorMessage::MoveCursorTo(Position::new(0, 5)));
+        editor.handle_message(EditorMessage::InsertText(",".to_string()));
+        editor.handle_message(EditorMessage::MoveCursorTo(Position::new(0, 0)));
+        editor.handle_message(EditorMessage::DeleteChar);
+
+        let changes: Vec<_> = editor
+            .current_buffer()
+            .changes_since(start)
+            .unwrap()
+            .collect();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].old_range, 5..5);
+        assert_eq!(changes[0].new_range, 5..6);
+        assert_eq!(changes[0].text, ",");
+        assert_eq!(changes[1].old_range, 0..1);
+        assert_eq!(changes[1].new_range, 0..0);
+
+        // Undo is reported as the minimal edit that restores the previous text
+        let before_undo = editor.current_buffer().revision();
+        editor.handle_message(EditorMessage::Undo);
+        let changes: Vec<_> = editor
+            .current_buffer()
+            .changes_since(before_undo)
+            .unwrap()
+            .collect();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].old_range, 0..0);
+        assert_eq!(changes[0].text, "h");
+
+        // Nothing changed since the current revision
+        let current = editor.current_buffer().revision();
+        assert_eq!(
+            editor
+                .current_buffer()
+                .changes_since(current)
+                .unwrap()
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_anchor_tracks_edits() {
+        let mut editor = Editor::with_text("one\ntwo\nthree");
+        let anchor = editor
+            .current_buffer_mut()
+            .create_anchor(Position::new(1, 1), Gravity::Left);
+
+        // Inserting a line above moves the anchor down
+        editor.handle_message(EditorMessage::InsertText("zero\n".to_string()));
+        assert_eq!(
+            editor.current_buffer().anchor_position(anchor),
+            Some(Position::new(2, 1))
+        );
+
+        // Undo moves it back
+        editor.handle_message(EditorMessage::Undo);
+        assert_eq!(
+            editor.current_buffer().anchor_position(anchor),
+            Some(Position::new(1, 1))
+        );
+
+        // Deleting its line collapses it to the start of the deletion
+        editor.handle_message(EditorMessage::MoveCursorTo(Position::new(1, 0)));
+        editor.handle_message(EditorMessage::DeleteLine);
+        assert_eq!(
+            editor.current_buffer().anchor_position(anchor),
+            Some(Position::new(1, 0))
+        );
+    }
+
+    #[test]
+    fn test_decorations_follow_edits() {
+        let mut editor = Editor::with_text("let value = 1;");
+        let style = DecorationStyle::new(DecorationShape::WavyUnderline, Rgba::rgb(1.0, 0.0, 0.0));
+        let id = editor.current_buffer_mut().add_decoration(
+            Position::new(0, 4)..Position::new(0, 9),
+            DecorationKind::Error,
+            style,
+        );
+
+        // Typing at the start edge doesn't extend the range
+        editor.handle_message(EditorMessage::MoveCursorTo(Position::new(0, 4)));
+        editor.handle_message(EditorMessage::InsertText("mut ".to_string()));
+
+        let spans: Vec<_> = editor.current_buffer().decorations().collect();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].id, id);
+        assert_eq!(spans[0].range, Position::new(0, 8)..Position::new(0, 13));
+
+        assert_eq!(
+            editor
+                .current_buffer_mut()
+                .clear_decorations(&DecorationKind::Error),
+            1
+        );
+        assert_eq!(editor.current_buffer().decorations().count(), 0);
+    }
+
+    #[test]
+    fn test_virtual_text_follows_edits() {
+        let mut editor = Editor::with_text("let x = 1;\nprint(x);");
+        let buffer = editor.current_buffer_mut();
+        let hint = buffer.add_virtual_text(
+            Position::new(0, 5),
+            ": i32",
+            VirtualTextKind::TypeHint,
+            VirtualTextPlacement::Inline,
+        );
+        buffer.add_virtual_text(
+            Position::new(1, 6),
+            "value: ",
+            VirtualTextKind::ParameterHint,
+            VirtualTextPlacement::Inline,
+        );
+
+        // Typing at the hint's position goes before the hint
+        editor.handle_message(EditorMessage::MoveCursorTo(Position::new(0, 5)));
+        editor.handle_message(EditorMessage::InsertText("yz".to_string()));
+        assert_eq!(editor.current_buffer().text(), "let xyz = 1;\nprint(x);");
+
+        let spans = editor.current_buffer().virtual_text_on_line(0);
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].id, hint);
+        assert_eq!(spans[0].text, ": i32");
+        assert_eq!(spans[0].position, Position::new(0, 7));
+
+        assert_eq!(
+            editor
+                .current_buffer_mut()
+                .clear_virtual_text(&VirtualTextKind::ParameterHint),
+            1
+        );
+        assert!(editor.current_buffer().virtual_text_on_line(1).is_empty());
+        assert!(editor.current_buffer_mut().remove_virtual_text(hint));
+        assert_eq!(editor.current_buffer().virtual_text().count(), 0);
+    }
+
+    #[test]
+    fn test_virtual_text_by_line() {
+        let mut buffer = Buffer::from_text("ab\nlonger line\ncd");
+        for (position, text, placement) in [
+            (Position::new(0, 2), "xyz", VirtualTextPlacement::Inline),
+            (
+                Position::new(0, 0),
+                "0123456789",
+                VirtualTextPlacement::Inline,
+            ),
+            (Position::new(2, 2), "end", VirtualTextPlacement::Inline),
+            (
+                Position::new(1, 11),
+                "a long blame",
+                VirtualTextPlacement::EndOfLine,
+            ),
+        ] {
+            buffer.add_virtual_text(position, text, VirtualTextKind::TypeHint, placement);
+        }
+
+        // Spans come back ordered by column, whatever order they were added in
+        let texts: Vec<&str> = buffer
+            .virtual_text_on_line(0)
+            .iter()
+            .map(|span| span.text)
+            .collect();
+        assert_eq!(texts, vec!["0123456789", "xyz"]);
+        assert_eq!(
+            buffer.virtual_text_on_line(2)[0].position,
+            Position::new(2, 2)
+        );
+        assert!(buffer.virtual_text_on_line(3).is_empty());
+
+        // Inline text widens its line, text after the line end does not
+        assert_eq!(buffer.max_line_width(), 11);
+        assert_eq!(buffer.max_line_width_with_virtual_text(), 15);
+    }
+
+    #[test]
+    fn test_inline_suggestions() {
+        struct FixedSuggestion;
+        impl InlineSuggestionProvider for FixedSuggestion {
+            fn suggest(&self, _buffer: &Buffer, _cursor: &Cursor) -> Option<String> {
+                Some("(left, right)\n    left + right".to_string())
+            }
+        }
+
+        let mut editor = Editor::with_text("fn ad");
+        editor.set_inline_suggestion_provider(FixedSuggestion);
+        editor.handle_message(EditorMessage::MoveCursorTo(Position::new(0, 5)));
+        editor.handle_message(EditorMessage::InsertChar('d'));
+
+        // The first line is shown as virtual text after the cursor
+        let suggestion = editor.inline_suggestion().unwrap();
+        assert_eq!(suggestion.position, Position::new(0, 6));
+        let spans = editor.current_buffer().virtual_text_on_line(0);
+        assert_eq!(spans[0].text, "(left, right)");
+
+        // Typing what is suggested keeps the rest of it
+        editor.handle_message(EditorMessage::InsertChar('('));
+        assert_eq!(
+            editor.inline_suggestion().unwrap().text,
+            "left, right)\n    left + right"
+        );
+
+        editor.handle_message(EditorMessage::AcceptInlineSuggestionWord);
+        assert_eq!(editor.current_buffer().text(), "fn add(left");
+        assert_eq!(
+            editor.inline_suggestion().unwrap().position,
+            Position::new(0, 11)
+        );
+
+        editor.handle_message(EditorMessage::AcceptInlineSuggestion);
+        assert_eq!(
+            editor.current_buffer().text(),
+            "fn add(left, right)\n    left + right"
+        );
+        assert_eq!(editor.current_cursor().position(), Position::new(1, 16));
+        assert!(editor.inline_suggestion().is_none());
+        assert_eq!(editor.current_buffer().virtual_text().count(), 0);
+
+        // Accepting is one undo step; moving the cursor dismisses the suggestion
+        editor.handle_message(EditorMessage::Undo);
+        assert_eq!(editor.current_buffer().text(), "fn add(left");
+        editor.handle_message(EditorMessage::ShowInlineSuggestion(")".to_string()));
+        editor.handle_message(EditorMessage::MoveCursor(CursorMovement::Left));
+        assert!(editor.inline_suggestion().is_none());
+        assert_eq!(editor.current_buffer().virtual_text().count(), 0);
+    }
+
+    #[test]
+    fn test_tooltip_closes_on_typing() {
+        let mut editor = Editor::with_text("let total = 1;");
+        let tooltip = Tooltip::text(Position::new(0, 6), "i32")
+            .with_range(Position::new(0, 4)..Position::new(0, 9));
+        editor.handle_message(EditorMessage::ShowTooltip(tooltip.clone()));
+        assert_eq!(editor.tooltip(), Some(&tooltip));
+
+        // Messages that neither edit nor move the cursor keep it open
+        editor.handle_message(EditorMessage::Copy);
+        assert!(editor.tooltip().is_some());
+
+        editor.handle_message(EditorMessage::InsertChar('x'));
+        assert!(editor.tooltip().is_none());
+
+        editor.handle_message(EditorMessage::ShowTooltip(tooltip));
+        editor.handle_message(EditorMessage::HideTooltip);
+        assert!(editor.tooltip().is_none());
+    }
+
+    #[test]
+    fn test_diagnostic_navigation() {
+        let mut editor = Editor::with_text("fn main() {\n    let x = 1\n    let y;\n}");
+        editor.current_buffer_mut().set_diagnostics(vec![
+            Diagnostic::new(
+                Position::new(2, 8)..Position::new(2, 9),
+                DiagnosticSeverity::Warning,
+                "unused variable",
+            ),
+            Diagnostic::new(
+                Position::new(1, 13)..Position::new(1, 13),
+                DiagnosticSeverity::Error,
+                "expected `;`",
+            )
+            .with_source("rustc"),
+        ]);
+
+        // Diagnostics come back ordered by position
+        let diagnostics = editor.current_buffer().diagnostics();
+        assert_eq!(diagnostics[0].message, "expected `;`");
+        assert_eq!(diagnostics[1].message, "unused variable");
+
+        editor.handle_message(EditorMessage::NextDiagnostic);
+        assert_eq!(editor.current_cursor().position(), Position::new(1, 13));
+        editor.handle_message(EditorMessage::NextDiagnostic);
+        assert_eq!(editor.current_cursor().position(), Position::new(2, 8));
+
+        // Wraps around in both directions
+        editor.handle_message(EditorMessage::NextDiagnostic);
+        assert_eq!(editor.current_cursor().position(), Position::new(1, 13));
+        editor.handle_message(EditorMessage::PreviousDiagnostic);
+        assert_eq!(editor.current_cursor().position(), Position::new(2, 8));
+
+        // Diagnostics stay attached to their text when lines are inserted above
+        editor.handle_message(EditorMessage::MoveCursorTo(Position::new(0, 0)));
+        editor.handle_message(EditorMessage::InsertText("// comment\n".to_string()));
+        let diagnostic = editor
+            .current_buffer()
+            .diagnostic_at(Position::new(3, 8))
+            .unwrap();
+        assert_eq!(diagnostic.severity, DiagnosticSeverity::Warning);
+    }
+
+    #[test]
+    fn test_overlapping_diagnostics() {
+        let mut buffer = Buffer::from_text("let a = b;\nlet c = d;\nlet e = f;");
+        buffer.add_diagnostic(Diagnostic::new(
+            Position::new(1, 8)..Position::new(1, 9),
+            DiagnosticSeverity::Error,
+            "unknown `d`",
+        ));
+        buffer.add_diagnostic(Diagnostic::new(
+            Position::new(0, 0)..Position::new(2, 10),
+            DiagnosticSeverity::Warning,
+            "dead code",
+        ));
+        buffer.add_diagnostic(Diagnostic::new(
+            Position::new(1, 8)..Position::new(1, 9),
+            DiagnosticSeverity::Hint,
+            "rename `d`",
+        ));
+
+        let messages: Vec<String> = buffer
+            .diagnostics()
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+        assert_eq!(messages, ["dead code", "unknown `d`", "rename `d`"]);
+
+        // The most severe diagnostic wins, including ones that started lines earlier
+        let message_at = |line, column| {
+            buffer
+                .diagnostic_at(Position::new(line, column))
+                .map(|diagnostic| diagnostic.message)
+        };
+        assert_eq!(message_at(1, 8).as_deref(), Some("unknown `d`"));
+        assert_eq!(message_at(2, 4).as_deref(), Some("dead code"));
+        assert_eq!(message_at(2, 10), None);
+    }
+
+    #[test]
+    fn test_apply_edits_single_undo_step() {
+        let mut editor = Editor::with_text("fn  main(){\nlet x=1;\n}");
+        editor.handle_message(EditorMessage::MoveCursorTo(Position::new(1, 4)));
+
+        let response = editor.handle_message(EditorMessage::ApplyEdits(vec![
+            TextEdit::new(Position::new(0, 2)..Position::new(0, 4), " "),
+            TextEdit::insert(Position::new(0, 10), " "),
+            TextEdit::insert(Position::new(1, 0), "    "),
+            TextEdit::new(Position::new(1, 5)..Position::new(1, 6), " = "),
+        ]));
+        assert!(matches!(response, EditorResponse::TextChanged));
+        assert_eq!(
+            editor.current_buffer().text(),
+            "fn main() {\n    let x = 1;\n}"
+        );
+
+        // The cursor stays on the same character
+        assert_eq!(editor.current_cursor().position(), Position::new(1, 8));
+
+        // One undo reverts the whole batch
+        editor.handle_message(EditorMessage::Undo);
+        assert_eq!(editor.current_buffer().text(), "fn  main(){\nlet x=1;\n}");
+    }
+
+    #[test]
+    fn test_completion_session() {
+        let mut editor = Editor::with_text("let counter = 0;\nlet count = 1;\n");
+        editor.handle_message(EditorMessage::MoveCursor(CursorMovement::DocumentEnd));
+        editor.handle_message(EditorMessage::InsertText("co".to_string()));
+
+        editor.handle_message(EditorMessage::TriggerCompletion);
+        let labels = |editor: &Editor| -> Vec<String> {
+            editor
+                .completion()
+                .map(|session| {
+                    session
+                        .visible_items()
+                        .map(|item| item.label.clone())
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        assert_eq!(labels(&editor), vec!["count", "counter"]);
+
+        // Typing filters the open popup
+        editor.handle_message(EditorMessage::InsertChar('u'));
+        editor.handle_message(EditorMessage::InsertChar('n'));
+        editor.handle_message(EditorMessage::InsertChar('t'));
+        editor.handle_message(EditorMessage::InsertChar('e'));
+        assert_eq!(labels(&editor), vec!["counter"]);
+        editor.handle_message(EditorMessage::DeleteCharBackward);
+        assert_eq!(labels(&editor), vec!["count", "counter"]);
+
+        editor.handle_message(EditorMessage::SelectNextCompletion);
+        editor.handle_message(EditorMessage::AcceptCompletion);
+        assert!(editor.completion().is_none());
+        assert_eq!(
+            editor.current_buffer().text(),
+            "let counter = 0;\nlet count = 1;\ncounter"
+        );
+        assert_eq!(editor.current_cursor().position(), Position::new(2, 7));
+
+        // Accepting is a single undo step
+        editor.handle_message(EditorMessage::Undo);
+        assert_eq!(
+            editor.current_buffer().text(),
+            "let counter = 0;\nlet count = 1;\ncount"
+        );
+
+        // Leaving the word closes the popup
+        editor.handle_message(EditorMessage::TriggerCompletion);
+        assert!(editor.completion().is_some());
+        editor.handle_message(EditorMessage::InsertChar(' '));
+        assert!(editor.completion().is_none());
+    }
+
+    #[test]
+    fn test_snippet_tab_stops_and_mirrors() {
+        let mut editor = Editor::with_text("    \n");
+        editor.handle_message(EditorMessage::MoveCursor(CursorMovement::LineEnd));
+        editor.handle_message(EditorMessage::InsertSnippet(
+            "fn ${1:name}($2) -> $1 {\n\t$0\n}".to_string(),
+        ));
+        assert_eq!(
+            editor.current_buffer().text(),
+            "    fn name() -> name {\n    \t\n    }\n"
+        );
+        assert!(editor.is_snippet_active());
+        assert_eq!(
+            editor.current_selection(),
+            Some(&Selection::new(Position::new(0, 7), Position::new(0, 11)))
+        );
+
+        // Typing replaces the placeholder and updates its mirror
+        editor.handle_message(EditorMessage::InsertChar('g'));
+        editor.handle_message(EditorMessage::InsertChar('o'));
+        assert_eq!(
+            editor.current_buffer().text(),
+            "    fn go() -> go {\n    \t\n    }\n"
+        );
+
+        editor.handle_message(EditorMessage::NextTabStop);
+        assert_eq!(editor.current_cursor().position(), Position::new(0, 10));
+        editor.handle_message(EditorMessage::PreviousTabStop);
+        assert_eq!(
+            editor.current_selection(),
+            Some(&Selection::new(Position::new(0, 7), Position::new(0, 9)))
+        );
+
+        // Reaching `$0` ends the snippet
+        editor.handle_message(EditorMessage::NextTabStop);
+        editor.handle_message(EditorMessage::NextTabStop);
+        assert!(!editor.is_snippet_active());
+        assert_eq!(editor.current_cursor().position(), Position::new(1, 5));
+        assert!(editor.current_buffer().anchors().is_empty());
+
+        // Each keystroke, mirror included, undoes in one step
+        editor.handle_message(EditorMessage::Undo);
+        assert_eq!(
+            editor.current_buffer().text(),
+            "    fn g() -> g {\n    \t\n    }\n"
+        );
+
+        // Moving away from the tab stop ends the snippet too
+        editor.handle_message(EditorMessage::InsertSnippet("(${1:x})".to_string()));
+        assert!(editor.is_snippet_active());
+        editor.handle_message(EditorMessage::MoveCursor(CursorMovement::DocumentEnd));
+        assert!(!editor.is_snippet_active());
+        assert!(matches!(
+            editor.handle_message(EditorMessage::InsertSnippet("${1:x".to_string())),
+            EditorResponse::Error(_)
+        ));
+
+        // Replacing the text ends the snippet and drops its anchors
+        editor.handle_message(EditorMessage::InsertSnippet("(${1:x})".to_string()));
+        assert!(!editor.current_buffer().anchors().is_empty());
+        editor.set_text("new");
+        assert!(!editor.is_snippet_active());
+        assert!(editor.current_buffer().anchors().is_empty());
+    }
+
     #[test]
     fn test_key_input_handling() {
         let mut editor = Editor::new();
@@ -85,6 +643,382 @@ mod tests {
         assert_eq!(content, "");
     }
 
+    #[test]
+    fn test_chord_shortcuts() {
+        use crate::keys::{Key, KeyEvent, Modifiers};
+        use crate::shortcuts::{ChordState, KeyBinding, KeyContext, Shortcut, ShortcutManager};
+        use std::time::{Duration, Instant};
+
+        let mut shortcut_manager = ShortcutManager::new();
+        shortcut_manager.bind(KeyBinding::chord(
+            vec![
+                Shortcut::ctrl(Key::Character('k')),
+                Shortcut::ctrl(Key::Character('u')),
+            ],
+            EditorMessage::DeleteLine,
+            "Delete line",
+        ));
+        let ctrl_k = KeyEvent::new(Key::Character('k'), Modifiers::new().control());
+        let ctrl_u = KeyEvent::new(Key::Character('u'), Modifiers::new().control());
+        let mut state = ChordState::new();
+        let context = KeyContext::new();
+        let now = Instant::now();
+
+        // Ctrl+K waits for the rest of the chord even though it is bound on its own
+        let result =
+            shortcut_manager.handle_key_sequence_at(&mut state, ctrl_k.clone(), &context, now);
+        assert!(result.is_empty());
+        assert_eq!(state.status().as_deref(), Some("Ctrl+K …"));
+        let result =
+            shortcut_manager.handle_key_sequence_at(&mut state, ctrl_u.clone(), &context, now);
+        assert!(matches!(result[..], [EditorMessage::DeleteLine]));
+        assert!(!state.is_pending());
+
+        // An unrelated key runs the prefix's own binding and is then handled as usual
+        shortcut_manager.handle_key_sequence_at(&mut state, ctrl_k.clone(), &context, now);
+        let result = shortcut_manager.handle_key_sequence_at(
+            &mut state,
+            KeyEvent::character('a'),
+            &context,
+            now,
+        );
+        assert!(matches!(
+            result[..],
+            [
+                EditorMessage::DeleteToLineEnd,
+                EditorMessage::InsertChar('a')
+            ]
+        ));
+
+        // After the timeout the chord starts over
+        shortcut_manager.handle_key_sequence_at(&mut state, ctrl_k.clone(), &context, now);
+        let later = now + shortcut_manager.chord_timeout() + Duration::from_millis(1);
+        assert!(state.is_expired(later));
+        let result = shortcut_manager.handle_key_sequence_at(&mut state, ctrl_u, &context, later);
+        assert!(matches!(
+            result[..],
+            [
+                EditorMessage::DeleteToLineEnd,
+                EditorMessage::DeleteToLineStart
+            ]
+        ));
+
+        // Escape cancels a pending chord
+        shortcut_manager.handle_key_sequence_at(&mut state, ctrl_k, &context, now);
+        let escape = KeyEvent::named(crate::keys::NamedKey::Escape);
+        assert!(shortcut_manager
+            .handle_key_sequence_at(&mut state, escape, &context, now)
+            .is_empty());
+        assert!(!state.is_pending());
+    }
+
+    #[test]
+    fn test_conditional_key_bindings() {
+        use crate::keys::{Key, KeyEvent, NamedKey};
+        use crate::shortcuts::{KeyBinding, KeyCondition, KeyContext, Shortcut, ShortcutManager};
+
+        let mut shortcut_manager = ShortcutManager::new();
+        let tab = KeyEvent::named(NamedKey::Tab);
+
+        let mut editor = Editor::with_text("fn main() {}");
+        let context = KeyContext::from_editor(&editor);
+        let result = shortcut_manager.handle_key_event_in(tab.clone(), &context);
+        assert_eq!(result, Some(EditorMessage::InsertChar('\t')));
+
+        editor.handle_message(EditorMessage::InsertSnippet("${1:a}, ${2:b}".to_string()));
+        let context = KeyContext::from_editor(&editor);
+        let result = shortcut_manager.handle_key_event_in(tab.clone(), &context);
+        assert_eq!(result, Some(EditorMessage::NextTabStop));
+
+        // The completion popup wins over the snippet
+        let context = KeyContext {
+            completion_open: true,
+            ..context
+        };
+        let result = shortcut_manager.handle_key_event_in(tab.clone(), &context);
+        assert_eq!(result, Some(EditorMessage::AcceptCompletion));
+
+        // App-defined keys, combined with editor state
+        shortcut_manager.bind(
+            KeyBinding::new(
+                Shortcut::ctrl(Key::Character('d')),
+                EditorMessage::DeleteLine,
+                "Delete line",
+            )
+            .when(KeyCondition::All(vec![
+                KeyCondition::Custom("vimMode".to_string()),
+                !KeyCondition::ReadOnly,
+            ])),
+        );
+        let ctrl_d = KeyEvent::new(Key::Character('d'), crate::keys::Modifiers::new().control());
+        let context = KeyContext::new().with_key("vimMode");
+        assert_eq!(
+            shortcut_manager.handle_key_event_in(ctrl_d.clone(), &context),
+            Some(EditorMessage::DeleteLine)
+        );
+        let context = context.read_only(true);
+        assert_eq!(shortcut_manager.handle_key_event_in(ctrl_d, &context), None);
+    }
+
+    #[test]
+    fn test_overwrite_mode() {
+        use crate::keys::{Key, KeyEvent, Modifiers, NamedKey};
+        use crate::shortcuts::ShortcutManager;
+
+        let shortcut_manager = ShortcutManager::new();
+        let insert = KeyEvent::new(Key::Named(NamedKey::Insert), Modifiers::new());
+        let message = shortcut_manager.handle_key_event(insert).unwrap();
+        assert_eq!(message, EditorMessage::ToggleOverwrite);
+
+        let mut editor = Editor::with_text("abc\nxy");
+        editor.handle_message(message);
+        assert!(editor.is_overwrite());
+
+        editor.handle_message(EditorMessage::InsertChar('X'));
+        editor.handle_message(EditorMessage::InsertChar('Y'));
+        assert_eq!(editor.current_buffer().text(), "XYc\nxy");
+
+        // Line breaks are never overwritten, and Enter still splits the line
+        editor.handle_message(EditorMessage::InsertChar('Z'));
+        editor.handle_message(EditorMessage::InsertChar('!'));
+        assert_eq!(editor.current_buffer().text(), "XYZ!\nxy");
+        editor.handle_message(EditorMessage::InsertChar('\n'));
+        assert_eq!(editor.current_buffer().text(), "XYZ!\n\nxy");
+
+        // Undo brings back the overwritten chars
+        for _ in 0..3 {
+            editor.handle_message(EditorMessage::Undo);
+        }
+        assert_eq!(editor.current_buffer().text(), "XYc\nxy");
+        editor.handle_message(EditorMessage::Undo);
+        assert_eq!(editor.current_buffer().text(), "Xbc\nxy");
+
+        // Typing over a selection replaces just the selection
+        editor.handle_message(EditorMessage::SetSelection(
+            Position::new(1, 0),
+            Position::new(1, 1),
+        ));
+        editor.handle_message(EditorMessage::InsertChar('q'));
+        assert_eq!(editor.current_buffer().text(), "Xbc\nqy");
+
+        editor.handle_message(EditorMessage::ToggleOverwrite);
+        editor.handle_message(EditorMessage::InsertChar('z'));
+        assert_eq!(editor.current_buffer().text(), "Xbc\nqzy");
+    }
+
+    #[test]
+    fn test_emacs_mark_and_kill() {
+        use crate::keys::{Key, KeyEvent, Modifiers, NamedKey};
+        use crate::shortcuts::{KeyContext, ShortcutManager};
+
+        let shortcut_manager = ShortcutManager::emacs();
+        let ctrl = |ch| KeyEvent::new(Key::Character(ch), Modifiers::new().control());
+        let f3 = |modifiers| KeyEvent::new(Key::Named(NamedKey::F3), modifiers);
+        let context = KeyContext::new();
+        let mut editor = Editor::with_text("one two\nthree\nfour");
+        let press = |editor: &mut Editor, event: KeyEvent| {
+            let message = shortcut_manager
+                .handle_key_event_in(event, &context)
+                .unwrap();
+            editor.handle_message(message);
+        };
+
+        // Consecutive kills collect into the clipboard, line breaks included
+        press(&mut editor, ctrl('k'));
+        press(&mut editor, ctrl('k'));
+        press(&mut editor, ctrl('k'));
+        assert_eq!(editor.current_buffer().text(), "\nfour");
+        assert_eq!(editor.clipboard(), "one two\nthree");
+
+        // Any other command starts a new kill
+        press(&mut editor, ctrl('n'));
+        press(&mut editor, ctrl('k'));
+        assert_eq!(editor.clipboard(), "four");
+        press(&mut editor, ctrl('y'));
+        assert_eq!(editor.current_buffer().text(), "\nfour");
+
+        // Movements after setting the mark select the region
+        editor.set_text("alpha beta gamma");
+        let alt_f = KeyEvent::new(Key::Character('f'), Modifiers::new().alt());
+        press(&mut editor, alt_f.clone());
+        press(
+            &mut editor,
+            KeyEvent::new(Key::Named(NamedKey::Space), Modifiers::new().control()),
+        );
+        assert_eq!(editor.mark(), Some(Position::new(0, 6)));
+        press(&mut editor, alt_f);
+        assert_eq!(
+            editor
+                .current_selection()
+                .map(|selection| (selection.start, selection.end)),
+            Some((Position::new(0, 6), Position::new(0, 11)))
+        );
+        press(&mut editor, ctrl('w'));
+        assert_eq!(editor.current_buffer().text(), "alpha gamma");
+        assert_eq!(editor.clipboard(), "beta ");
+        assert_eq!(editor.mark(), None);
+
+        // There is no incremental search, so C-s and C-r are unbound
+        for ch in ['s', 'r'] {
+            assert!(shortcut_manager
+                .handle_key_event_in(ctrl(ch), &context)
+                .is_none());
+        }
+
+        // F3 jumps between the matches of the last search
+        editor.handle_message(EditorMessage::MoveCursorTo(Position::new(0, 0)));
+        editor.handle_message(EditorMessage::Find("a".to_string()));
+        press(&mut editor, f3(Modifiers::new()));
+        press(&mut editor, f3(Modifiers::new()));
+        assert_eq!(editor.current_cursor().position(), Position::new(0, 5));
+        press(&mut editor, f3(Modifiers::new().shift()));
+        assert_eq!(editor.current_cursor().position(), Position::new(0, 4));
+
+        // Text changed behind the editor's back is searched again
+        editor.current_buffer_mut().reset("xx a");
+        editor.handle_message(EditorMessage::MoveCursorTo(Position::new(0, 0)));
+        press(&mut editor, f3(Modifiers::new()));
+        assert_eq!(editor.current_cursor().position(), Position::new(0, 4));
+
+        // Windows line breaks are kept whole
+        editor.set_text("ab\r\ncd");
+        press(&mut editor, ctrl('k'));
+        assert_eq!(editor.current_buffer().text(), "\r\ncd");
+        press(&mut editor, ctrl('k'));
+        assert_eq!(editor.current_buffer().text(), "cd");
+        assert_eq!(editor.clipboard(), "ab\r\n");
+    }
+
+    #[test]
+    fn test_search_results_follow_edits() {
+        let mut editor = Editor::with_text("ab\nab\nab");
+        editor.handle_message(EditorMessage::Find("ab".to_string()));
+        assert_eq!(editor.search_results().len(), 3);
+
+        editor.handle_message(EditorMessage::DeleteLine);
+        assert_eq!(
+            editor.search_results(),
+            &[Position::new(0, 0), Position::new(1, 0)]
+        );
+
+        editor.handle_message(EditorMessage::InsertText("x ab ".to_string()));
+        assert_eq!(
+            editor.search_results(),
+            &[
+                Position::new(0, 2),
+                Position::new(0, 5),
+                Position::new(1, 0)
+            ]
+        );
+
+        // Joining lines can create a match across the old line break
+        editor.set_text("xa\nbx\nab");
+        editor.handle_message(EditorMessage::Find("ab".to_string()));
+        editor.handle_message(EditorMessage::MoveCursorTo(Position::new(1, 0)));
+        editor.handle_message(EditorMessage::DeleteCharBackward);
+        assert_eq!(
+            editor.search_results(),
+            &[Position::new(0, 1), Position::new(1, 0)]
+        );
+
+        // Loading new text forgets the search
+        editor.set_text("ab ab");
+        editor.handle_message(EditorMessage::InsertText("ab".to_string()));
+        assert!(editor.search_results().is_empty());
+        assert!(matches!(
+            editor.handle_message(EditorMessage::FindNext),
+            EditorResponse::Success
+        ));
+        assert_eq!(editor.current_cursor().position(), Position::new(0, 2));
+    }
+
+    #[test]
+    fn test_record_and_play_macro() {
+        let mut editor = Editor::with_text("a\nb\nc\nd");
+
+        editor.handle_message(EditorMessage::StartRecording("comment".to_string()));
+        assert_eq!(editor.recording(), Some("comment"));
+        editor.handle_message(EditorMessage::InsertText("// ".to_string()));
+        editor.handle_message(EditorMessage::SetDiagnostics(Vec::new()));
+        editor.handle_message(EditorMessage::MoveCursor(CursorMovement::Down));
+        editor.handle_message(EditorMessage::MoveCursor(CursorMovement::LineStart));
+        editor.handle_message(EditorMessage::StopRecording);
+        assert_eq!(editor.recording(), None);
+        // Diagnostics come from the app, so they are not replayed
+        assert_eq!(editor.get_macro("comment").unwrap().messages.len(), 3);
+
+        let play = EditorMessage::PlayMacro {
+            name: "comment".to_string(),
+            count: 2,
+        };
+        assert_eq!(editor.handle_message(play), EditorResponse::Success);
+        assert_eq!(editor.current_buffer().text(), "// a\n// b\n// c\nd");
+
+        // All repetitions are undone at once
+        editor.handle_message(EditorMessage::Undo);
+        assert_eq!(editor.current_buffer().text(), "// a\nb\nc\nd");
+
+        let play = EditorMessage::PlayMacro {
+            name: "missing".to_string(),
+            count: 1,
+        };
+        assert!(matches!(
+            editor.handle_message(play),
+            EditorResponse::Error(_)
+        ));
+
+        // A macro playing itself stops instead of recursing forever
+        editor.set_macro(
+            "loop",
+            crate::Macro::new(vec![EditorMessage::PlayMacro {
+                name: "loop".to_string(),
+                count: 1,
+            }]),
+        );
+        let play = EditorMessage::PlayMacro {
+            name: "loop".to_string(),
+            count: 1,
+        };
+        assert!(matches!(
+            editor.handle_message(play),
+            EditorResponse::Error(_)
+        ));
+    }
+
+    #[test]
+    fn test_parse_shortcuts_and_conditions() {
+        use crate::keys::{Key, Modifiers, NamedKey};
+        use crate::shortcuts::{KeyCondition, Shortcut, ShortcutParseError};
+
+        let shortcut: Shortcut = "Ctrl+Shift+PageUp".parse().unwrap();
+        assert_eq!(shortcut, Shortcut::ctrl_shift(Key::Named(NamedKey::PageUp)));
+        assert_eq!(shortcut.to_string().parse::<Shortcut>(), Ok(shortcut));
+        assert_eq!(
+            "ctrl++".parse::<Shortcut>(),
+            Ok(Shortcut::new(
+                Key::Character('+'),
+                Modifiers::new().control()
+            ))
+        );
+        assert_eq!(
+            "ctrl+nope".parse::<Shortcut>(),
+            Err(ShortcutParseError::UnknownKey("nope".to_string()))
+        );
+        assert_eq!(
+            Shortcut::parse_sequence("  "),
+            Err(ShortcutParseError::Empty)
+        );
+
+        assert_eq!(
+            "inSnippet && !vimMode".parse::<KeyCondition>(),
+            Ok(KeyCondition::All(vec![
+                KeyCondition::InSnippet,
+                !KeyCondition::Custom("vimMode".to_string()),
+            ]))
+        );
+        assert!("hasSelection &&".parse::<KeyCondition>().is_err());
+    }
+
     #[test]
     fn test_whitespace_and_tab_input() {
         use crate::keys::{Key, KeyEvent, Modifiers, NamedKey};
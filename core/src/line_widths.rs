@@ -0,0 +1,123 @@
+use ropey::{Rope, RopeSlice};
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// Number of columns a tab advances to (next multiple of this value)
+pub const TAB_WIDTH: usize = 4;
+
+/// Measure the visual width of a line in columns
+///
+/// Tabs advance to the next tab stop and line endings are not counted.
+pub fn line_width(line: RopeSlice) -> usize {
+    let mut width = 0;
+
+    for ch in line.chars() {
+        match ch {
+            '\t' => width = (width / TAB_WIDTH + 1) * TAB_WIDTH,
+            '\n' | '\r' => {}
+            _ => width += 1,
+        }
+    }
+
+    width
+}
+
+/// Visual width of every line in a buffer, kept up to date as the buffer is edited
+///
+/// Widths are stored per line alongside a count of how many lines have each width,
+/// so the longest line is available without rescanning the buffer and an edit only
+/// re-measures the lines it touched.
+#[derive(Debug, Clone, Default)]
+pub struct LineWidths {
+    widths: Vec<usize>,
+    counts: BTreeMap<usize, usize>,
+}
+
+impl LineWidths {
+    /// Measure every line of a rope
+    pub fn from_rope(rope: &Rope) -> Self {
+        let mut line_widths = Self::default();
+        line_widths.splice(rope, 0..0, rope.len_lines());
+        line_widths
+    }
+
+    /// Width of the longest line in columns
+    pub fn max(&self) -> usize {
+        self.counts.keys().next_back().copied().unwrap_or(0)
+    }
+
+    /// Width of a single line in columns
+    pub fn get(&self, line: usize) -> Option<usize> {
+        self.widths.get(line).copied()
+    }
+
+    /// Number of tracked lines
+    pub fn len(&self) -> usize {
+        self.widths.len()
+    }
+
+    /// Check if no lines are tracked
+    pub fn is_empty(&self) -> bool {
+        self.widths.is_empty()
+    }
+
+    /// Replace the widths of `old_lines` with the widths of `new_line_count` lines
+    /// starting at `old_lines.start` in the (already edited) rope
+    pub fn splice(&mut self, rope: &Rope, old_lines: Range<usize>, new_line_count: usize) {
+        let old_lines =
+            old_lines.start.min(self.widths.len())..old_lines.end.min(self.widths.len());
+        let start = old_lines.start;
+
+        let new_widths: Vec<usize> = (start..start + new_line_count)
+            .filter_map(|line| rope.get_line(line))
+            .map(line_width)
+            .collect();
+
+        for width in &new_widths {
+            *self.counts.entry(*width).or_insert(0) += 1;
+        }
+
+        for width in self.widths.splice(old_lines, new_widths) {
+            if let Some(count) = self.counts.get_mut(&width) {
+                *count -= 1;
+                if *count == 0 {
+                    self.counts.remove(&width);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_width_tabs() {
+        assert_eq!(line_width(Rope::from_str("abc\n").slice(..)), 3);
+        assert_eq!(line_width(Rope::from_str("\tx").slice(..)), 5);
+        assert_eq!(line_width(Rope::from_str("ab\tx\r\n").slice(..)), 5);
+    }
+
+    #[test]
+    fn test_splice_tracks_max() {
+        let mut rope = Rope::from_str("a\nlongest line\nbb");
+        let mut widths = LineWidths::from_rope(&rope);
+        assert_eq!(widths.len(), 3);
+        assert_eq!(widths.max(), 12);
+
+        // Remove the longest line
+        rope.remove(2..15);
+        widths.splice(&rope, 1..2, 0);
+        assert_eq!(widths.len(), 2);
+        assert_eq!(widths.max(), 2);
+
+        // Split a line in two
+        rope.insert(3, "cccc\n");
+        widths.splice(&rope, 1..2, 2);
+        assert_eq!(rope.to_string(), "a\nbcccc\nb");
+        assert_eq!(widths.len(), 3);
+        assert_eq!(widths.get(1), Some(5));
+        assert_eq!(widths.max(), 5);
+    }
+}
@@ -0,0 +1,82 @@
+use crate::EditorMessage;
+
+/// Editor messages recorded between `StartRecording` and `StopRecording`
+///
+/// With the `serde` feature macros can be saved to and loaded from files,
+/// e.g. as JSON:
+///
+/// ```json
+/// { "messages": [{ "InsertText": "// " }, { "MoveCursor": "Down" }, { "MoveCursor": "LineStart" }] }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Macro {
+    pub messages: Vec<EditorMessage>,
+}
+
+impl Macro {
+    pub fn new(messages: Vec<EditorMessage>) -> Self {
+        Self { messages }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Parse a macro from JSON
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Write the macro as pretty-printed JSON
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+/// Check if a message belongs in a macro
+///
+/// Macro control messages and messages that come from the app rather than the
+/// user, such as diagnostics or tooltips, would replay stale data.
+pub(crate) fn is_recordable(message: &EditorMessage) -> bool {
+    !matches!(
+        message,
+        EditorMessage::StartRecording(_)
+            | EditorMessage::StopRecording
+            | EditorMessage::PlayMacro { .. }
+            | EditorMessage::RunScript(_)
+            | EditorMessage::SetDiagnostics(_)
+            | EditorMessage::ShowCompletions(_)
+            | EditorMessage::ShowInlineSuggestion(_)
+            | EditorMessage::ShowTooltip(_)
+            | EditorMessage::HideTooltip
+            | EditorMessage::ScrollToLine(_)
+    )
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::CursorMovement;
+
+    #[test]
+    fn test_macro_json_round_trip() {
+        let recorded = Macro::new(vec![
+            EditorMessage::InsertText("// ".to_string()),
+            EditorMessage::MoveCursor(CursorMovement::Down),
+            EditorMessage::MoveCursor(CursorMovement::LineStart),
+        ]);
+
+        let json = recorded.to_json().unwrap();
+        assert_eq!(Macro::from_json(&json).unwrap(), recorded);
+
+        let loaded = Macro::from_json(r#"{ "messages": ["Undo", { "InsertChar": "x" }] }"#);
+        assert_eq!(
+            loaded.unwrap().messages,
+            vec![EditorMessage::Undo, EditorMessage::InsertChar('x')]
+        );
+        assert!(Macro::from_json(r#"{ "messages": ["Teleport"] }"#).is_err());
+    }
+}
@@ -1,7 +1,8 @@
-use crate::{Position, Selection};
+use crate::{CompletionItem, Diagnostic, Position, Selection, TextEdit, Tooltip};
 
 /// All possible editor actions represented as messages
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EditorMessage {
     // Text manipulation
     InsertChar(char),
@@ -14,6 +15,10 @@ pub enum EditorMessage {
     DeleteWordBackward,
     DeleteToLineEnd,
     DeleteToLineStart,
+    /// Apply a batch of edits (e.g. from a formatter) as one undo step
+    ApplyEdits(Vec<TextEdit>),
+    /// Switch between inserting typed chars and overwriting the char under the cursor
+    ToggleOverwrite,
 
     // Cursor movement
     MoveCursor(CursorMovement),
@@ -30,8 +35,20 @@ pub enum EditorMessage {
     SelectAll,
     SelectLine,
     SelectWord,
+    /// Clear the selection and deactivate the mark
     ClearSelection,
 
+    // Mark and kill
+    /// Set the mark at the cursor; cursor movements then select from the mark
+    /// until the mark is cleared, the text is edited or the selection is changed
+    SetMark,
+    /// Cut from the cursor to the line end, or the line break at the end of a line
+    ///
+    /// Consecutive kills append to the clipboard instead of replacing it.
+    KillToLineEnd,
+    /// Cut the selection, appending to the clipboard right after another kill
+    KillRegion,
+
     // Editing operations
     Undo,
     Redo,
@@ -46,11 +63,72 @@ pub enum EditorMessage {
     Replace(String, String),
     ReplaceAll(String, String),
 
+    // Diagnostics
+    /// Replace the diagnostics of the current buffer
+    SetDiagnostics(Vec<Diagnostic>),
+    /// Move the cursor to the next diagnostic, wrapping around
+    NextDiagnostic,
+    /// Move the cursor to the previous diagnostic, wrapping around
+    PreviousDiagnostic,
+
+    // Completion
+    /// Ask the completion providers for items at the cursor and open the popup
+    TriggerCompletion,
+    /// Open the popup with the given items, e.g. from a language server
+    ShowCompletions(Vec<CompletionItem>),
+    SelectNextCompletion,
+    SelectPreviousCompletion,
+    /// Apply the selected completion item as one undo step
+    AcceptCompletion,
+    CancelCompletion,
+
+    // Snippets
+    /// Insert a snippet in LSP/TextMate syntax and select its first tab stop
+    InsertSnippet(String),
+    NextTabStop,
+    PreviousTabStop,
+    /// Stop tracking the tab stops of the active snippet
+    EndSnippet,
+
+    // Inline suggestions
+    /// Ask the inline suggestion provider for ghost text at the cursor
+    TriggerInlineSuggestion,
+    /// Show ghost text at the cursor, e.g. from a provider that answers asynchronously
+    ShowInlineSuggestion(String),
+    /// Insert the whole inline suggestion
+    AcceptInlineSuggestion,
+    /// Insert the inline suggestion up to the end of its next word
+    AcceptInlineSuggestionWord,
+    DismissInlineSuggestion,
+
+    // Tooltips
+    /// Show a tooltip, replacing the open one; typing or moving the cursor closes it
+    ShowTooltip(Tooltip),
+    HideTooltip,
+
+    // Macros
+    /// Record the messages that follow into a named macro
+    StartRecording(String),
+    /// Stop recording and keep the macro, replacing one with the same name
+    StopRecording,
+    /// Replay a macro `count` times as one undo step
+    PlayMacro {
+        name: String,
+        count: usize,
+    },
+
+    // Scripting
+    /// Run a script added with `Editor::add_script`, as one undo step
+    ///
+    /// Needs the `scripting` feature; without it the editor responds with an error.
+    RunScript(String),
+
     // View operations
     ScrollToLine(usize),
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CursorMovement {
     Up,
     Down,
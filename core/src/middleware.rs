@@ -0,0 +1,144 @@
+use crate::{Editor, EditorMessage, EditorResponse, TextChange};
+
+/// What the editor does with a message after a middleware has seen it
+#[derive(Debug, Clone, PartialEq)]
+pub enum MiddlewareAction {
+    /// Pass the message on, possibly rewritten
+    Continue(EditorMessage),
+    /// Drop the message; the editor responds with the reason as an error
+    Veto(String),
+}
+
+/// Hooks run around every message the editor handles
+///
+/// `before` hooks run in the order the middleware was added, each seeing the
+/// message the previous one passed on. Once the message is handled, `after`
+/// hooks see it together with the response and the text changes it made.
+/// Vetoed messages never reach `after`.
+pub trait Middleware {
+    fn before(&mut self, _editor: &Editor, message: EditorMessage) -> MiddlewareAction {
+        MiddlewareAction::Continue(message)
+    }
+
+    fn after(
+        &mut self,
+        _editor: &Editor,
+        _message: &EditorMessage,
+        _response: &EditorResponse,
+        _changes: &[TextChange],
+    ) {
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CursorMovement, Position};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Blocks edits on the first line
+    struct ProtectFirstLine;
+
+    impl Middleware for ProtectFirstLine {
+        fn before(&mut self, editor: &Editor, message: EditorMessage) -> MiddlewareAction {
+            let is_edit = matches!(
+                message,
+                EditorMessage::InsertChar(_)
+                    | EditorMessage::InsertText(_)
+                    | EditorMessage::DeleteChar
+                    | EditorMessage::DeleteCharBackward
+            );
+            if is_edit && editor.current_cursor().position().line == 0 {
+                MiddlewareAction::Veto("Line is read-only".to_string())
+            } else {
+                MiddlewareAction::Continue(message)
+            }
+        }
+    }
+
+    struct AutoCloseParens;
+
+    impl Middleware for AutoCloseParens {
+        fn before(&mut self, _editor: &Editor, message: EditorMessage) -> MiddlewareAction {
+            match message {
+                EditorMessage::InsertChar('(') => {
+                    MiddlewareAction::Continue(EditorMessage::InsertSnippet("($0)".to_string()))
+                }
+                message => MiddlewareAction::Continue(message),
+            }
+        }
+    }
+
+    type Entries = Rc<RefCell<Vec<(EditorMessage, Vec<String>)>>>;
+
+    /// Records each handled message and the text it inserted
+    struct Log(Entries);
+
+    impl Middleware for Log {
+        fn after(
+            &mut self,
+            _editor: &Editor,
+            message: &EditorMessage,
+            _response: &EditorResponse,
+            changes: &[TextChange],
+        ) {
+            let inserted = changes.iter().map(|change| change.text.clone()).collect();
+            self.0.borrow_mut().push((message.clone(), inserted));
+        }
+    }
+
+    #[test]
+    fn test_veto_and_rewrite_messages() {
+        let mut editor = Editor::with_text("header\nbody");
+        editor.add_middleware(ProtectFirstLine);
+        editor.add_middleware(AutoCloseParens);
+
+        let response = editor.handle_message(EditorMessage::InsertChar('x'));
+        assert_eq!(
+            response,
+            EditorResponse::Error("Line is read-only".to_string())
+        );
+        assert_eq!(editor.current_buffer().text(), "header\nbody");
+
+        editor.handle_message(EditorMessage::MoveCursor(CursorMovement::Down));
+        editor.handle_message(EditorMessage::MoveCursor(CursorMovement::LineEnd));
+        editor.handle_message(EditorMessage::InsertChar('('));
+        editor.handle_message(EditorMessage::InsertChar('x'));
+        assert_eq!(editor.current_buffer().text(), "header\nbody(x)");
+        assert_eq!(editor.current_cursor().position(), Position::new(1, 6));
+    }
+
+    #[test]
+    fn test_after_sees_response_and_changes() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut editor = Editor::with_text("one");
+        editor.add_middleware(AutoCloseParens);
+        editor.add_middleware(Log(log.clone()));
+
+        editor.handle_message(EditorMessage::MoveCursor(CursorMovement::LineEnd));
+        editor.handle_message(EditorMessage::InsertChar('('));
+        assert_eq!(
+            *log.borrow(),
+            vec![
+                (EditorMessage::MoveCursor(CursorMovement::LineEnd), vec![]),
+                (
+                    EditorMessage::InsertSnippet("($0)".to_string()),
+                    vec!["()".to_string()]
+                ),
+            ]
+        );
+
+        // Replayed macro messages go through the middleware too
+        editor.set_macro("x", crate::Macro::new(vec![EditorMessage::InsertChar('x')]));
+        editor.handle_message(EditorMessage::PlayMacro {
+            name: "x".to_string(),
+            count: 1,
+        });
+        assert_eq!(
+            log.borrow()[2],
+            (EditorMessage::InsertChar('x'), vec!["x".to_string()])
+        );
+        assert_eq!(log.borrow().len(), 4);
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{Editor, EditorMessage, EditorResponse, Position};
+use rhai::{Dynamic, Engine, EvalAltResult, Scope, AST};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use thiserror::Error;
+
+/// Number of operations a script may run before it is stopped
+pub const DEFAULT_MAX_SCRIPT_OPERATIONS: u64 = 100_000;
+
+/// Largest string a script may build, in bytes; reading a larger buffer's text fails too
+const MAX_STRING_SIZE: usize = 16 * 1024 * 1024;
+/// Largest array a script may build, in items
+const MAX_ARRAY_SIZE: usize = 100_000;
+/// Largest object map a script may build, in entries
+const MAX_MAP_SIZE: usize = 10_000;
+
+/// Errors that can occur while adding a script
+#[derive(Debug, Error, PartialEq)]
+pub enum ScriptError {
+    #[error("Failed to compile script {name:?}: {message}")]
+    Compile { name: String, message: String },
+}
+
+/// The editor as seen by a running script, bound to the `editor` constant
+#[derive(Clone)]
+struct ScriptEditor(Rc<RefCell<Editor>>);
+
+impl ScriptEditor {
+    fn send(&mut self, message: EditorMessage) -> Result<(), Box<EvalAltResult>> {
+        match self.0.borrow_mut().handle_message(message) {
+            EditorResponse::Error(error) => Err(error.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Compiled scripts and the sandboxed engine running them
+///
+/// Scripts cannot reach the file system or the network. They are stopped after
+/// a number of operations, so an endless loop cannot hang the editor, and when
+/// their strings, arrays or maps grow too large, so they cannot exhaust memory.
+pub(crate) struct ScriptHost {
+    engine: Engine,
+    scripts: HashMap<String, AST>,
+}
+
+impl ScriptHost {
+    pub(crate) fn new() -> Self {
+        let mut engine = Engine::new();
+        engine.set_max_operations(DEFAULT_MAX_SCRIPT_OPERATIONS);
+        engine.set_max_call_levels(32);
+        engine.set_max_expr_depths(64, 32);
+        engine.set_max_string_size(MAX_STRING_SIZE);
+        engine.set_max_array_size(MAX_ARRAY_SIZE);
+        engine.set_max_map_size(MAX_MAP_SIZE);
+        // Scripts have no console to write to
+        engine.on_print(|_| {});
+        engine.on_debug(|_, _, _| {});
+        register_editor_api(&mut engine);
+
+        Self {
+            engine,
+            scripts: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn set_max_operations(&mut self, operations: u64) {
+        self.engine.set_max_operations(operations);
+    }
+
+    pub(crate) fn compile(&mut self, name: &str, source: &str) -> Result<(), ScriptError> {
+        let ast = self
+            .engine
+            .compile(source)
+            .map_err(|e| ScriptError::Compile {
+                name: name.to_string(),
+                message: e.to_string(),
+            })?;
+        self.scripts.insert(name.to_string(), ast);
+        Ok(())
+    }
+
+    pub(crate) fn remove(&mut self, name: &str) -> bool {
+        self.scripts.remove(name).is_some()
+    }
+
+    /// Run a script against an editor taken out of its owner for the duration
+    pub(crate) fn run(&self, name: &str, editor: Rc<RefCell<Editor>>) -> Result<(), String> {
+        let ast = self
+            .scripts
+            .get(name)
+            .ok_or_else(|| format!("Unknown script {name:?}"))?;
+
+        let mut scope = Scope::new();
+        scope.push_constant("editor", ScriptEditor(editor));
+        self.engine
+            .run_ast_with_scope(&mut scope, ast)
+            .map_err(|e| format!("Script {name:?} failed: {e}"))
+    }
+}
+
+fn register_editor_api(engine: &mut Engine) {
+    engine
+        .register_type_with_name::<ScriptEditor>("Editor")
+        .register_get("text", |editor: &mut ScriptEditor| {
+            editor.0.borrow().current_buffer().text()
+        })
+        .register_get("line_count", |editor: &mut ScriptEditor| {
+            editor.0.borrow().current_buffer().line_count() as i64
+        })
+        .register_fn("line", |editor: &mut ScriptEditor, line: i64| {
+            let text = usize::try_from(line)
+                .ok()
+                .and_then(|line| editor.0.borrow().current_buffer().line_text(line))
+                .unwrap_or_default();
+            text.trim_end_matches(['\r', '\n']).to_string()
+        })
+        .register_get("cursor", |editor: &mut ScriptEditor| {
+            rhai::serde::to_dynamic(editor.0.borrow().current_cursor().position())
+        })
+        .register_get("selection", |editor: &mut ScriptEditor| {
+            match editor.0.borrow().current_selection() {
+                Some(selection) => rhai::serde::to_dynamic(selection),
+                None => Ok(Dynamic::UNIT),
+            }
+        })
+        .register_get("selected_text", |editor: &mut ScriptEditor| {
+            let editor = editor.0.borrow();
+            editor
+                .current_selection()
+                .map(|selection| selection.get_text(editor.current_buffer().rope()))
+                .unwrap_or_default()
+        })
+        // Any editor message, e.g. `"Undo"` or `#{ InsertText: "x" }`
+        .register_fn("send", |editor: &mut ScriptEditor, message: Dynamic| {
+            let message = rhai::serde::from_dynamic(&message)?;
+            editor.send(message)
+        })
+        .register_fn("insert", |editor: &mut ScriptEditor, text: &str| {
+            editor.send(EditorMessage::InsertText(text.to_string()))
+        })
+        .register_fn(
+            "move_to",
+            |editor: &mut ScriptEditor, line: i64, column: i64| {
+                let position = script_position(line, column)?;
+                editor.send(EditorMessage::MoveCursorTo(position))
+            },
+        )
+        .register_fn(
+            "select",
+            |editor: &mut ScriptEditor,
+             start_line: i64,
+             start_column: i64,
+             end_line: i64,
+             end_column: i64| {
+                editor.send(EditorMessage::SetSelection(
+                    script_position(start_line, start_column)?,
+                    script_position(end_line, end_column)?,
+                ))
+            },
+        );
+}
+
+fn script_position(line: i64, column: i64) -> Result<Position, Box<EvalAltResult>> {
+    match (usize::try_from(line), usize::try_from(column)) {
+        (Ok(line), Ok(column)) => Ok(Position::new(line, column)),
+        _ => Err(format!("Invalid position {line}:{column}").into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CursorMovement;
+
+    #[test]
+    fn test_wrap_selection_script() {
+        let mut editor = Editor::with_text("fix this\nnext");
+        editor
+            .add_script(
+                "todo",
+                r#"
+                    let text = editor.selected_text;
+                    editor.insert("TODO(" + text + ")");
+                "#,
+            )
+            .unwrap();
+
+        editor.handle_message(EditorMessage::SelectWord);
+        assert_eq!(
+            editor.handle_message(EditorMessage::RunScript("todo".to_string())),
+            EditorResponse::TextChanged
+        );
+        assert_eq!(editor.current_buffer().text(), "TODO(fix) this\nnext");
+
+        // The whole script is one undo step
+        editor.handle_message(EditorMessage::Undo);
+        assert_eq!(editor.current_buffer().text(), "fix this\nnext");
+    }
+
+    #[test]
+    fn test_renumber_list_script() {
+        let mut editor = Editor::with_text("3. one\n7. two\n1. three");
+        editor
+            .add_script(
+                "renumber",
+                r#"
+                    for i in 0..editor.line_count {
+                        let line = editor.line(i);
+                        let dot = line.index_of(". ");
+                        if dot > 0 {
+                            editor.select(i, 0, i, dot);
+                            editor.insert(`${i + 1}`);
+                        }
+                    }
+                    editor.send(#{ MoveCursor: "DocumentStart" });
+                "#,
+            )
+            .unwrap();
+
+        editor.handle_message(EditorMessage::RunScript("renumber".to_string()));
+        assert_eq!(editor.current_buffer().text(), "1. one\n2. two\n3. three");
+        assert_eq!(editor.current_cursor().position(), Position::new(0, 0));
+    }
+
+    #[test]
+    fn test_script_errors_and_limits() {
+        let mut editor = Editor::with_text("text");
+        assert!(matches!(
+            editor.add_script("broken", "let x = ;"),
+            Err(ScriptError::Compile { .. })
+        ));
+        assert!(matches!(
+            editor.handle_message(EditorMessage::RunScript("missing".to_string())),
+            EditorResponse::Error(_)
+        ));
+
+        editor.add_script("spin", "loop { }").unwrap();
+        editor.set_max_script_operations(1_000);
+        assert!(matches!(
+            editor.handle_message(EditorMessage::RunScript("spin".to_string())),
+            EditorResponse::Error(_)
+        ));
+
+        // Doubling a string runs few operations but would take gigabytes
+        editor
+            .add_script("grow", r#"let s = "x"; for i in 0..32 { s += s; }"#)
+            .unwrap();
+        assert!(matches!(
+            editor.handle_message(EditorMessage::RunScript("grow".to_string())),
+            EditorResponse::Error(_)
+        ));
+
+        // Edits made before a failure stay, and the editor keeps working
+        editor
+            .add_script("fail", r#"editor.insert("a"); editor.send("Teleport");"#)
+            .unwrap();
+        assert!(matches!(
+            editor.handle_message(EditorMessage::RunScript("fail".to_string())),
+            EditorResponse::Error(_)
+        ));
+        assert_eq!(editor.current_buffer().text(), "atext");
+        editor.handle_message(EditorMessage::MoveCursor(CursorMovement::LineEnd));
+        assert_eq!(editor.current_cursor().position(), Position::new(0, 5));
+
+        // Scripts cannot start other scripts
+        editor
+            .add_script("nested", r#"editor.send(#{ RunScript: "spin" });"#)
+            .unwrap();
+        assert!(matches!(
+            editor.handle_message(EditorMessage::RunScript("nested".to_string())),
+            EditorResponse::Error(_)
+        ));
+    }
+}
@@ -3,6 +3,7 @@ use ropey::Rope;
 
 /// Represents a text selection range
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Selection {
     pub start: Position,
     pub end: Position,
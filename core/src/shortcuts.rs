@@ -1,8 +1,37 @@
-use crate::{CursorMovement, EditorMessage, Key, KeyEvent, Modifiers, NamedKey};
-use std::collections::HashMap;
+#[cfg(feature = "serde")]
+use crate::keymap::{Keymap, KeymapError, KeymapWarning};
+use crate::{CursorMovement, Editor, EditorMessage, Key, KeyEvent, Modifiers, NamedKey};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Errors that can occur while parsing a shortcut such as `ctrl+shift+k`
+#[derive(Debug, Error, PartialEq)]
+pub enum ShortcutParseError {
+    #[error("Empty shortcut")]
+    Empty,
+    #[error("Unknown modifier {0:?}")]
+    UnknownModifier(String),
+    #[error("Unknown key {0:?}")]
+    UnknownKey(String),
+}
+
+/// Error for a `when` condition that cannot be parsed
+#[derive(Debug, Error, PartialEq)]
+#[error("Invalid condition {0:?}")]
+pub struct KeyConditionParseError(pub String);
 
 /// Represents a keyboard shortcut using the new key event system
+///
+/// With the `serde` feature it is (de)serialized as text, e.g. `"Ctrl+K"`.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "String", try_from = "String")
+)]
 pub struct Shortcut {
     pub key: Key,
     pub modifiers: Modifiers,
@@ -22,6 +51,10 @@ impl Shortcut {
     }
 
     /// Create shortcut from string description (e.g., "ctrl+a", "shift+f3")
+    ///
+    /// Unknown modifiers are ignored and unknown keys become a space; parse the
+    /// description with [`str::parse`] to get an error instead.
+    #[deprecated(note = "parse the description with `str::parse`, which reports invalid shortcuts")]
     pub fn from_string(desc: &str) -> Self {
         let mut modifiers = Modifiers::new();
         let parts: Vec<&str> = desc.split('+').collect();
@@ -37,44 +70,61 @@ impl Shortcut {
                 }
             }
 
-            let key = match key_str.to_lowercase().as_str() {
-                "left" => Key::Named(NamedKey::ArrowLeft),
-                "right" => Key::Named(NamedKey::ArrowRight),
-                "up" => Key::Named(NamedKey::ArrowUp),
-                "down" => Key::Named(NamedKey::ArrowDown),
-                "f1" => Key::Named(NamedKey::F1),
-                "f2" => Key::Named(NamedKey::F2),
-                "f3" => Key::Named(NamedKey::F3),
-                "f4" => Key::Named(NamedKey::F4),
-                "f5" => Key::Named(NamedKey::F5),
-                "f6" => Key::Named(NamedKey::F6),
-                "f7" => Key::Named(NamedKey::F7),
-                "f8" => Key::Named(NamedKey::F8),
-                "f9" => Key::Named(NamedKey::F9),
-                "f10" => Key::Named(NamedKey::F10),
-                "f11" => Key::Named(NamedKey::F11),
-                "f12" => Key::Named(NamedKey::F12),
-                "backspace" => Key::Named(NamedKey::Backspace),
-                "delete" => Key::Named(NamedKey::Delete),
-                "enter" => Key::Named(NamedKey::Enter),
-                "escape" => Key::Named(NamedKey::Escape),
-                "tab" => Key::Named(NamedKey::Tab),
-                "space" => Key::Named(NamedKey::Space),
-                "home" => Key::Named(NamedKey::Home),
-                "end" => Key::Named(NamedKey::End),
-                "pageup" => Key::Named(NamedKey::PageUp),
-                "pagedown" => Key::Named(NamedKey::PageDown),
-                "insert" => Key::Named(NamedKey::Insert),
-                s if s.len() == 1 => Key::Character(s.chars().next().unwrap()),
-                _ => Key::Character(' '), // fallback
-            };
-
+            let key = Self::parse_key(key_str).unwrap_or(Key::Character(' '));
             Self::new(key, modifiers)
         } else {
             Self::new(Key::Character(' '), Modifiers::new())
         }
     }
 
+    /// Parse a sequence of shortcuts separated by spaces, e.g. `ctrl+k ctrl+c`
+    pub fn parse_sequence(desc: &str) -> Result<Vec<Self>, ShortcutParseError> {
+        let keys = desc
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<Vec<Self>, _>>()?;
+        if keys.is_empty() {
+            return Err(ShortcutParseError::Empty);
+        }
+        Ok(keys)
+    }
+
+    /// Get the key with a name such as `pageup`, `f3` or `a`
+    fn parse_key(name: &str) -> Option<Key> {
+        let key = match name.to_lowercase().as_str() {
+            "left" => Key::Named(NamedKey::ArrowLeft),
+            "right" => Key::Named(NamedKey::ArrowRight),
+            "up" => Key::Named(NamedKey::ArrowUp),
+            "down" => Key::Named(NamedKey::ArrowDown),
+            "f1" => Key::Named(NamedKey::F1),
+            "f2" => Key::Named(NamedKey::F2),
+            "f3" => Key::Named(NamedKey::F3),
+            "f4" => Key::Named(NamedKey::F4),
+            "f5" => Key::Named(NamedKey::F5),
+            "f6" => Key::Named(NamedKey::F6),
+            "f7" => Key::Named(NamedKey::F7),
+            "f8" => Key::Named(NamedKey::F8),
+            "f9" => Key::Named(NamedKey::F9),
+            "f10" => Key::Named(NamedKey::F10),
+            "f11" => Key::Named(NamedKey::F11),
+            "f12" => Key::Named(NamedKey::F12),
+            "backspace" => Key::Named(NamedKey::Backspace),
+            "delete" => Key::Named(NamedKey::Delete),
+            "enter" => Key::Named(NamedKey::Enter),
+            "escape" => Key::Named(NamedKey::Escape),
+            "tab" => Key::Named(NamedKey::Tab),
+            "space" => Key::Named(NamedKey::Space),
+            "home" => Key::Named(NamedKey::Home),
+            "end" => Key::Named(NamedKey::End),
+            "pageup" => Key::Named(NamedKey::PageUp),
+            "pagedown" => Key::Named(NamedKey::PageDown),
+            "insert" => Key::Named(NamedKey::Insert),
+            s if s.chars().count() == 1 => Key::Character(s.chars().next().unwrap()),
+            _ => return None,
+        };
+        Some(key)
+    }
+
     /// Common shortcuts
     pub fn ctrl(key: Key) -> Self {
         Self::new(key, Modifiers::new().control())
@@ -105,70 +155,517 @@ impl Shortcut {
     }
 }
 
+/// Parses descriptions like `ctrl+shift+k` or `Cmd+PageUp`, ignoring case
+impl FromStr for Shortcut {
+    type Err = ShortcutParseError;
+
+    fn from_str(desc: &str) -> Result<Self, Self::Err> {
+        let desc = desc.trim();
+        if desc.is_empty() {
+            return Err(ShortcutParseError::Empty);
+        }
+
+        // A trailing `+` is the plus key itself, as in `ctrl++`
+        let (modifier_part, key_part) = match desc.strip_suffix("++") {
+            Some(modifier_part) => (Some(modifier_part), "+"),
+            None if desc == "+" => (None, "+"),
+            None => match desc.rsplit_once('+') {
+                Some((modifier_part, key_part)) => (Some(modifier_part), key_part),
+                None => (None, desc),
+            },
+        };
+
+        let mut modifiers = Modifiers::new();
+        for part in modifier_part.into_iter().flat_map(|part| part.split('+')) {
+            match part.to_lowercase().as_str() {
+                "ctrl" | "control" => modifiers.control = true,
+                "alt" | "option" => modifiers.alt = true,
+                "shift" => modifiers.shift = true,
+                "super" | "cmd" | "meta" => modifiers.super_key = true,
+                _ => return Err(ShortcutParseError::UnknownModifier(part.to_string())),
+            }
+        }
+
+        let key = Self::parse_key(key_part)
+            .ok_or_else(|| ShortcutParseError::UnknownKey(key_part.to_string()))?;
+        Ok(Self::new(key, modifiers))
+    }
+}
+
+impl TryFrom<String> for Shortcut {
+    type Error = ShortcutParseError;
+
+    fn try_from(desc: String) -> Result<Self, Self::Error> {
+        desc.parse()
+    }
+}
+
+impl From<Shortcut> for String {
+    fn from(shortcut: Shortcut) -> Self {
+        shortcut.to_string()
+    }
+}
+
+/// Shows the shortcut the way menus do, e.g. `Ctrl+Shift+K`
+impl fmt::Display for Shortcut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.control {
+            f.write_str("Ctrl+")?;
+        }
+        if self.modifiers.alt {
+            f.write_str("Alt+")?;
+        }
+        if self.modifiers.shift {
+            f.write_str("Shift+")?;
+        }
+        if self.modifiers.super_key {
+            f.write_str(if cfg!(target_os = "macos") {
+                "Cmd+"
+            } else {
+                "Super+"
+            })?;
+        }
+
+        match &self.key {
+            Key::Character(ch) => write!(f, "{}", ch.to_uppercase()),
+            Key::Named(NamedKey::ArrowLeft) => f.write_str("Left"),
+            Key::Named(NamedKey::ArrowRight) => f.write_str("Right"),
+            Key::Named(NamedKey::ArrowUp) => f.write_str("Up"),
+            Key::Named(NamedKey::ArrowDown) => f.write_str("Down"),
+            Key::Named(named) => write!(f, "{:?}", named),
+        }
+    }
+}
+
+/// Editor state that decides which key bindings apply
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyContext {
+    pub has_selection: bool,
+    pub completion_open: bool,
+    pub inline_suggestion_shown: bool,
+    pub in_snippet: bool,
+    /// Set by the app, the editor itself does not know about read-only views
+    pub read_only: bool,
+    /// App-defined context keys that are currently set
+    pub custom: HashSet<String>,
+}
+
+impl KeyContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a context describing the editor's current state
+    pub fn from_editor(editor: &Editor) -> Self {
+        Self::new().with_editor(editor)
+    }
+
+    /// Update the editor-derived flags, keeping `read_only` and custom keys
+    pub fn with_editor(mut self, editor: &Editor) -> Self {
+        self.has_selection = editor
+            .current_selection()
+            .is_some_and(|selection| !selection.is_empty());
+        self.completion_open = editor.completion().is_some();
+        self.inline_suggestion_shown = editor.inline_suggestion().is_some();
+        self.in_snippet = editor.is_snippet_active();
+        self
+    }
+
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Set an app-defined context key
+    pub fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.custom.insert(key.into());
+        self
+    }
+}
+
+/// Condition under which a key binding applies, like a `when` clause
+///
+/// Parses from text such as `inSnippet && !readOnly`; names other than the
+/// built-in ones are app-defined context keys.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeyCondition {
+    #[default]
+    Always,
+    HasSelection,
+    CompletionOpen,
+    InlineSuggestionShown,
+    InSnippet,
+    ReadOnly,
+    /// An app-defined context key is set
+    Custom(String),
+    Not(Box<KeyCondition>),
+    /// Every condition holds
+    All(Vec<KeyCondition>),
+}
+
+impl KeyCondition {
+    /// Check the condition against the editor's state
+    pub fn holds(&self, context: &KeyContext) -> bool {
+        match self {
+            Self::Always => true,
+            Self::HasSelection => context.has_selection,
+            Self::CompletionOpen => context.completion_open,
+            Self::InlineSuggestionShown => context.inline_suggestion_shown,
+            Self::InSnippet => context.in_snippet,
+            Self::ReadOnly => context.read_only,
+            Self::Custom(key) => context.custom.contains(key),
+            Self::Not(condition) => !condition.holds(context),
+            Self::All(conditions) => conditions.iter().all(|condition| condition.holds(context)),
+        }
+    }
+}
+
+impl FromStr for KeyCondition {
+    type Err = KeyConditionParseError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut conditions = text
+            .split("&&")
+            .map(|term| {
+                let term = term.trim();
+                let (negated, name) = match term.strip_prefix('!') {
+                    Some(name) => (true, name.trim()),
+                    None => (false, term),
+                };
+                let is_name = |ch: char| ch.is_alphanumeric() || ch == '_' || ch == '.';
+                if name.is_empty() || !name.chars().all(is_name) {
+                    return Err(KeyConditionParseError(text.to_string()));
+                }
+
+                let condition = match name {
+                    "true" => Self::Always,
+                    "hasSelection" => Self::HasSelection,
+                    "completionOpen" => Self::CompletionOpen,
+                    "inlineSuggestionShown" => Self::InlineSuggestionShown,
+                    "inSnippet" => Self::InSnippet,
+                    "readOnly" => Self::ReadOnly,
+                    custom => Self::Custom(custom.to_string()),
+                };
+                Ok(if negated { !condition } else { condition })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(if conditions.len() == 1 {
+            conditions.remove(0)
+        } else {
+            Self::All(conditions)
+        })
+    }
+}
+
+impl std::ops::Not for KeyCondition {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self::Not(Box::new(self))
+    }
+}
+
 /// Represents a key binding that maps shortcuts to editor messages
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KeyBinding {
-    pub shortcut: Shortcut,
+    /// Shortcuts pressed one after the other; usually just one
+    pub keys: Vec<Shortcut>,
     pub message: EditorMessage,
+    #[cfg_attr(feature = "serde", serde(default))]
     pub description: String,
+    /// When the binding applies
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub when: KeyCondition,
 }
 
 impl KeyBinding {
     pub fn new(shortcut: Shortcut, message: EditorMessage, description: &str) -> Self {
+        Self::chord(vec![shortcut], message, description)
+    }
+
+    /// Create a binding for a sequence of shortcuts, e.g. `Ctrl+K Ctrl+C` or `g g`
+    pub fn chord(keys: Vec<Shortcut>, message: EditorMessage, description: &str) -> Self {
         Self {
-            shortcut,
+            keys,
             message,
             description: description.to_string(),
+            when: KeyCondition::Always,
+        }
+    }
+
+    /// Only apply the binding while a condition holds
+    pub fn when(mut self, when: KeyCondition) -> Self {
+        self.when = when;
+        self
+    }
+}
+
+/// A message bound to a key sequence under some condition
+#[derive(Debug, Clone)]
+struct ConditionalMessage {
+    when: KeyCondition,
+    message: EditorMessage,
+    description: String,
+}
+
+/// Keys typed so far towards a multi-key binding
+///
+/// Kept by the UI between key events and passed to
+/// [`ShortcutManager::handle_key_sequence`].
+#[derive(Debug, Clone, Default)]
+pub struct ChordState {
+    pending: Vec<Shortcut>,
+    expires_at: Option<Instant>,
+}
+
+impl ChordState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the keys typed so far
+    pub fn pending_keys(&self) -> &[Shortcut] {
+        &self.pending
+    }
+
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Get when the pending keys stop waiting for the rest of the chord
+    pub fn expires_at(&self) -> Option<Instant> {
+        self.expires_at
+    }
+
+    pub fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+
+    /// Describe the pending keys for a status bar, e.g. `Ctrl+K …`
+    pub fn status(&self) -> Option<String> {
+        if self.pending.is_empty() {
+            return None;
         }
+
+        let keys: Vec<String> = self.pending.iter().map(ToString::to_string).collect();
+        Some(format!("{} …", keys.join(" ")))
+    }
+
+    /// Forget the pending keys without running anything
+    pub fn cancel(&mut self) {
+        self.pending.clear();
+        self.expires_at = None;
     }
 }
 
 /// Manages keyboard shortcuts and key bindings
 #[derive(Debug, Clone)]
 pub struct ShortcutManager {
-    bindings: HashMap<Shortcut, EditorMessage>,
-    descriptions: HashMap<Shortcut, String>,
+    /// Bindings of each key sequence, in the order they were bound
+    bindings: HashMap<Vec<Shortcut>, Vec<ConditionalMessage>>,
+    chord_timeout: Duration,
 }
 
 impl ShortcutManager {
+    /// How long a chord waits for its next key by default
+    pub const DEFAULT_CHORD_TIMEOUT: Duration = Duration::from_millis(1000);
+
     pub fn new() -> Self {
         let mut manager = Self {
             bindings: HashMap::new(),
-            descriptions: HashMap::new(),
+            chord_timeout: Self::DEFAULT_CHORD_TIMEOUT,
         };
 
         manager.load_default_bindings();
         manager
     }
 
+    /// Create a manager with Emacs key bindings over the defaults
+    ///
+    /// Ctrl+Space sets the mark, so completion moves to Alt+/. Ctrl+X starts
+    /// chords such as Ctrl+X H to select all. Ctrl+S and Ctrl+R are left
+    /// unbound, as there is no incremental search; F3 and Shift+F3 still move
+    /// between the matches of the last `Find`.
+    pub fn emacs() -> Self {
+        let mut manager = Self::new();
+        manager.load_emacs_bindings();
+        manager
+    }
+
     /// Add a key binding
+    ///
+    /// Keys may be bound several times under different conditions; the binding
+    /// added last wins among those whose condition holds. Binding the same keys
+    /// under the same condition replaces the earlier binding.
+    ///
+    /// A chord may share its first keys with a shorter binding. The shorter one
+    /// then runs once the chord times out or the next key does not continue it.
     pub fn bind(&mut self, binding: KeyBinding) {
-        self.bindings
-            .insert(binding.shortcut.clone(), binding.message);
-        self.descriptions
-            .insert(binding.shortcut, binding.description);
+        let bound = self.bindings.entry(binding.keys).or_default();
+        bound.retain(|bound| bound.when != binding.when);
+        bound.push(ConditionalMessage {
+            when: binding.when,
+            message: binding.message,
+            description: binding.description,
+        });
     }
 
-    /// Remove a key binding
+    /// Remove a key binding under every condition
     pub fn unbind(&mut self, shortcut: &Shortcut) {
-        self.bindings.remove(shortcut);
-        self.descriptions.remove(shortcut);
+        self.unbind_chord(std::slice::from_ref(shortcut));
+    }
+
+    /// Remove a multi-key binding under every condition
+    pub fn unbind_chord(&mut self, keys: &[Shortcut]) {
+        self.bindings.remove(keys);
     }
 
-    /// Get message for a shortcut
+    /// Get message for a shortcut outside of any particular editor state
     pub fn get_message(&self, shortcut: &Shortcut) -> Option<&EditorMessage> {
-        self.bindings.get(shortcut)
+        self.lookup(std::slice::from_ref(shortcut), &KeyContext::default())
+    }
+
+    /// Get the key sequences running a message outside of any particular editor state
+    ///
+    /// Shorter sequences come first, e.g. to show the simplest one in a menu.
+    pub fn keys_for(&self, message: &EditorMessage) -> Vec<Vec<Shortcut>> {
+        let mut keys: Vec<Vec<Shortcut>> = self
+            .bindings
+            .keys()
+            .filter(|keys| self.lookup(keys, &KeyContext::default()) == Some(message))
+            .cloned()
+            .collect();
+        keys.sort_by_cached_key(|keys| {
+            let text: Vec<String> = keys.iter().map(Shortcut::to_string).collect();
+            (keys.len(), text)
+        });
+        keys
+    }
+
+    /// Get the message bound to a key sequence whose condition holds
+    fn lookup(&self, keys: &[Shortcut], context: &KeyContext) -> Option<&EditorMessage> {
+        self.bindings
+            .get(keys)?
+            .iter()
+            .rev()
+            .find(|bound| bound.when.holds(context))
+            .map(|bound| &bound.message)
+    }
+
+    /// Set how long a chord waits for its next key
+    pub fn set_chord_timeout(&mut self, timeout: Duration) {
+        self.chord_timeout = timeout;
+    }
+
+    pub fn chord_timeout(&self) -> Duration {
+        self.chord_timeout
     }
 
     /// Handle key event - returns either a shortcut event or character input
+    ///
+    /// Only single-key bindings without conditions are considered; use
+    /// [`Self::handle_key_sequence`] to also resolve chords and conditions.
     pub fn handle_key_event(&self, event: KeyEvent) -> Option<EditorMessage> {
+        self.handle_key_event_in(event, &KeyContext::default())
+    }
+
+    /// Handle a single key event, picking the binding whose condition holds
+    pub fn handle_key_event_in(
+        &self,
+        event: KeyEvent,
+        context: &KeyContext,
+    ) -> Option<EditorMessage> {
         let shortcut = Shortcut::from_key_event(event.clone());
 
         // Check if this is a shortcut first
-        if let Some(message) = self.bindings.get(&shortcut) {
+        if let Some(message) = self.lookup(std::slice::from_ref(&shortcut), context) {
             return Some(message.clone());
         }
 
+        Self::text_input(event)
+    }
+
+    /// Handle a key event that may continue or start a chord
+    ///
+    /// Returns nothing while the keys typed so far are the start of a longer
+    /// binding. Several messages are returned when a pending prefix with its own
+    /// binding is interrupted by an unrelated key.
+    pub fn handle_key_sequence(
+        &self,
+        state: &mut ChordState,
+        event: KeyEvent,
+        context: &KeyContext,
+    ) -> Vec<EditorMessage> {
+        self.handle_key_sequence_at(state, event, context, Instant::now())
+    }
+
+    /// Handle a key event that may continue or start a chord, typed at a given time
+    pub fn handle_key_sequence_at(
+        &self,
+        state: &mut ChordState,
+        event: KeyEvent,
+        context: &KeyContext,
+        now: Instant,
+    ) -> Vec<EditorMessage> {
+        let mut messages = Vec::new();
+        if state.is_expired(now) {
+            messages.extend(self.flush_chord(state, context));
+        }
+
+        let shortcut = Shortcut::from_key_event(event.clone());
+        if state.is_pending()
+            && shortcut == Shortcut::new(Key::Named(NamedKey::Escape), Modifiers::new())
+        {
+            state.cancel();
+            return messages;
+        }
+
+        let mut keys = state.pending.clone();
+        keys.push(shortcut);
+
+        if self.is_chord_prefix(&keys, context) {
+            state.pending = keys;
+            state.expires_at = Some(now + self.chord_timeout);
+        } else if let Some(message) = self.lookup(&keys, context) {
+            state.cancel();
+            messages.push(message.clone());
+        } else if state.is_pending() {
+            // The key does not continue the chord, so finish the prefix and start over
+            messages.extend(self.flush_chord(state, context));
+            messages.extend(self.handle_key_sequence_at(state, event, context, now));
+        } else {
+            messages.extend(Self::text_input(event));
+        }
+
+        messages
+    }
+
+    /// Give up waiting for the rest of a chord, returning the binding of the keys
+    /// typed so far if they have one
+    pub fn flush_chord(
+        &self,
+        state: &mut ChordState,
+        context: &KeyContext,
+    ) -> Option<EditorMessage> {
+        let message = self.lookup(&state.pending, context).cloned();
+        state.cancel();
+        message
+    }
+
+    /// Check if some applicable binding starts with the given keys and is longer
+    fn is_chord_prefix(&self, keys: &[Shortcut], context: &KeyContext) -> bool {
+        self.bindings.iter().any(|(bound_keys, bound)| {
+            bound_keys.len() > keys.len()
+                && bound_keys.starts_with(keys)
+                && bound.iter().any(|bound| bound.when.holds(context))
+        })
+    }
+
+    /// Turn a key that is not bound into typed text
+    fn text_input(event: KeyEvent) -> Option<EditorMessage> {
         // If not a shortcut and it's a character with no modifiers (except shift), treat as character input
         match event.key {
             Key::Character(ch) => {
@@ -212,13 +709,11 @@ impl ShortcutManager {
     pub fn get_bindings(&self) -> Vec<KeyBinding> {
         self.bindings
             .iter()
-            .map(|(shortcut, message)| {
-                let description = self
-                    .descriptions
-                    .get(shortcut)
-                    .cloned()
-                    .unwrap_or_else(|| "No description".to_string());
-                KeyBinding::new(shortcut.clone(), message.clone(), &description)
+            .flat_map(|(keys, bound)| {
+                bound.iter().map(|bound| {
+                    KeyBinding::chord(keys.clone(), bound.message.clone(), &bound.description)
+                        .when(bound.when.clone())
+                })
             })
             .collect()
     }
@@ -379,6 +874,12 @@ impl ShortcutManager {
             "Select page down",
         ));
 
+        self.bind(KeyBinding::new(
+            Shortcut::new(Key::Named(NamedKey::Insert), Modifiers::new()),
+            EditorMessage::ToggleOverwrite,
+            "Toggle overwrite mode",
+        ));
+
         // Basic deletion
         self.bind(KeyBinding::new(
             Shortcut::new(Key::Named(NamedKey::Delete), Modifiers::new()),
@@ -532,6 +1033,124 @@ impl ShortcutManager {
             "Replace",
         ));
 
+        // Diagnostics
+        self.bind(KeyBinding::new(
+            Shortcut::new(Key::Named(NamedKey::F8), Modifiers::new()),
+            EditorMessage::NextDiagnostic,
+            "Go to next diagnostic",
+        ));
+
+        self.bind(KeyBinding::new(
+            Shortcut::shift(Key::Named(NamedKey::F8)),
+            EditorMessage::PreviousDiagnostic,
+            "Go to previous diagnostic",
+        ));
+
+        // Completion
+        self.bind(KeyBinding::new(
+            Shortcut::ctrl(Key::Named(NamedKey::Space)),
+            EditorMessage::TriggerCompletion,
+            "Trigger completion",
+        ));
+        self.bind(KeyBinding::new(
+            Shortcut::alt(Key::Character('\\')),
+            EditorMessage::TriggerInlineSuggestion,
+            "Trigger inline suggestion",
+        ));
+
+        // Keys driving an active snippet, inline suggestion or completion popup.
+        // Later bindings win, so the completion popup comes first when several apply.
+        self.bind(
+            KeyBinding::new(
+                Shortcut::new(Key::Named(NamedKey::Tab), Modifiers::new()),
+                EditorMessage::NextTabStop,
+                "Go to next tab stop",
+            )
+            .when(KeyCondition::InSnippet),
+        );
+        self.bind(
+            KeyBinding::new(
+                Shortcut::shift(Key::Named(NamedKey::Tab)),
+                EditorMessage::PreviousTabStop,
+                "Go to previous tab stop",
+            )
+            .when(KeyCondition::InSnippet),
+        );
+        self.bind(
+            KeyBinding::new(
+                Shortcut::new(Key::Named(NamedKey::Escape), Modifiers::new()),
+                EditorMessage::EndSnippet,
+                "End snippet",
+            )
+            .when(KeyCondition::InSnippet),
+        );
+
+        self.bind(
+            KeyBinding::new(
+                Shortcut::new(Key::Named(NamedKey::Tab), Modifiers::new()),
+                EditorMessage::AcceptInlineSuggestion,
+                "Accept inline suggestion",
+            )
+            .when(KeyCondition::InlineSuggestionShown),
+        );
+        self.bind(
+            KeyBinding::new(
+                Shortcut::ctrl(Key::Named(NamedKey::ArrowRight)),
+                EditorMessage::AcceptInlineSuggestionWord,
+                "Accept next word of inline suggestion",
+            )
+            .when(KeyCondition::InlineSuggestionShown),
+        );
+        self.bind(
+            KeyBinding::new(
+                Shortcut::new(Key::Named(NamedKey::Escape), Modifiers::new()),
+                EditorMessage::DismissInlineSuggestion,
+                "Dismiss inline suggestion",
+            )
+            .when(KeyCondition::InlineSuggestionShown),
+        );
+
+        self.bind(
+            KeyBinding::new(
+                Shortcut::new(Key::Named(NamedKey::ArrowUp), Modifiers::new()),
+                EditorMessage::SelectPreviousCompletion,
+                "Select previous completion",
+            )
+            .when(KeyCondition::CompletionOpen),
+        );
+        self.bind(
+            KeyBinding::new(
+                Shortcut::new(Key::Named(NamedKey::ArrowDown), Modifiers::new()),
+                EditorMessage::SelectNextCompletion,
+                "Select next completion",
+            )
+            .when(KeyCondition::CompletionOpen),
+        );
+        self.bind(
+            KeyBinding::new(
+                Shortcut::new(Key::Named(NamedKey::Enter), Modifiers::new()),
+                EditorMessage::AcceptCompletion,
+                "Accept completion",
+            )
+            .when(KeyCondition::CompletionOpen),
+        );
+        self.bind(
+            KeyBinding::new(
+                Shortcut::new(Key::Named(NamedKey::Tab), Modifiers::new()),
+                EditorMessage::AcceptCompletion,
+                "Accept completion",
+            )
+            .when(KeyCondition::CompletionOpen),
+        );
+        self.bind(
+            KeyBinding::new(
+                Shortcut::new(Key::Named(NamedKey::Escape), Modifiers::new()),
+                EditorMessage::CancelCompletion,
+                "Cancel completion",
+            )
+            .when(KeyCondition::CompletionOpen),
+        );
+
         // macOS specific bindings
         if cfg!(target_os = "macos") {
             // Basic movement with Cmd key (Super)
@@ -735,6 +1354,191 @@ impl ShortcutManager {
         }
     }
 
+    /// Load the bindings of the Emacs preset
+    fn load_emacs_bindings(&mut self) {
+        let moves = [
+            ('a', CursorMovement::LineStart, "Move to line start"),
+            ('e', CursorMovement::LineEnd, "Move to line end"),
+            ('f', CursorMovement::Right, "Move forward a char"),
+            ('b', CursorMovement::Left, "Move back a char"),
+            ('n', CursorMovement::Down, "Move to next line"),
+            ('p', CursorMovement::Up, "Move to previous line"),
+            ('v', CursorMovement::PageDown, "Scroll down a page"),
+        ];
+        for (ch, movement, description) in moves {
+            self.bind(KeyBinding::new(
+                Shortcut::ctrl(Key::Character(ch)),
+                EditorMessage::MoveCursor(movement),
+                description,
+            ));
+        }
+
+        let meta_moves = [
+            ('f', CursorMovement::WordRight, "Move forward a word"),
+            ('b', CursorMovement::WordLeft, "Move back a word"),
+            ('v', CursorMovement::PageUp, "Scroll up a page"),
+        ];
+        for (ch, movement, description) in meta_moves {
+            self.bind(KeyBinding::new(
+                Shortcut::alt(Key::Character(ch)),
+                EditorMessage::MoveCursor(movement),
+                description,
+            ));
+        }
+
+        // Mark, kill and yank
+        self.bind(KeyBinding::new(
+            Shortcut::ctrl(Key::Named(NamedKey::Space)),
+            EditorMessage::SetMark,
+            "Set mark",
+        ));
+        self.bind(KeyBinding::new(
+            Shortcut::ctrl(Key::Character('g')),
+            EditorMessage::ClearSelection,
+            "Deactivate mark",
+        ));
+        self.bind(KeyBinding::new(
+            Shortcut::ctrl(Key::Character('k')),
+            EditorMessage::KillToLineEnd,
+            "Kill to end of line",
+        ));
+        self.bind(KeyBinding::new(
+            Shortcut::ctrl(Key::Character('w')),
+            EditorMessage::KillRegion,
+            "Kill region",
+        ));
+        self.bind(KeyBinding::new(
+            Shortcut::alt(Key::Character('w')),
+            EditorMessage::Copy,
+            "Copy region",
+        ));
+        self.bind(KeyBinding::new(
+            Shortcut::ctrl(Key::Character('y')),
+            EditorMessage::Paste,
+            "Yank",
+        ));
+        self.bind(KeyBinding::new(
+            Shortcut::ctrl(Key::Character('d')),
+            EditorMessage::DeleteChar,
+            "Delete char",
+        ));
+        self.bind(KeyBinding::new(
+            Shortcut::alt(Key::Character('d')),
+            EditorMessage::DeleteWordForward,
+            "Delete word",
+        ));
+        self.bind(KeyBinding::new(
+            Shortcut::alt(Key::Named(NamedKey::Backspace)),
+            EditorMessage::DeleteWordBackward,
+            "Delete word backward",
+        ));
+
+        // Ctrl+X only starts chords
+        self.unbind(&Shortcut::ctrl(Key::Character('x')));
+        self.bind(KeyBinding::chord(
+            vec![
+                Shortcut::ctrl(Key::Character('x')),
+                Shortcut::new(Key::Character('h'), Modifiers::new()),
+            ],
+            EditorMessage::SelectAll,
+            "Select all",
+        ));
+        self.bind(KeyBinding::chord(
+            vec![
+                Shortcut::ctrl(Key::Character('x')),
+                Shortcut::new(Key::Character('u'), Modifiers::new()),
+            ],
+            EditorMessage::Undo,
+            "Undo",
+        ));
+        self.bind(KeyBinding::new(
+            Shortcut::ctrl(Key::Character('/')),
+            EditorMessage::Undo,
+            "Undo",
+        ));
+
+        self.bind(KeyBinding::new(
+            Shortcut::alt(Key::Character('/')),
+            EditorMessage::TriggerCompletion,
+            "Trigger completion",
+        ));
+
+        // The completion popup keeps its keys
+        let completion_keys = [
+            (
+                'n',
+                EditorMessage::SelectNextCompletion,
+                "Select next completion",
+            ),
+            (
+                'p',
+                EditorMessage::SelectPreviousCompletion,
+                "Select previous completion",
+            ),
+            ('g', EditorMessage::CancelCompletion, "Close completion"),
+        ];
+        for (ch, message, description) in completion_keys {
+            self.bind(
+                KeyBinding::new(Shortcut::ctrl(Key::Character(ch)), message, description)
+                    .when(KeyCondition::CompletionOpen),
+            );
+        }
+    }
+
+    /// Merge a keymap over the current bindings
+    ///
+    /// Nothing is bound when an entry is invalid; every invalid entry is reported.
+    /// On success, warnings point out entries that may not behave as intended.
+    #[cfg(feature = "serde")]
+    pub fn load_keymap(&mut self, keymap: &Keymap) -> Result<Vec<KeymapWarning>, Vec<KeymapError>> {
+        let bindings = keymap.bindings()?;
+        let mut warnings = Vec::new();
+
+        // Entries binding the same keys under the same condition
+        for (second, binding) in bindings.iter().enumerate() {
+            if let Some(first) = bindings[..second]
+                .iter()
+                .position(|other| other.keys == binding.keys && other.when == binding.when)
+            {
+                warnings.push(KeymapWarning::Duplicate {
+                    keys: binding.keys.clone(),
+                    first,
+                    second,
+                });
+            }
+        }
+
+        let entry_keys: Vec<Vec<Shortcut>> = bindings.iter().map(|b| b.keys.clone()).collect();
+        for binding in bindings {
+            self.bind(binding);
+        }
+
+        // Bindings that wait for a longer chord starting with their keys
+        let mut shadowed: Vec<(Vec<Shortcut>, Vec<Shortcut>)> = Vec::new();
+        for entry_keys in &entry_keys {
+            for bound_keys in self.bindings.keys() {
+                let (keys, chord) = if bound_keys.len() > entry_keys.len() {
+                    (entry_keys, bound_keys)
+                } else {
+                    (bound_keys, entry_keys)
+                };
+                if chord.len() > keys.len()
+                    && chord.starts_with(keys)
+                    && !shadowed.iter().any(|(k, c)| k == keys && c == chord)
+                {
+                    shadowed.push((keys.clone(), chord.clone()));
+                }
+            }
+        }
+        warnings.extend(
+            shadowed
+                .into_iter()
+                .map(|(keys, chord)| KeymapWarning::ShadowedByChord { keys, chord }),
+        );
+
+        Ok(warnings)
+    }
+
     /// Load bindings from configuration
     pub fn load_from_config(&mut self, bindings: Vec<KeyBinding>) {
         for binding in bindings {
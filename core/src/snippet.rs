@@ -0,0 +1,298 @@
+use crate::anchor::AnchorId;
+use std::ops::Range;
+use thiserror::Error;
+
+/// Errors that can occur while parsing a snippet
+#[derive(Debug, Error, PartialEq)]
+pub enum SnippetError {
+    #[error("Unclosed placeholder starting at offset {0}")]
+    UnclosedPlaceholder(usize),
+    #[error("Invalid tab stop at offset {0}")]
+    InvalidTabStop(usize),
+}
+
+/// A tab stop of a snippet and every place it appears
+///
+/// The first range is the one the user edits; the others mirror its text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TabStop {
+    pub index: u32,
+    /// Char ranges within the snippet text
+    pub ranges: Vec<Range<usize>>,
+}
+
+/// A parsed snippet in LSP/TextMate syntax, e.g. `fn ${1:name}($2) {\n\t$0\n}`
+///
+/// Supported are `$1`, `${1}`, `${1:placeholder}` (placeholders may nest),
+/// `${1|one,two|}` (the first choice is inserted) and `\` escapes for `$`, `}` and `\`.
+/// Repeated tab stops are left empty here; the editor fills them in from the first
+/// occurrence when the snippet is inserted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snippet {
+    /// Text with all placeholders expanded
+    pub text: String,
+    /// Tab stops in the order they are visited; `$0` comes last
+    pub tab_stops: Vec<TabStop>,
+}
+
+impl Snippet {
+    pub fn parse(source: &str) -> Result<Self, SnippetError> {
+        let chars: Vec<char> = source.chars().collect();
+        let mut parser = Parser {
+            chars: &chars,
+            pos: 0,
+            text: String::new(),
+            text_len: 0,
+            stops: Vec::new(),
+        };
+        parser.parse_until(None)?;
+
+        let mut tab_stops: Vec<TabStop> = Vec::new();
+        for (index, range) in parser.stops {
+            match tab_stops.iter_mut().find(|stop| stop.index == index) {
+                // A placeholder's text is the primary occurrence if the first one was bare
+                Some(stop) if !range.is_empty() && stop.ranges[0].is_empty() => {
+                    stop.ranges.insert(0, range)
+                }
+                Some(stop) => stop.ranges.push(range),
+                None => tab_stops.push(TabStop {
+                    index,
+                    ranges: vec![range],
+                }),
+            }
+        }
+
+        // `$0` is visited last, and is implied at the end when missing
+        tab_stops.sort_by_key(|stop| {
+            if stop.index == 0 {
+                u32::MAX
+            } else {
+                stop.index
+            }
+        });
+        if tab_stops.last().is_none_or(|stop| stop.index != 0) {
+            let end = parser.text_len..parser.text_len;
+            tab_stops.push(TabStop {
+                index: 0,
+                ranges: vec![end],
+            });
+        }
+
+        Ok(Self {
+            text: parser.text,
+            tab_stops,
+        })
+    }
+
+    /// Indent every line after the first, so the snippet lines up with the line it is inserted on
+    pub fn indented(mut self, indent: &str) -> Self {
+        if indent.is_empty() {
+            return self;
+        }
+
+        let indent_len = indent.chars().count();
+        let newline_offsets: Vec<usize> = self
+            .text
+            .chars()
+            .enumerate()
+            .filter(|(_, ch)| *ch == '\n')
+            .map(|(offset, _)| offset)
+            .collect();
+
+        // Offsets after the n-th newline move by n indents
+        let shift = |offset: usize| {
+            offset + newline_offsets.iter().filter(|&&nl| nl < offset).count() * indent_len
+        };
+        for stop in &mut self.tab_stops {
+            for range in &mut stop.ranges {
+                *range = shift(range.start)..shift(range.end);
+            }
+        }
+
+        self.text = self.text.replace('\n', &format!("\n{indent}"));
+        self
+    }
+}
+
+struct Parser<'a> {
+    chars: &'a [char],
+    pos: usize,
+    text: String,
+    /// Length of `text` in chars
+    text_len: usize,
+    stops: Vec<(u32, Range<usize>)>,
+}
+
+impl Parser<'_> {
+    /// Parse text until the closing char of a placeholder, or the end of input
+    fn parse_until(&mut self, close: Option<char>) -> Result<(), SnippetError> {
+        while let Some(&ch) = self.chars.get(self.pos) {
+            match ch {
+                '\\' if matches!(self.chars.get(self.pos + 1), Some('$' | '}' | '\\')) => {
+                    self.push(self.chars[self.pos + 1]);
+                    self.pos += 2;
+                }
+                '$' => self.parse_tab_stop()?,
+                ch if Some(ch) == close => return Ok(()),
+                ch => {
+                    self.push(ch);
+                    self.pos += 1;
+                }
+            }
+        }
+
+        match close {
+            Some(_) => Err(SnippetError::UnclosedPlaceholder(self.pos)),
+            None => Ok(()),
+        }
+    }
+
+    fn parse_tab_stop(&mut self) -> Result<(), SnippetError> {
+        let start = self.pos;
+        self.pos += 1;
+
+        // `$1`
+        if let Some(index) = self.parse_number() {
+            self.stops.push((index, self.text_len..self.text_len));
+            return Ok(());
+        }
+
+        // Anything else that is not `${` is literal text
+        if self.chars.get(self.pos) != Some(&'{') {
+            self.push('$');
+            return Ok(());
+        }
+        self.pos += 1;
+
+        let index = self
+            .parse_number()
+            .ok_or(SnippetError::InvalidTabStop(start))?;
+        let text_start = self.text_len;
+
+        match self.chars.get(self.pos) {
+            Some('}') => self.pos += 1,
+            Some(':') => {
+                self.pos += 1;
+                // Reserve the slot so the outer stop keeps its order relative to nested ones
+                let slot = self.stops.len();
+                self.stops.push((index, 0..0));
+                self.parse_until(Some('}'))?;
+                self.pos += 1;
+                self.stops[slot].1 = text_start..self.text_len;
+                return Ok(());
+            }
+            Some('|') => {
+                self.pos += 1;
+                let choices_end = self.chars[self.pos..]
+                    .windows(2)
+                    .position(|pair| pair == ['|', '}'])
+                    .ok_or(SnippetError::UnclosedPlaceholder(start))?;
+                let first_choice: String = self.chars[self.pos..self.pos + choices_end]
+                    .iter()
+                    .take_while(|&&ch| ch != ',')
+                    .collect();
+                first_choice.chars().for_each(|ch| self.push(ch));
+                self.pos += choices_end + 2;
+            }
+            _ => return Err(SnippetError::InvalidTabStop(start)),
+        }
+
+        self.stops.push((index, text_start..self.text_len));
+        Ok(())
+    }
+
+    fn parse_number(&mut self) -> Option<u32> {
+        let digits: String = self.chars[self.pos..]
+            .iter()
+            .take_while(|ch| ch.is_ascii_digit())
+            .collect();
+        let number = digits.parse().ok()?;
+        self.pos += digits.len();
+        Some(number)
+    }
+
+    fn push(&mut self, ch: char) {
+        self.text.push(ch);
+        self.text_len += 1;
+    }
+}
+
+/// One occurrence of a tab stop in the buffer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct AnchoredRange {
+    pub start: AnchorId,
+    pub end: AnchorId,
+}
+
+/// A snippet being filled in: its tab stops tracked by anchors and the current one
+#[derive(Debug, Clone)]
+pub(crate) struct SnippetSession {
+    /// Occurrences of each tab stop, in visiting order; the first is edited, the others mirror it
+    pub stops: Vec<Vec<AnchoredRange>>,
+    pub current: usize,
+}
+
+impl SnippetSession {
+    pub fn current_stop(&self) -> &[AnchoredRange] {
+        &self.stops[self.current]
+    }
+
+    /// Check if the current tab stop is the last one
+    pub fn is_at_last_stop(&self) -> bool {
+        self.current + 1 == self.stops.len()
+    }
+
+    /// Anchors owned by the session, so they can be removed when it ends
+    pub fn anchors(&self) -> impl Iterator<Item = AnchorId> + '_ {
+        self.stops
+            .iter()
+            .flatten()
+            .flat_map(|range| [range.start, range.end])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tab_stops_and_placeholders() {
+        let snippet = Snippet::parse("fn ${1:name}($2) {\n\t$0\n}").unwrap();
+        assert_eq!(snippet.text, "fn name() {\n\t\n}");
+        let indices: Vec<u32> = snippet.tab_stops.iter().map(|stop| stop.index).collect();
+        assert_eq!(indices, vec![1, 2, 0]);
+        assert_eq!(snippet.tab_stops[0].ranges, vec![3..7]);
+        assert_eq!(snippet.tab_stops[1].ranges, vec![8..8]);
+        assert_eq!(snippet.tab_stops[2].ranges, vec![13..13]);
+
+        // Without `$0` the snippet ends after its text; mirrors start out empty
+        let snippet = Snippet::parse("${1:a} \\$1 ${2|x,y|} $1").unwrap();
+        assert_eq!(snippet.text, "a $1 x ");
+        assert_eq!(snippet.tab_stops[0].ranges, vec![0..1, 7..7]);
+        assert_eq!(snippet.tab_stops[1].ranges, vec![5..6]);
+        assert_eq!(snippet.tab_stops[2].ranges, vec![7..7]);
+    }
+
+    #[test]
+    fn test_nested_placeholders_and_errors() {
+        let snippet = Snippet::parse("${1:outer ${2:inner}}").unwrap();
+        assert_eq!(snippet.text, "outer inner");
+        assert_eq!(snippet.tab_stops[0].ranges, vec![0..11]);
+        assert_eq!(snippet.tab_stops[1].ranges, vec![6..11]);
+
+        assert_eq!(
+            Snippet::parse("${1:oops"),
+            Err(SnippetError::UnclosedPlaceholder(8))
+        );
+        assert_eq!(Snippet::parse("${x}"), Err(SnippetError::InvalidTabStop(0)));
+        assert_eq!(Snippet::parse("cost: $").unwrap().text, "cost: $");
+    }
+
+    #[test]
+    fn test_indented_snippet() {
+        let snippet = Snippet::parse("if $1 {\n\t$0\n}").unwrap().indented("    ");
+        assert_eq!(snippet.text, "if  {\n    \t\n    }");
+        assert_eq!(snippet.tab_stops[0].ranges, vec![3..3]);
+        assert_eq!(snippet.tab_stops[1].ranges, vec![11..11]);
+    }
+}
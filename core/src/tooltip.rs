@@ -0,0 +1,111 @@
+use crate::Position;
+use std::ops::Range;
+
+/// A paragraph of tooltip content
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TooltipBlock {
+    /// Prose, wrapped by the UI as needed
+    Text(String),
+    /// Preformatted code, shown in a monospace font as is
+    Code(String),
+}
+
+/// Information shown in an overlay anchored to a buffer position, e.g. hover docs
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tooltip {
+    /// Where the tooltip is anchored
+    pub position: Position,
+    /// Text the tooltip is about; the tooltip stays open while the mouse is over it
+    pub range: Option<Range<Position>>,
+    pub blocks: Vec<TooltipBlock>,
+}
+
+impl Tooltip {
+    /// Create a tooltip showing plain text
+    pub fn text(position: Position, text: impl Into<String>) -> Self {
+        Self {
+            position,
+            range: None,
+            blocks: vec![TooltipBlock::Text(text.into())],
+        }
+    }
+
+    /// Create a tooltip from Markdown, splitting fenced code blocks from the prose around them
+    ///
+    /// Other Markdown syntax is kept as is.
+    pub fn markdown(position: Position, markdown: &str) -> Self {
+        let mut blocks = Vec::new();
+        let mut current = String::new();
+        let mut in_code = false;
+
+        for line in markdown.lines() {
+            if line.trim_start().starts_with("```") {
+                push_block(&mut blocks, &mut current, in_code);
+                in_code = !in_code;
+                continue;
+            }
+            if !current.is_empty() {
+                current.push('\n');
+            }
+            current.push_str(line);
+        }
+        push_block(&mut blocks, &mut current, in_code);
+
+        Self {
+            position,
+            range: None,
+            blocks,
+        }
+    }
+
+    /// Keep the tooltip open while the mouse is over a range
+    pub fn with_range(mut self, range: Range<Position>) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    /// Check if a position is on what the tooltip is about
+    pub fn covers(&self, position: Position) -> bool {
+        let key = |position: Position| (position.line, position.column);
+        match &self.range {
+            Some(range) => key(range.start) <= key(position) && key(position) <= key(range.end),
+            None => position == self.position,
+        }
+    }
+}
+
+/// Finish the block collected so far, skipping blank prose
+fn push_block(blocks: &mut Vec<TooltipBlock>, current: &mut String, in_code: bool) {
+    let text = std::mem::take(current);
+    if in_code {
+        blocks.push(TooltipBlock::Code(text));
+    } else if !text.trim().is_empty() {
+        blocks.push(TooltipBlock::Text(text.trim().to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown_blocks() {
+        let tooltip = Tooltip::markdown(
+            Position::new(0, 4),
+            "```rust\nfn add(a: i32) -> i32\n```\n\nAdds one.\n",
+        );
+        assert_eq!(
+            tooltip.blocks,
+            vec![
+                TooltipBlock::Code("fn add(a: i32) -> i32".to_string()),
+                TooltipBlock::Text("Adds one.".to_string()),
+            ]
+        );
+
+        let tooltip = tooltip.with_range(Position::new(0, 3)..Position::new(0, 6));
+        assert!(tooltip.covers(Position::new(0, 5)));
+        assert!(!tooltip.covers(Position::new(0, 7)));
+    }
+}
@@ -0,0 +1,1950 @@
+use crate::{CursorStyle, Editor, EditorMessage, Key, KeyEvent, NamedKey, Position, TextEdit};
+use ropey::Rope;
+use std::collections::HashMap;
+use std::ops::{Range, RangeInclusive};
+
+/// Editing mode of the Vim layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VimMode {
+    #[default]
+    Normal,
+    Insert,
+    Visual,
+    VisualLine,
+    VisualBlock,
+    /// Typing a `:` command
+    CommandLine,
+}
+
+impl VimMode {
+    /// Get how the cursor is drawn in this mode
+    pub fn cursor_style(self) -> CursorStyle {
+        match self {
+            Self::Normal | Self::CommandLine => CursorStyle::Block,
+            Self::Insert | Self::Visual | Self::VisualLine | Self::VisualBlock => CursorStyle::Line,
+        }
+    }
+
+    pub fn is_visual(self) -> bool {
+        matches!(self, Self::Visual | Self::VisualLine | Self::VisualBlock)
+    }
+}
+
+/// Result of a key handled by the Vim layer
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VimOutput {
+    /// Messages to send to the editor, in order
+    pub messages: Vec<EditorMessage>,
+    /// `:` commands the layer does not run itself, such as `w` or `q`
+    pub commands: Vec<String>,
+    /// Whether the key was consumed; insert mode leaves most keys to the regular key bindings
+    pub handled: bool,
+}
+
+impl Default for Vim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VimOutput {
+    fn handled(messages: Vec<EditorMessage>) -> Self {
+        Self {
+            messages,
+            commands: Vec::new(),
+            handled: true,
+        }
+    }
+}
+
+/// Text stored in a register
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Register {
+    pub text: String,
+    /// Whole lines, pasted above or below the cursor line
+    pub linewise: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordForward,
+    WordEnd,
+    WordBackward,
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    /// `gg`, or the line given by the count
+    FirstLine,
+    /// `G`, or the line given by the count
+    LastLine,
+    Find {
+        ch: char,
+        forward: bool,
+        till: bool,
+    },
+    RepeatFind {
+        reverse: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TextObject {
+    Word {
+        around: bool,
+    },
+    Quote {
+        quote: char,
+        around: bool,
+    },
+    Bracket {
+        open: char,
+        close: char,
+        around: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target {
+    Motion(Motion),
+    Object(TextObject),
+    /// Doubled operator such as `dd`, acting on whole lines
+    Lines,
+    /// The visual selection
+    Selection,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InsertAt {
+    Cursor,
+    AfterCursor,
+    FirstNonBlank,
+    LineEnd,
+    LineBelow,
+    LineAbove,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    Move(Motion),
+    Operate(Operator, Target),
+    Insert(InsertAt),
+    Paste {
+        before: bool,
+    },
+    Replace(char),
+    Undo,
+    Repeat,
+    Visual(VimMode),
+    /// `o` in visual mode
+    SwapAnchor,
+    /// `iw`, `a(`, ... in visual mode
+    SelectObject(TextObject),
+    CommandLine,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Command {
+    register: Option<char>,
+    count: Option<usize>,
+    action: Action,
+}
+
+impl Command {
+    fn count(&self) -> usize {
+        self.count.unwrap_or(1).max(1)
+    }
+}
+
+enum Parse {
+    Incomplete,
+    Invalid,
+    Complete(Command),
+}
+
+/// The last change, replayed by `.`
+#[derive(Debug, Clone, PartialEq)]
+struct Change {
+    command: Command,
+    /// Text typed in insert mode after the command
+    inserted: String,
+}
+
+/// Text typed since entering insert mode
+#[derive(Debug, Clone, PartialEq)]
+struct InsertSession {
+    /// The command that entered insert mode, unless it cannot be repeated
+    command: Option<Command>,
+    typed: String,
+    /// Lines below the first one of a visual block and the column to repeat the text at
+    block: Option<(RangeInclusive<usize>, usize)>,
+}
+
+/// Vim emulation that turns keys into editor messages
+///
+/// The layer reads the editor to resolve motions and text objects but never
+/// changes it; the returned messages do. Keys typed in insert mode are left
+/// to the regular key bindings, except for Escape.
+#[derive(Debug, Clone)]
+pub struct Vim {
+    mode: VimMode,
+    /// Keys of the normal or visual mode command typed so far
+    pending: Vec<char>,
+    registers: HashMap<char, Register>,
+    last_change: Option<Change>,
+    last_find: Option<(char, bool, bool)>,
+    insert: Option<InsertSession>,
+    visual_anchor: Position,
+    visual_cursor: Position,
+    /// Column that `j` and `k` try to keep
+    sticky_column: Option<usize>,
+    command_line: String,
+}
+
+impl Vim {
+    pub fn new() -> Self {
+        Self {
+            mode: VimMode::Normal,
+            pending: Vec::new(),
+            registers: HashMap::new(),
+            last_change: None,
+            last_find: None,
+            insert: None,
+            visual_anchor: Position::zero(),
+            visual_cursor: Position::zero(),
+            sticky_column: None,
+            command_line: String::new(),
+        }
+    }
+
+    pub fn mode(&self) -> VimMode {
+        self.mode
+    }
+
+    /// Get a register; `"` holds the last delete or yank and `0` the last yank
+    pub fn register(&self, name: char) -> Option<&Register> {
+        self.registers.get(&name)
+    }
+
+    pub fn set_register(&mut self, name: char, register: Register) {
+        self.registers.insert(name, register);
+    }
+
+    /// Describe the mode and pending keys for a status bar, e.g. `-- INSERT --` or `:s/a`
+    pub fn status(&self) -> Option<String> {
+        if self.mode == VimMode::CommandLine {
+            return Some(format!(":{}", self.command_line));
+        }
+        if !self.pending.is_empty() {
+            return Some(self.pending.iter().collect());
+        }
+
+        match self.mode {
+            VimMode::Normal | VimMode::CommandLine => None,
+            VimMode::Insert => Some("-- INSERT --".to_string()),
+            VimMode::Visual => Some("-- VISUAL --".to_string()),
+            VimMode::VisualLine => Some("-- VISUAL LINE --".to_string()),
+            VimMode::VisualBlock => Some("-- VISUAL BLOCK --".to_string()),
+        }
+    }
+
+    /// Handle a key pressed in the editor
+    pub fn handle_key(&mut self, editor: &Editor, event: KeyEvent) -> VimOutput {
+        let control = event.modifiers.control;
+        let is_escape = matches!(event.key, Key::Named(NamedKey::Escape))
+            || (control && event.key == Key::Character('['));
+
+        // The text may have changed since the last key, e.g. through the app
+        let rope = editor.current_buffer().rope();
+        self.visual_anchor = clamp_normal(rope, self.visual_anchor);
+        self.visual_cursor = clamp_normal(rope, self.visual_cursor);
+
+        match self.mode {
+            VimMode::Insert => {
+                if is_escape {
+                    VimOutput::handled(self.leave_insert(editor))
+                } else {
+                    self.record_insert_key(&event);
+                    VimOutput::default()
+                }
+            }
+            VimMode::CommandLine => self.handle_command_line_key(editor, &event, is_escape),
+            VimMode::Normal | VimMode::Visual | VimMode::VisualLine | VimMode::VisualBlock => {
+                if is_escape {
+                    let had_pending = !self.pending.is_empty();
+                    self.pending.clear();
+                    if self.mode.is_visual() && !had_pending {
+                        return VimOutput::handled(self.leave_visual());
+                    }
+                    return VimOutput::handled(Vec::new());
+                }
+
+                let key = match (&event.key, control) {
+                    (Key::Character('r'), true) if self.pending.is_empty() => {
+                        return VimOutput::handled(vec![EditorMessage::Redo]);
+                    }
+                    (Key::Character('v'), true) if self.pending.is_empty() => {
+                        return VimOutput::handled(
+                            self.toggle_visual(editor, VimMode::VisualBlock),
+                        );
+                    }
+                    (_, true) => return VimOutput::default(),
+                    (_, false) if event.modifiers.alt || event.modifiers.super_key => {
+                        return VimOutput::default();
+                    }
+                    (Key::Character(ch), false) => *ch,
+                    (Key::Named(named), false) => match named {
+                        NamedKey::ArrowLeft | NamedKey::Backspace => 'h',
+                        NamedKey::ArrowRight | NamedKey::Space => 'l',
+                        NamedKey::ArrowUp => 'k',
+                        NamedKey::ArrowDown | NamedKey::Enter => 'j',
+                        NamedKey::Home => '0',
+                        NamedKey::End => '$',
+                        NamedKey::Delete => 'x',
+                        _ => return VimOutput::handled(Vec::new()),
+                    },
+                };
+
+                self.pending.push(key);
+                match parse_command(&self.pending, self.mode.is_visual()) {
+                    Parse::Incomplete => VimOutput::handled(Vec::new()),
+                    Parse::Invalid => {
+                        self.pending.clear();
+                        VimOutput::handled(Vec::new())
+                    }
+                    Parse::Complete(command) => {
+                        self.pending.clear();
+                        let messages = if self.mode.is_visual() {
+                            self.execute_visual(editor, command)
+                        } else {
+                            self.execute(editor, command, false)
+                        };
+                        VimOutput::handled(messages)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Run a normal mode command
+    fn execute(
+        &mut self,
+        editor: &Editor,
+        command: Command,
+        repeating: bool,
+    ) -> Vec<EditorMessage> {
+        let rope = editor.current_buffer().rope();
+        let cursor = clamp_normal(rope, editor.current_cursor().position());
+        let count = command.count();
+
+        match command.action {
+            Action::Move(motion) => {
+                let Some(target) = self.motion_target(rope, cursor, motion, command.count) else {
+                    return Vec::new();
+                };
+                vec![EditorMessage::MoveCursorTo(clamp_normal(rope, target))]
+            }
+            Action::Operate(operator, target) => {
+                let Some((range, linewise)) =
+                    self.operator_range(rope, cursor, operator, target, command.count)
+                else {
+                    return Vec::new();
+                };
+                if !repeating && operator != Operator::Yank {
+                    self.last_change = Some(Change {
+                        command,
+                        inserted: String::new(),
+                    });
+                }
+                self.operate(rope, cursor, operator, range, linewise, command)
+            }
+            Action::Insert(at) => {
+                let mut messages = Vec::new();
+                let line_len = line_len(rope, cursor.line);
+                let indent = indentation(rope, cursor.line);
+                match at {
+                    InsertAt::Cursor => {}
+                    InsertAt::AfterCursor => messages.push(EditorMessage::MoveCursorTo(
+                        Position::new(cursor.line, (cursor.column + 1).min(line_len)),
+                    )),
+                    InsertAt::FirstNonBlank => messages.push(EditorMessage::MoveCursorTo(
+                        Position::new(cursor.line, first_non_blank(rope, cursor.line)),
+                    )),
+                    InsertAt::LineEnd => messages.push(EditorMessage::MoveCursorTo(Position::new(
+                        cursor.line,
+                        line_len,
+                    ))),
+                    InsertAt::LineBelow => {
+                        messages.push(EditorMessage::ApplyEdits(vec![TextEdit::insert(
+                            Position::new(cursor.line, line_len),
+                            format!("\n{}", indent),
+                        )]));
+                        messages.push(EditorMessage::MoveCursorTo(Position::new(
+                            cursor.line + 1,
+                            indent.chars().count(),
+                        )));
+                    }
+                    InsertAt::LineAbove => {
+                        messages.push(EditorMessage::ApplyEdits(vec![TextEdit::insert(
+                            Position::new(cursor.line, 0),
+                            format!("{}\n", indent),
+                        )]));
+                        messages.push(EditorMessage::MoveCursorTo(Position::new(
+                            cursor.line,
+                            indent.chars().count(),
+                        )));
+                    }
+                }
+                self.enter_insert(Some(command), None);
+                messages
+            }
+            Action::Paste { before } => {
+                if !repeating {
+                    self.last_change = Some(Change {
+                        command,
+                        inserted: String::new(),
+                    });
+                }
+                self.paste(rope, cursor, command.register.unwrap_or('"'), before, count)
+            }
+            Action::Replace(ch) => {
+                let line_len = line_len(rope, cursor.line);
+                if cursor.column.saturating_add(count) > line_len {
+                    return Vec::new();
+                }
+                if !repeating {
+                    self.last_change = Some(Change {
+                        command,
+                        inserted: String::new(),
+                    });
+                }
+                let end = Position::new(cursor.line, cursor.column + count);
+                vec![
+                    EditorMessage::ApplyEdits(vec![TextEdit::new(
+                        cursor..end,
+                        ch.to_string().repeat(count),
+                    )]),
+                    EditorMessage::MoveCursorTo(Position::new(
+                        cursor.line,
+                        cursor.column + count - 1,
+                    )),
+                ]
+            }
+            Action::Undo => vec![EditorMessage::Undo; count],
+            Action::Repeat => self.repeat(editor, command.count),
+            Action::Visual(mode) => self.toggle_visual(editor, mode),
+            Action::CommandLine => {
+                self.mode = VimMode::CommandLine;
+                self.command_line.clear();
+                Vec::new()
+            }
+            Action::SwapAnchor | Action::SelectObject(_) => Vec::new(),
+        }
+    }
+
+    /// Replay the last change, with a new count if one is given
+    fn repeat(&mut self, editor: &Editor, count: Option<usize>) -> Vec<EditorMessage> {
+        let Some(change) = self.last_change.clone() else {
+            return Vec::new();
+        };
+        let mut command = change.command;
+        if count.is_some() {
+            command.count = count;
+        }
+
+        let mut messages = self.execute(editor, command, true);
+        if self.mode == VimMode::Insert {
+            self.insert = None;
+            self.mode = VimMode::Normal;
+            if !change.inserted.is_empty() {
+                messages.push(EditorMessage::InsertText(change.inserted.clone()));
+                if !change.inserted.ends_with('\n') {
+                    messages.push(EditorMessage::MoveCursor(crate::CursorMovement::Left));
+                }
+            }
+        }
+        messages
+    }
+
+    /// Apply an operator to a char range of the buffer
+    fn operate(
+        &mut self,
+        rope: &Rope,
+        cursor: Position,
+        operator: Operator,
+        range: Range<usize>,
+        linewise: bool,
+        command: Command,
+    ) -> Vec<EditorMessage> {
+        let mut text = rope.slice(range.clone()).to_string();
+        if linewise && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        self.store_register(
+            command.register,
+            Register { text, linewise },
+            operator == Operator::Yank,
+        );
+
+        let start = to_position(rope, range.start);
+        match operator {
+            Operator::Yank => {
+                if linewise || start == cursor {
+                    Vec::new()
+                } else {
+                    vec![EditorMessage::MoveCursorTo(start)]
+                }
+            }
+            Operator::Delete if linewise => {
+                let first_line = start.line;
+                let last_line = rope.char_to_line(range.end.saturating_sub(1).max(range.start));
+                // Removing the last lines also removes the line break before them
+                let (edit_start, cursor_line) = if range.end >= rope.len_chars() && first_line > 0 {
+                    (range.start - 1, first_line - 1)
+                } else {
+                    (range.start, first_line)
+                };
+                let next_line = if edit_start < range.start {
+                    first_line - 1
+                } else {
+                    last_line + 1
+                };
+                let column = if next_line < rope.len_lines() {
+                    first_non_blank(rope, next_line)
+                } else {
+                    0
+                };
+                vec![
+                    EditorMessage::ApplyEdits(vec![TextEdit::new(
+                        to_position(rope, edit_start)..to_position(rope, range.end),
+                        "",
+                    )]),
+                    EditorMessage::MoveCursorTo(Position::new(cursor_line, column)),
+                ]
+            }
+            Operator::Delete => {
+                let end = to_position(rope, range.end);
+                let remaining = line_len(rope, end.line).saturating_sub(end.column);
+                let column = start
+                    .column
+                    .min((start.column + remaining).saturating_sub(1));
+                vec![
+                    EditorMessage::ApplyEdits(vec![TextEdit::new(start..end, "")]),
+                    EditorMessage::MoveCursorTo(Position::new(start.line, column)),
+                ]
+            }
+            Operator::Change => {
+                let messages = if linewise {
+                    // Keep the first line, emptied down to its indentation
+                    let indent = indentation(rope, start.line);
+                    let last_line = rope.char_to_line(range.end.saturating_sub(1).max(range.start));
+                    let end = Position::new(last_line, line_len(rope, last_line));
+                    vec![
+                        EditorMessage::ApplyEdits(vec![TextEdit::new(
+                            Position::new(start.line, 0)..end,
+                            indent.clone(),
+                        )]),
+                        EditorMessage::MoveCursorTo(Position::new(
+                            start.line,
+                            indent.chars().count(),
+                        )),
+                    ]
+                } else {
+                    vec![
+                        EditorMessage::ApplyEdits(vec![TextEdit::new(
+                            start..to_position(rope, range.end),
+                            "",
+                        )]),
+                        EditorMessage::MoveCursorTo(start),
+                    ]
+                };
+                self.enter_insert(Some(command), None);
+                messages
+            }
+        }
+    }
+
+    /// Insert a register's text before or after the cursor
+    fn paste(
+        &self,
+        rope: &Rope,
+        cursor: Position,
+        register: char,
+        before: bool,
+        count: usize,
+    ) -> Vec<EditorMessage> {
+        let Some(register) = self.registers.get(&register) else {
+            return Vec::new();
+        };
+        let text = register.text.repeat(count);
+        if text.is_empty() {
+            return Vec::new();
+        }
+
+        if register.linewise {
+            let column = text
+                .chars()
+                .take_while(|ch| *ch == ' ' || *ch == '\t')
+                .count();
+            let (edit, line) = if before {
+                (
+                    TextEdit::insert(Position::new(cursor.line, 0), text),
+                    cursor.line,
+                )
+            } else if cursor.line + 1 < rope.len_lines() {
+                (
+                    TextEdit::insert(Position::new(cursor.line + 1, 0), text),
+                    cursor.line + 1,
+                )
+            } else {
+                // Below the last line, which has no line break to insert after
+                let end = Position::new(cursor.line, line_len(rope, cursor.line));
+                let text = format!("\n{}", text.strip_suffix('\n').unwrap_or(&text));
+                (TextEdit::insert(end, text), cursor.line + 1)
+            };
+            return vec![
+                EditorMessage::ApplyEdits(vec![edit]),
+                EditorMessage::MoveCursorTo(Position::new(line, column)),
+            ];
+        }
+
+        let at = if before {
+            cursor
+        } else {
+            Position::new(
+                cursor.line,
+                (cursor.column + 1).min(line_len(rope, cursor.line)),
+            )
+        };
+        // The cursor ends on the last pasted char
+        let last = match text.rfind('\n') {
+            Some(index) => Position::new(
+                at.line + text.matches('\n').count(),
+                text[index + 1..].chars().count().saturating_sub(1),
+            ),
+            None => Position::new(at.line, at.column + text.chars().count() - 1),
+        };
+        vec![
+            EditorMessage::ApplyEdits(vec![TextEdit::insert(at, text)]),
+            EditorMessage::MoveCursorTo(last),
+        ]
+    }
+
+    /// Save deleted or yanked text; uppercase register names append
+    fn store_register(&mut self, name: Option<char>, register: Register, yank: bool) {
+        let register = match name {
+            Some('_') => return,
+            Some(name) if name.is_ascii_uppercase() => {
+                let target = self.registers.entry(name.to_ascii_lowercase()).or_default();
+                target.text.push_str(&register.text);
+                target.linewise |= register.linewise;
+                target.clone()
+            }
+            Some(name) if name != '"' => {
+                self.registers.insert(name, register.clone());
+                register
+            }
+            _ => {
+                if yank {
+                    self.registers.insert('0', register.clone());
+                }
+                register
+            }
+        };
+        self.registers.insert('"', register);
+    }
+
+    fn enter_insert(
+        &mut self,
+        command: Option<Command>,
+        block: Option<(RangeInclusive<usize>, usize)>,
+    ) {
+        self.mode = VimMode::Insert;
+        self.insert = Some(InsertSession {
+            command,
+            typed: String::new(),
+            block,
+        });
+    }
+
+    /// Keep track of typed text so `.` and counts can repeat it
+    fn record_insert_key(&mut self, event: &KeyEvent) {
+        let Some(session) = &mut self.insert else {
+            return;
+        };
+        let modifiers = event.modifiers;
+        if modifiers.control || modifiers.alt || modifiers.super_key {
+            return;
+        }
+        match event.key {
+            Key::Character(ch) => session.typed.push(ch),
+            Key::Named(NamedKey::Space) => session.typed.push(' '),
+            Key::Named(NamedKey::Enter) => session.typed.push('\n'),
+            Key::Named(NamedKey::Tab) => session.typed.push('\t'),
+            Key::Named(NamedKey::Backspace) => {
+                session.typed.pop();
+            }
+            _ => {}
+        }
+    }
+
+    fn leave_insert(&mut self, editor: &Editor) -> Vec<EditorMessage> {
+        self.mode = VimMode::Normal;
+        let mut messages = Vec::new();
+        if editor.completion().is_some() {
+            messages.push(EditorMessage::CancelCompletion);
+        }
+
+        let Some(session) = self.insert.take() else {
+            return messages;
+        };
+        let rope = editor.current_buffer().rope();
+
+        // Text typed at the top of a visual block goes on every line of it
+        if let Some((lines, column)) = session.block {
+            if !session.typed.is_empty() && !session.typed.contains('\n') {
+                let edits = lines
+                    .filter(|line| *line < rope.len_lines() && line_len(rope, *line) >= column)
+                    .map(|line| TextEdit::insert(Position::new(line, column), &session.typed))
+                    .collect();
+                messages.push(EditorMessage::ApplyEdits(edits));
+            }
+        }
+
+        let count = session.command.map(|command| command.count()).unwrap_or(1);
+        let is_insert = matches!(
+            session.command.map(|command| command.action),
+            Some(Action::Insert(_))
+        );
+        if is_insert && count > 1 && !session.typed.is_empty() {
+            messages.push(EditorMessage::InsertText(session.typed.repeat(count - 1)));
+        }
+
+        if let Some(mut command) = session.command {
+            command.count = Some(count).filter(|_| is_insert);
+            self.last_change = Some(Change {
+                command,
+                inserted: session.typed.repeat(if is_insert { count } else { 1 }),
+            });
+            if let Some(change) = &mut self.last_change {
+                change.command.count = None;
+            }
+        }
+
+        let cursor = editor.current_cursor().position();
+        if cursor.column > 0 || (!session.typed.is_empty() && !session.typed.ends_with('\n')) {
+            messages.push(EditorMessage::MoveCursor(crate::CursorMovement::Left));
+        }
+        messages
+    }
+
+    fn toggle_visual(&mut self, editor: &Editor, mode: VimMode) -> Vec<EditorMessage> {
+        if self.mode == mode {
+            return self.leave_visual();
+        }
+        if !self.mode.is_visual() {
+            let rope = editor.current_buffer().rope();
+            let cursor = clamp_normal(rope, editor.current_cursor().position());
+            self.visual_anchor = cursor;
+            self.visual_cursor = cursor;
+        }
+        self.mode = mode;
+        self.show_selection(editor.current_buffer().rope())
+    }
+
+    fn leave_visual(&mut self) -> Vec<EditorMessage> {
+        self.mode = VimMode::Normal;
+        vec![
+            EditorMessage::ClearSelection,
+            EditorMessage::MoveCursorTo(self.visual_cursor),
+        ]
+    }
+
+    /// Select the visual range in the editor, leaving its cursor near the Vim cursor
+    fn show_selection(&self, rope: &Rope) -> Vec<EditorMessage> {
+        let anchor = self.visual_anchor;
+        let cursor = self.visual_cursor;
+        let forward = (cursor.line, cursor.column) >= (anchor.line, anchor.column);
+        let (start, end) = if forward {
+            (anchor, cursor)
+        } else {
+            (cursor, anchor)
+        };
+
+        let (start, end) = match self.mode {
+            VimMode::VisualLine => (
+                Position::new(start.line, 0),
+                to_position(rope, line_end_with_break(rope, end.line)),
+            ),
+            VimMode::VisualBlock => {
+                let left = anchor.column.min(cursor.column);
+                let right = anchor.column.max(cursor.column);
+                (
+                    Position::new(start.line, left),
+                    Position::new(end.line, (right + 1).min(line_len(rope, end.line))),
+                )
+            }
+            _ => (
+                start,
+                to_position(rope, next_char(rope, to_index(rope, end))),
+            ),
+        };
+
+        let selection = if forward {
+            EditorMessage::SetSelection(start, end)
+        } else {
+            EditorMessage::SetSelection(end, start)
+        };
+        vec![selection]
+    }
+
+    /// Run a command typed in a visual mode
+    fn execute_visual(&mut self, editor: &Editor, command: Command) -> Vec<EditorMessage> {
+        let rope = editor.current_buffer().rope();
+        match command.action {
+            Action::Move(motion) => {
+                if let Some(target) =
+                    self.motion_target(rope, self.visual_cursor, motion, command.count)
+                {
+                    self.visual_cursor = clamp_normal(rope, target);
+                }
+                self.show_selection(rope)
+            }
+            Action::SwapAnchor => {
+                std::mem::swap(&mut self.visual_anchor, &mut self.visual_cursor);
+                self.show_selection(rope)
+            }
+            Action::SelectObject(object) => {
+                if let Some(range) =
+                    text_object_range(rope, to_index(rope, self.visual_cursor), object)
+                {
+                    if range.end > range.start {
+                        self.visual_anchor = to_position(rope, range.start);
+                        self.visual_cursor = to_position(rope, range.end - 1);
+                    }
+                }
+                self.mode = VimMode::Visual;
+                self.show_selection(rope)
+            }
+            Action::Visual(mode) => self.toggle_visual(editor, mode),
+            Action::Operate(operator, _) if self.mode == VimMode::VisualBlock => {
+                self.operate_block(rope, operator, command)
+            }
+            Action::Insert(at) if self.mode == VimMode::VisualBlock => {
+                let (lines, left, right) = self.block_bounds();
+                let column = if at == InsertAt::LineEnd {
+                    right + 1
+                } else {
+                    left
+                };
+                self.mode = VimMode::Normal;
+                let start =
+                    Position::new(*lines.start(), column.min(line_len(rope, *lines.start())));
+                self.enter_insert(None, Some((lines.start() + 1..=*lines.end(), column)));
+                vec![
+                    EditorMessage::ClearSelection,
+                    EditorMessage::MoveCursorTo(start),
+                ]
+            }
+            Action::Operate(operator, _) => {
+                let linewise = self.mode == VimMode::VisualLine;
+                let (start, end) = ordered(self.visual_anchor, self.visual_cursor);
+                let range = if linewise {
+                    rope.line_to_char(start.line)..line_end_with_break(rope, end.line)
+                } else {
+                    to_index(rope, start)..next_char(rope, to_index(rope, end))
+                };
+                self.mode = VimMode::Normal;
+                let cursor = self.visual_cursor;
+                let mut messages = self.operate(rope, cursor, operator, range, linewise, command);
+                if operator == Operator::Yank {
+                    messages.insert(0, EditorMessage::ClearSelection);
+                    messages.push(EditorMessage::MoveCursorTo(if linewise {
+                        Position::new(start.line, cursor.column.min(start.column))
+                    } else {
+                        start
+                    }));
+                }
+                messages
+            }
+            Action::Paste { .. } => {
+                // Replace the selection with the register, keeping the replaced text
+                let Some(register) = self
+                    .registers
+                    .get(&command.register.unwrap_or('"'))
+                    .cloned()
+                else {
+                    return Vec::new();
+                };
+                let (start, end) = ordered(self.visual_anchor, self.visual_cursor);
+                let range = to_index(rope, start)..next_char(rope, to_index(rope, end));
+                self.mode = VimMode::Normal;
+                self.store_register(
+                    None,
+                    Register {
+                        text: rope.slice(range.clone()).to_string(),
+                        linewise: false,
+                    },
+                    false,
+                );
+                vec![
+                    EditorMessage::ApplyEdits(vec![TextEdit::new(
+                        start..to_position(rope, range.end),
+                        register.text,
+                    )]),
+                    EditorMessage::MoveCursorTo(start),
+                ]
+            }
+            Action::CommandLine => {
+                self.mode = VimMode::CommandLine;
+                self.command_line.clear();
+                vec![EditorMessage::ClearSelection]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Get the lines and inclusive columns of the visual block
+    fn block_bounds(&self) -> (RangeInclusive<usize>, usize, usize) {
+        let (start, end) = ordered(self.visual_anchor, self.visual_cursor);
+        let left = self.visual_anchor.column.min(self.visual_cursor.column);
+        let right = self.visual_anchor.column.max(self.visual_cursor.column);
+        (start.line..=end.line, left, right)
+    }
+
+    /// Apply an operator to each line of the visual block
+    fn operate_block(
+        &mut self,
+        rope: &Rope,
+        operator: Operator,
+        command: Command,
+    ) -> Vec<EditorMessage> {
+        let (lines, left, right) = self.block_bounds();
+        let mut texts = Vec::new();
+        let mut edits = Vec::new();
+        for line in lines.clone() {
+            let len = line_len(rope, line);
+            let start = left.min(len);
+            let end = (right + 1).min(len);
+            let from = rope.line_to_char(line);
+            texts.push(rope.slice(from + start..from + end).to_string());
+            if end > start {
+                edits.push(TextEdit::new(
+                    Position::new(line, start)..Position::new(line, end),
+                    "",
+                ));
+            }
+        }
+
+        self.mode = VimMode::Normal;
+        self.store_register(
+            command.register,
+            Register {
+                text: texts.join("\n"),
+                linewise: false,
+            },
+            operator == Operator::Yank,
+        );
+
+        let top_left = Position::new(*lines.start(), left.min(line_len(rope, *lines.start())));
+        match operator {
+            Operator::Yank => vec![
+                EditorMessage::ClearSelection,
+                EditorMessage::MoveCursorTo(top_left),
+            ],
+            Operator::Delete => vec![
+                EditorMessage::ApplyEdits(edits),
+                EditorMessage::MoveCursorTo(top_left),
+            ],
+            Operator::Change => {
+                self.enter_insert(None, Some((lines.start() + 1..=*lines.end(), left)));
+                vec![
+                    EditorMessage::ApplyEdits(edits),
+                    EditorMessage::MoveCursorTo(top_left),
+                ]
+            }
+        }
+    }
+
+    fn handle_command_line_key(
+        &mut self,
+        editor: &Editor,
+        event: &KeyEvent,
+        is_escape: bool,
+    ) -> VimOutput {
+        if is_escape {
+            self.mode = VimMode::Normal;
+            return VimOutput::handled(Vec::new());
+        }
+
+        match event.key {
+            Key::Named(NamedKey::Enter) => {
+                self.mode = VimMode::Normal;
+                let command_line = std::mem::take(&mut self.command_line);
+                self.run_command_line(editor, command_line.trim())
+            }
+            Key::Named(NamedKey::Backspace) => {
+                if self.command_line.pop().is_none() {
+                    self.mode = VimMode::Normal;
+                }
+                VimOutput::handled(Vec::new())
+            }
+            Key::Named(NamedKey::Space) => {
+                self.command_line.push(' ');
+                VimOutput::handled(Vec::new())
+            }
+            Key::Character(ch) if !event.modifiers.control => {
+                self.command_line.push(ch);
+                VimOutput::handled(Vec::new())
+            }
+            _ => VimOutput::handled(Vec::new()),
+        }
+    }
+
+    /// Run a `:` command: a line number, `s`/`%s` substitution, `u` or `redo`
+    ///
+    /// Anything else is handed to the app.
+    fn run_command_line(&mut self, editor: &Editor, command_line: &str) -> VimOutput {
+        let rope = editor.current_buffer().rope();
+        if command_line.is_empty() {
+            return VimOutput::handled(Vec::new());
+        }
+
+        if let Ok(line) = command_line.parse::<usize>() {
+            let line = line
+                .saturating_sub(1)
+                .min(rope.len_lines().saturating_sub(1));
+            return VimOutput::handled(vec![EditorMessage::MoveCursorTo(Position::new(
+                line,
+                first_non_blank(rope, line),
+            ))]);
+        }
+
+        match command_line {
+            "u" | "undo" => return VimOutput::handled(vec![EditorMessage::Undo]),
+            "red" | "redo" => return VimOutput::handled(vec![EditorMessage::Redo]),
+            _ => {}
+        }
+
+        let (lines, substitute) = match command_line.strip_prefix('%') {
+            Some(rest) => (0..=rope.len_lines().saturating_sub(1), rest),
+            None => {
+                let line = editor.current_cursor().position().line;
+                (line..=line, command_line)
+            }
+        };
+        if let Some(edits) = substitute_edits(rope, lines, substitute) {
+            return VimOutput::handled(vec![EditorMessage::ApplyEdits(edits)]);
+        }
+
+        VimOutput {
+            messages: Vec::new(),
+            commands: vec![command_line.to_string()],
+            handled: true,
+        }
+    }
+
+    /// Get where a motion moves the cursor
+    fn motion_target(
+        &mut self,
+        rope: &Rope,
+        from: Position,
+        motion: Motion,
+        count: Option<usize>,
+    ) -> Option<Position> {
+        let times = count.unwrap_or(1).max(1);
+        let last_line = rope.len_lines().saturating_sub(1);
+        let index = to_index(rope, from);
+
+        let target = match motion {
+            Motion::Left => Position::new(from.line, from.column.saturating_sub(times)),
+            Motion::Right => Position::new(
+                from.line,
+                from.column
+                    .saturating_add(times)
+                    .min(line_len(rope, from.line)),
+            ),
+            Motion::Up | Motion::Down => {
+                let line = if motion == Motion::Up {
+                    from.line.saturating_sub(times)
+                } else {
+                    from.line.saturating_add(times).min(last_line)
+                };
+                let column = self.sticky_column.unwrap_or(from.column);
+                self.sticky_column = Some(column);
+                return Some(Position::new(
+                    line,
+                    column.min(line_len(rope, line).saturating_sub(1)),
+                ));
+            }
+            Motion::WordForward => to_position(
+                rope,
+                repeat_motion(index, times, |index| next_word_start(rope, index)),
+            ),
+            Motion::WordEnd => to_position(
+                rope,
+                repeat_motion(index, times, |index| word_end(rope, index)),
+            ),
+            Motion::WordBackward => to_position(
+                rope,
+                repeat_motion(index, times, |index| previous_word_start(rope, index)),
+            ),
+            Motion::LineStart => Position::new(from.line, 0),
+            Motion::FirstNonBlank => Position::new(from.line, first_non_blank(rope, from.line)),
+            Motion::LineEnd => {
+                let line = from.line.saturating_add(times - 1).min(last_line);
+                self.sticky_column = Some(usize::MAX);
+                return Some(Position::new(line, line_len(rope, line).saturating_sub(1)));
+            }
+            Motion::FirstLine | Motion::LastLine => {
+                let line = match count {
+                    Some(count) => count.saturating_sub(1).min(last_line),
+                    None if motion == Motion::FirstLine => 0,
+                    None => last_line,
+                };
+                Position::new(line, first_non_blank(rope, line))
+            }
+            Motion::Find { ch, forward, till } => {
+                self.last_find = Some((ch, forward, till));
+                Position::new(
+                    from.line,
+                    find_in_line(rope, from, ch, forward, till, times)?,
+                )
+            }
+            Motion::RepeatFind { reverse } => {
+                let (ch, forward, till) = self.last_find?;
+                Position::new(
+                    from.line,
+                    find_in_line(rope, from, ch, forward != reverse, till, times)?,
+                )
+            }
+        };
+
+        self.sticky_column = None;
+        Some(target)
+    }
+
+    /// Get the char range an operator acts on, and whether it spans whole lines
+    fn operator_range(
+        &mut self,
+        rope: &Rope,
+        cursor: Position,
+        operator: Operator,
+        target: Target,
+        count: Option<usize>,
+    ) -> Option<(Range<usize>, bool)> {
+        let index = to_index(rope, cursor);
+        match target {
+            Target::Lines => {
+                let last = cursor
+                    .line
+                    .saturating_add(count.unwrap_or(1).max(1) - 1)
+                    .min(rope.len_lines().saturating_sub(1));
+                Some((
+                    rope.line_to_char(cursor.line)..line_end_with_break(rope, last),
+                    true,
+                ))
+            }
+            Target::Object(object) => {
+                text_object_range(rope, index, object).map(|range| (range, false))
+            }
+            Target::Selection => None,
+            Target::Motion(motion) => {
+                // `cw` on a word changes up to its end, like `ce`
+                let on_word = rope.get_char(index).is_some_and(|ch| !ch.is_whitespace());
+                let motion =
+                    if operator == Operator::Change && motion == Motion::WordForward && on_word {
+                        Motion::WordEnd
+                    } else {
+                        motion
+                    };
+                let target = self.motion_target(rope, cursor, motion, count)?;
+                let target_index = to_index(rope, target);
+
+                match motion {
+                    Motion::Up | Motion::Down | Motion::FirstLine | Motion::LastLine => {
+                        let (first, last) =
+                            (cursor.line.min(target.line), cursor.line.max(target.line));
+                        Some((
+                            rope.line_to_char(first)..line_end_with_break(rope, last),
+                            true,
+                        ))
+                    }
+                    Motion::WordEnd
+                    | Motion::LineEnd
+                    | Motion::Find { .. }
+                    | Motion::RepeatFind { .. } => {
+                        let (start, end) = (index.min(target_index), index.max(target_index));
+                        Some((start..next_char(rope, end), false))
+                    }
+                    Motion::WordForward if target.line > cursor.line => {
+                        // The last word of a line stops at the line end
+                        let end = rope.line_to_char(cursor.line) + line_len(rope, cursor.line);
+                        Some((index..end.max(index), false))
+                    }
+                    _ => Some((index.min(target_index)..index.max(target_index), false)),
+                }
+            }
+        }
+    }
+}
+
+/// Parse the keys of a normal or visual mode command
+fn parse_command(keys: &[char], visual: bool) -> Parse {
+    let mut rest = keys;
+    let mut register = None;
+    if let Some(('"', after)) = rest.split_first() {
+        let Some((&name, after)) = after.split_first() else {
+            return Parse::Incomplete;
+        };
+        register = Some(name);
+        rest = after;
+    }
+
+    let (mut count, after) = parse_count(rest);
+    rest = after;
+    let Some((&key, rest)) = rest.split_first() else {
+        return Parse::Incomplete;
+    };
+
+    let complete = |count: Option<usize>, action| {
+        Parse::Complete(Command {
+            register,
+            count,
+            action,
+        })
+    };
+
+    let operator = match key {
+        'd' | 'x' | 'X' | 'D' if visual => Some(Operator::Delete),
+        'c' | 's' | 'S' | 'C' if visual => Some(Operator::Change),
+        'y' | 'Y' if visual => Some(Operator::Yank),
+        _ => None,
+    };
+    if let Some(operator) = operator {
+        return complete(count, Action::Operate(operator, Target::Selection));
+    }
+
+    let action = match key {
+        'd' | 'c' | 'y' if !visual => {
+            let operator = match key {
+                'd' => Operator::Delete,
+                'c' => Operator::Change,
+                _ => Operator::Yank,
+            };
+            let (motion_count, rest) = parse_count(rest);
+            count = match (count, motion_count) {
+                (Some(a), Some(b)) => Some(a.saturating_mul(b).min(MAX_COUNT)),
+                (a, b) => a.or(b),
+            };
+            let Some((&next, rest)) = rest.split_first() else {
+                return Parse::Incomplete;
+            };
+            if next == key {
+                Action::Operate(operator, Target::Lines)
+            } else if next == 'i' || next == 'a' {
+                match rest.first() {
+                    None => return Parse::Incomplete,
+                    Some(&ch) => match text_object(ch, next == 'a') {
+                        Some(object) => Action::Operate(operator, Target::Object(object)),
+                        None => return Parse::Invalid,
+                    },
+                }
+            } else {
+                match parse_motion(next, rest) {
+                    Parse::Complete(Command {
+                        action: Action::Move(motion),
+                        ..
+                    }) => Action::Operate(operator, Target::Motion(motion)),
+                    Parse::Incomplete => return Parse::Incomplete,
+                    _ => return Parse::Invalid,
+                }
+            }
+        }
+        'i' | 'a' if visual => match rest.first() {
+            None => return Parse::Incomplete,
+            Some(&ch) => match text_object(ch, key == 'a') {
+                Some(object) => Action::SelectObject(object),
+                None => return Parse::Invalid,
+            },
+        },
+        'I' if visual => Action::Insert(InsertAt::FirstNonBlank),
+        'A' if visual => Action::Insert(InsertAt::LineEnd),
+        'o' if visual => Action::SwapAnchor,
+        'x' => Action::Operate(Operator::Delete, Target::Motion(Motion::Right)),
+        'X' => Action::Operate(Operator::Delete, Target::Motion(Motion::Left)),
+        'D' => Action::Operate(Operator::Delete, Target::Motion(Motion::LineEnd)),
+        'C' => Action::Operate(Operator::Change, Target::Motion(Motion::LineEnd)),
+        's' => Action::Operate(Operator::Change, Target::Motion(Motion::Right)),
+        'S' => Action::Operate(Operator::Change, Target::Lines),
+        'Y' => Action::Operate(Operator::Yank, Target::Lines),
+        'i' => Action::Insert(InsertAt::Cursor),
+        'a' => Action::Insert(InsertAt::AfterCursor),
+        'I' => Action::Insert(InsertAt::FirstNonBlank),
+        'A' => Action::Insert(InsertAt::LineEnd),
+        'o' => Action::Insert(InsertAt::LineBelow),
+        'O' => Action::Insert(InsertAt::LineAbove),
+        'p' => Action::Paste { before: false },
+        'P' => Action::Paste { before: true },
+        'r' => match rest.first() {
+            None => return Parse::Incomplete,
+            Some(&ch) => Action::Replace(ch),
+        },
+        'u' => Action::Undo,
+        '.' => Action::Repeat,
+        'v' => Action::Visual(VimMode::Visual),
+        'V' => Action::Visual(VimMode::VisualLine),
+        ':' => Action::CommandLine,
+        _ => {
+            return match parse_motion(key, rest) {
+                Parse::Complete(command) => complete(count, command.action),
+                other => other,
+            }
+        }
+    };
+    complete(count, action)
+}
+
+/// The largest count a command accepts, so huge counts can't run away
+const MAX_COUNT: usize = 10_000;
+
+/// Parse a count, where a leading `0` is the line start motion rather than a count
+fn parse_count(keys: &[char]) -> (Option<usize>, &[char]) {
+    let digits = keys
+        .iter()
+        .enumerate()
+        .take_while(|(index, ch)| ch.is_ascii_digit() && (*index > 0 || **ch != '0'))
+        .count();
+    if digits == 0 {
+        return (None, keys);
+    }
+    let count = keys[..digits].iter().fold(0usize, |count, ch| {
+        let digit = ch.to_digit(10).unwrap_or(0) as usize;
+        count
+            .saturating_mul(10)
+            .saturating_add(digit)
+            .min(MAX_COUNT)
+    });
+    (Some(count), &keys[digits..])
+}
+
+/// Apply a word motion up to `times` times, stopping once it no longer moves
+fn repeat_motion(mut index: usize, times: usize, step: impl Fn(usize) -> usize) -> usize {
+    for _ in 0..times {
+        let next = step(index);
+        if next == index {
+            break;
+        }
+        index = next;
+    }
+    index
+}
+
+/// Parse a motion starting with `key`, followed by `rest`
+fn parse_motion(key: char, rest: &[char]) -> Parse {
+    let motion = match key {
+        'h' => Motion::Left,
+        'l' => Motion::Right,
+        'k' => Motion::Up,
+        'j' => Motion::Down,
+        'w' => Motion::WordForward,
+        'e' => Motion::WordEnd,
+        'b' => Motion::WordBackward,
+        '0' => Motion::LineStart,
+        '^' => Motion::FirstNonBlank,
+        '$' => Motion::LineEnd,
+        'G' => Motion::LastLine,
+        'g' => match rest.first() {
+            None => return Parse::Incomplete,
+            Some('g') => Motion::FirstLine,
+            Some(_) => return Parse::Invalid,
+        },
+        'f' | 't' | 'F' | 'T' => match rest.first() {
+            None => return Parse::Incomplete,
+            Some(&ch) => Motion::Find {
+                ch,
+                forward: key == 'f' || key == 't',
+                till: key == 't' || key == 'T',
+            },
+        },
+        ';' => Motion::RepeatFind { reverse: false },
+        ',' => Motion::RepeatFind { reverse: true },
+        _ => return Parse::Invalid,
+    };
+    Parse::Complete(Command {
+        register: None,
+        count: None,
+        action: Action::Move(motion),
+    })
+}
+
+fn text_object(ch: char, around: bool) -> Option<TextObject> {
+    let (open, close) = match ch {
+        'w' => return Some(TextObject::Word { around }),
+        '"' | '\'' | '`' => return Some(TextObject::Quote { quote: ch, around }),
+        '(' | ')' | 'b' => ('(', ')'),
+        '{' | '}' | 'B' => ('{', '}'),
+        '[' | ']' => ('[', ']'),
+        '<' | '>' => ('<', '>'),
+        _ => return None,
+    };
+    Some(TextObject::Bracket {
+        open,
+        close,
+        around,
+    })
+}
+
+/// Get the char range of a text object around an offset
+fn text_object_range(rope: &Rope, index: usize, object: TextObject) -> Option<Range<usize>> {
+    let line = rope.char_to_line(index.min(rope.len_chars()));
+    let line_start = rope.line_to_char(line);
+    let line_end = line_start + line_len(rope, line);
+
+    match object {
+        TextObject::Word { around } => {
+            if index >= line_end {
+                return None;
+            }
+            let class = char_class(rope.char(index));
+            let mut start = index;
+            while start > line_start && char_class(rope.char(start - 1)) == class {
+                start -= 1;
+            }
+            let mut end = index + 1;
+            while end < line_end && char_class(rope.char(end)) == class {
+                end += 1;
+            }
+
+            if around && class != CharClass::Whitespace {
+                // Include the spaces after the word, or else the ones before it
+                let mut trailing = end;
+                while trailing < line_end && rope.char(trailing).is_whitespace() {
+                    trailing += 1;
+                }
+                if trailing > end {
+                    end = trailing;
+                } else {
+                    while start > line_start && rope.char(start - 1).is_whitespace() {
+                        start -= 1;
+                    }
+                }
+            }
+            Some(start..end)
+        }
+        TextObject::Quote { quote, around } => {
+            let column = index - line_start;
+            let quotes: Vec<usize> = rope
+                .line(line)
+                .chars()
+                .enumerate()
+                .scan(false, |escaped, (column, ch)| {
+                    let is_quote = ch == quote && !*escaped;
+                    *escaped = ch == '\\' && !*escaped;
+                    Some((column, is_quote))
+                })
+                .filter(|(_, is_quote)| *is_quote)
+                .map(|(column, _)| column)
+                .collect();
+
+            // Quotes pair up from the start of the line; prefer the pair around the cursor
+            let (open, close) = quotes
+                .chunks_exact(2)
+                .map(|pair| (pair[0], pair[1]))
+                .find(|(open, close)| *open <= column && column <= *close)
+                .or_else(|| {
+                    quotes
+                        .chunks_exact(2)
+                        .map(|pair| (pair[0], pair[1]))
+                        .find(|(open, _)| *open > column)
+                })?;
+            Some(if around {
+                line_start + open..line_start + close + 1
+            } else {
+                line_start + open + 1..line_start + close
+            })
+        }
+        TextObject::Bracket {
+            open,
+            close,
+            around,
+        } => {
+            let len = rope.len_chars();
+            // Find the unmatched opening bracket before the cursor
+            let mut depth = 0;
+            let mut position = if rope.get_char(index) == Some(close) {
+                index.checked_sub(1)?
+            } else {
+                index.min(len.checked_sub(1)?)
+            };
+            let open_index = loop {
+                let ch = rope.char(position);
+                if ch == open {
+                    if depth == 0 {
+                        break position;
+                    }
+                    depth -= 1;
+                } else if ch == close && position != index {
+                    depth += 1;
+                }
+                position = position.checked_sub(1)?;
+            };
+
+            let mut depth = 0;
+            let mut close_index = open_index + 1;
+            loop {
+                let ch = rope.get_char(close_index)?;
+                if ch == close {
+                    if depth == 0 {
+                        break;
+                    }
+                    depth -= 1;
+                } else if ch == open {
+                    depth += 1;
+                }
+                close_index += 1;
+            }
+
+            Some(if around {
+                open_index..close_index + 1
+            } else {
+                open_index + 1..close_index
+            })
+        }
+    }
+}
+
+/// Substitute `s/pattern/replacement/flags` literally on some lines
+fn substitute_edits(
+    rope: &Rope,
+    lines: RangeInclusive<usize>,
+    command: &str,
+) -> Option<Vec<TextEdit>> {
+    let rest = command.strip_prefix('s')?;
+    let delimiter = rest.chars().next()?;
+    if delimiter.is_alphanumeric() || delimiter.is_whitespace() {
+        return None;
+    }
+    let mut parts = rest[delimiter.len_utf8()..].splitn(3, delimiter);
+    let pattern = parts.next().filter(|pattern| !pattern.is_empty())?;
+    let replacement = parts.next().unwrap_or("");
+    let global = parts.next().is_some_and(|flags| flags.contains('g'));
+
+    let mut edits = Vec::new();
+    for line in lines {
+        let text: String = rope.line(line).chars().take(line_len(rope, line)).collect();
+        for (byte_index, _) in text
+            .match_indices(pattern)
+            .take(if global { usize::MAX } else { 1 })
+        {
+            let column = text[..byte_index].chars().count();
+            edits.push(TextEdit::new(
+                Position::new(line, column)..Position::new(line, column + pattern.chars().count()),
+                replacement,
+            ));
+        }
+    }
+    Some(edits)
+}
+
+/// Find the column of the `times`-th occurrence of a char on the cursor line
+fn find_in_line(
+    rope: &Rope,
+    from: Position,
+    ch: char,
+    forward: bool,
+    till: bool,
+    times: usize,
+) -> Option<usize> {
+    let chars: Vec<char> = rope
+        .line(from.line)
+        .chars()
+        .take(line_len(rope, from.line))
+        .collect();
+    // Repeating `t` must not find the char right next to the cursor again
+    let skip = usize::from(till);
+
+    let column = if forward {
+        (from.column + 1 + skip..chars.len())
+            .filter(|column| chars[*column] == ch)
+            .nth(times - 1)?
+    } else {
+        (0..from.column.saturating_sub(skip))
+            .rev()
+            .filter(|column| chars[*column] == ch)
+            .nth(times - 1)?
+    };
+
+    Some(match (till, forward) {
+        (false, _) => column,
+        (true, true) => column - 1,
+        (true, false) => column + 1,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Whitespace,
+    Word,
+    Punctuation,
+}
+
+fn char_class(ch: char) -> CharClass {
+    if ch.is_whitespace() {
+        CharClass::Whitespace
+    } else if ch.is_alphanumeric() || ch == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+/// Get the start of the next word; empty lines count as words
+fn next_word_start(rope: &Rope, index: usize) -> usize {
+    let len = rope.len_chars();
+    if index >= len {
+        return len;
+    }
+
+    let mut position = index;
+    let class = char_class(rope.char(index));
+    if class != CharClass::Whitespace {
+        while position < len && char_class(rope.char(position)) == class {
+            position += 1;
+        }
+    }
+    while position < len && rope.char(position).is_whitespace() {
+        let empty_line =
+            rope.char(position) == '\n' && position > index && rope.char(position - 1) == '\n';
+        if empty_line {
+            break;
+        }
+        position += 1;
+    }
+    position.min(len.saturating_sub(1))
+}
+
+/// Get the end of the current or next word
+fn word_end(rope: &Rope, index: usize) -> usize {
+    let len = rope.len_chars();
+    let mut position = index + 1;
+    while position < len && rope.char(position).is_whitespace() {
+        position += 1;
+    }
+    if position >= len {
+        return len.saturating_sub(1);
+    }
+    let class = char_class(rope.char(position));
+    while position + 1 < len && char_class(rope.char(position + 1)) == class {
+        position += 1;
+    }
+    position
+}
+
+/// Get the start of the current or previous word
+fn previous_word_start(rope: &Rope, index: usize) -> usize {
+    if index == 0 {
+        return 0;
+    }
+    let mut position = index - 1;
+    while position > 0 && rope.char(position).is_whitespace() {
+        position -= 1;
+    }
+    let class = char_class(rope.char(position));
+    while position > 0 && char_class(rope.char(position - 1)) == class {
+        position -= 1;
+    }
+    position
+}
+
+/// Get the number of chars of a line, excluding its line break
+fn line_len(rope: &Rope, line: usize) -> usize {
+    if line >= rope.len_lines() {
+        return 0;
+    }
+    let slice = rope.line(line);
+    let len = slice.len_chars();
+    if len > 0 && slice.char(len - 1) == '\n' {
+        len - 1
+    } else {
+        len
+    }
+}
+
+/// Get the offset after a line, including its line break
+fn line_end_with_break(rope: &Rope, line: usize) -> usize {
+    if line + 1 < rope.len_lines() {
+        rope.line_to_char(line + 1)
+    } else {
+        rope.len_chars()
+    }
+}
+
+fn first_non_blank(rope: &Rope, line: usize) -> usize {
+    indentation(rope, line).chars().count()
+}
+
+fn indentation(rope: &Rope, line: usize) -> String {
+    if line >= rope.len_lines() {
+        return String::new();
+    }
+    rope.line(line)
+        .chars()
+        .take_while(|ch| *ch == ' ' || *ch == '\t')
+        .collect()
+}
+
+/// Get the offset after the char at an offset, without going past a line break
+fn next_char(rope: &Rope, index: usize) -> usize {
+    match rope.get_char(index) {
+        Some('\n') if index > 0 && rope.char(index - 1) != '\n' => index,
+        Some(_) => index + 1,
+        None => index,
+    }
+}
+
+/// Keep the cursor on a char, as normal mode does
+fn clamp_normal(rope: &Rope, position: Position) -> Position {
+    let line = position.line.min(rope.len_lines().saturating_sub(1));
+    Position::new(
+        line,
+        position.column.min(line_len(rope, line).saturating_sub(1)),
+    )
+}
+
+fn to_index(rope: &Rope, position: Position) -> usize {
+    let line = position.line.min(rope.len_lines().saturating_sub(1));
+    rope.line_to_char(line) + position.column.min(line_len(rope, line))
+}
+
+fn to_position(rope: &Rope, index: usize) -> Position {
+    let index = index.min(rope.len_chars());
+    let line = rope.char_to_line(index);
+    Position::new(line, index - rope.line_to_char(line))
+}
+
+fn ordered(a: Position, b: Position) -> (Position, Position) {
+    if (a.line, a.column) <= (b.line, b.column) {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Modifiers;
+
+    /// Type keys into the editor; `<Esc>`, `<CR>` and `<C-v>` stand for special keys
+    fn type_keys(vim: &mut Vim, editor: &mut Editor, keys: &str) -> Vec<String> {
+        let mut commands = Vec::new();
+        let mut rest = keys;
+        while let Some(ch) = rest.chars().next() {
+            let (event, len) = if rest.starts_with("<Esc>") {
+                (KeyEvent::named(NamedKey::Escape), 5)
+            } else if rest.starts_with("<CR>") {
+                (KeyEvent::named(NamedKey::Enter), 4)
+            } else if rest.starts_with("<C-v>") {
+                let modifiers = Modifiers::new().control();
+                (KeyEvent::with_modifiers(Key::Character('v'), modifiers), 5)
+            } else {
+                (KeyEvent::character(ch), ch.len_utf8())
+            };
+            rest = &rest[len..];
+
+            let output = vim.handle_key(editor, event.clone());
+            commands.extend(output.commands);
+            for message in output.messages {
+                editor.handle_message(message);
+            }
+            if !output.handled {
+                let message = match event.key {
+                    Key::Named(NamedKey::Enter) => EditorMessage::InsertChar('\n'),
+                    Key::Character(ch) => EditorMessage::InsertChar(ch),
+                    _ => continue,
+                };
+                editor.handle_message(message);
+            }
+        }
+        commands
+    }
+
+    fn cursor(editor: &Editor) -> Position {
+        editor.current_cursor().position()
+    }
+
+    #[test]
+    fn test_operators_and_motions() {
+        let mut editor = Editor::with_text("one two three\nfour five");
+        let mut vim = Vim::new();
+
+        type_keys(&mut vim, &mut editor, "dw");
+        assert_eq!(editor.current_buffer().text(), "two three\nfour five");
+        assert_eq!(vim.register('"').unwrap().text, "one ");
+
+        type_keys(&mut vim, &mut editor, "$dbx");
+        assert_eq!(editor.current_buffer().text(), "two \nfour five");
+        assert_eq!(cursor(&editor), Position::new(0, 3));
+
+        type_keys(&mut vim, &mut editor, "jwdfv");
+        assert_eq!(editor.current_buffer().text(), "two \nfour e");
+
+        type_keys(&mut vim, &mut editor, "0d2l");
+        assert_eq!(editor.current_buffer().text(), "two \nur e");
+
+        type_keys(&mut vim, &mut editor, "ggdG");
+        assert_eq!(editor.current_buffer().text(), "");
+        assert_eq!(vim.mode(), VimMode::Normal);
+    }
+
+    #[test]
+    fn test_huge_counts() {
+        let mut editor = Editor::with_text("one two\nthree\nfour");
+        let mut vim = Vim::new();
+
+        // A word motion past the end stops there, and `dw` stops at the line end
+        type_keys(&mut vim, &mut editor, "99999999999d99999999999w");
+        assert_eq!(editor.current_buffer().text(), "\nthree\nfour");
+
+        editor.set_text("one two\nthree\nfour");
+        type_keys(&mut vim, &mut editor, "j18446744073709551615dd");
+        assert_eq!(editor.current_buffer().text(), "one two");
+
+        editor.set_text("one");
+        type_keys(&mut vim, &mut editor, "99999999999p99999999999u");
+        assert_eq!(editor.current_buffer().text(), "one");
+        assert_eq!(parse_count(&['9'; 30]).0, Some(MAX_COUNT));
+    }
+
+    #[test]
+    fn test_change_and_repeat() {
+        let mut editor = Editor::with_text("let foo = foo + 1;");
+        let mut vim = Vim::new();
+
+        type_keys(&mut vim, &mut editor, "wcwbar<Esc>");
+        assert_eq!(editor.current_buffer().text(), "let bar = foo + 1;");
+        assert_eq!(vim.mode(), VimMode::Normal);
+        assert_eq!(cursor(&editor), Position::new(0, 6));
+
+        type_keys(&mut vim, &mut editor, "ww.");
+        assert_eq!(editor.current_buffer().text(), "let bar = bar + 1;");
+
+        type_keys(&mut vim, &mut editor, "03ix<Esc>");
+        assert_eq!(editor.current_buffer().text(), "xxxlet bar = bar + 1;");
+
+        type_keys(&mut vim, &mut editor, "uu");
+        assert_eq!(editor.current_buffer().text(), "let bar = bar + 1;");
+    }
+
+    #[test]
+    fn test_text_objects() {
+        let mut editor = Editor::with_text("call(a, (b), \"text\")");
+        let mut vim = Vim::new();
+
+        type_keys(&mut vim, &mut editor, "t,di(");
+        assert_eq!(editor.current_buffer().text(), "call()");
+
+        editor.set_text("say(\"hello world\")");
+        type_keys(&mut vim, &mut editor, "0fwci\"bye<Esc>");
+        assert_eq!(editor.current_buffer().text(), "say(\"bye\")");
+
+        type_keys(&mut vim, &mut editor, "da(");
+        assert_eq!(editor.current_buffer().text(), "say");
+
+        editor.set_text("one two three");
+        type_keys(&mut vim, &mut editor, "0wdaw");
+        assert_eq!(editor.current_buffer().text(), "one three");
+    }
+
+    #[test]
+    fn test_yank_paste_and_registers() {
+        let mut editor = Editor::with_text("first\nsecond");
+        let mut vim = Vim::new();
+
+        type_keys(&mut vim, &mut editor, "yyjp");
+        assert_eq!(editor.current_buffer().text(), "first\nsecond\nfirst");
+        assert_eq!(cursor(&editor), Position::new(2, 0));
+
+        type_keys(&mut vim, &mut editor, "\"ayiwggdd\"aP");
+        assert_eq!(editor.current_buffer().text(), "firstsecond\nfirst");
+        assert_eq!(vim.register('a').unwrap().text, "first");
+        assert_eq!(vim.register('0').unwrap().text, "first\n");
+        assert_eq!(vim.register('"').unwrap().text, "first\n");
+
+        type_keys(&mut vim, &mut editor, "\"Ayy");
+        assert_eq!(vim.register('a').unwrap().text, "firstfirstsecond\n");
+        assert!(vim.register('a').unwrap().linewise);
+
+        type_keys(&mut vim, &mut editor, "\"_dd");
+        assert_eq!(editor.current_buffer().text(), "first");
+        assert_eq!(vim.register('"').unwrap().text, "firstfirstsecond\n");
+    }
+
+    #[test]
+    fn test_visual_modes() {
+        let mut editor = Editor::with_text("abc\ndef\nghi");
+        let mut vim = Vim::new();
+
+        type_keys(&mut vim, &mut editor, "vl");
+        assert_eq!(vim.mode(), VimMode::Visual);
+        assert_eq!(vim.status().as_deref(), Some("-- VISUAL --"));
+        type_keys(&mut vim, &mut editor, "d");
+        assert_eq!(editor.current_buffer().text(), "c\ndef\nghi");
+
+        type_keys(&mut vim, &mut editor, "jVd");
+        assert_eq!(editor.current_buffer().text(), "c\nghi");
+
+        editor.set_text("abc\ndef\nghi");
+        type_keys(&mut vim, &mut editor, "gg<C-v>jjI# <Esc>");
+        assert_eq!(editor.current_buffer().text(), "# abc\n# def\n# ghi");
+
+        type_keys(&mut vim, &mut editor, "gg<C-v>jld");
+        assert_eq!(editor.current_buffer().text(), "abc\ndef\n# ghi");
+    }
+
+    #[test]
+    fn test_visual_mode_after_external_edit() {
+        let mut editor = Editor::with_text("abc\ndef");
+        let mut vim = Vim::new();
+
+        type_keys(&mut vim, &mut editor, "vj");
+        editor.handle_message(EditorMessage::ApplyEdits(vec![TextEdit::new(
+            Position::new(0, 3)..Position::new(1, 3),
+            "",
+        )]));
+
+        // The cached selection pointed past the end of the buffer, it is kept inside now
+        type_keys(&mut vim, &mut editor, "fa");
+        assert_eq!(vim.mode(), VimMode::Visual);
+        type_keys(&mut vim, &mut editor, "d");
+        assert_eq!(editor.current_buffer().text(), "bc");
+    }
+
+    #[test]
+    fn test_command_line() {
+        let mut editor = Editor::with_text("a a\nb a\nc");
+        let mut vim = Vim::new();
+
+        let commands = type_keys(&mut vim, &mut editor, ":%s/a/x/g<CR>");
+        assert!(commands.is_empty());
+        assert_eq!(editor.current_buffer().text(), "x x\nb x\nc");
+
+        type_keys(&mut vim, &mut editor, ":3<CR>");
+        assert_eq!(cursor(&editor), Position::new(2, 0));
+
+        let commands = type_keys(&mut vim, &mut editor, ":w<CR>");
+        assert_eq!(commands, vec!["w".to_string()]);
+        assert_eq!(vim.mode(), VimMode::Normal);
+    }
+}
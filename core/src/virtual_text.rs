@@ -0,0 +1,137 @@
+use crate::anchor::AnchorId;
+use crate::Position;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// What a piece of virtual text represents, so related hints can be cleared together
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum VirtualTextKind {
+    /// Inferred type, e.g. `: i32` after a binding
+    TypeHint,
+    /// Parameter name before an argument, e.g. `count: `
+    ParameterHint,
+    /// Authorship information for a line
+    Blame,
+    /// Ghost text of an inline suggestion, managed by the editor
+    InlineSuggestion,
+    /// App-defined kind
+    Custom(String),
+}
+
+/// Where virtual text is shown relative to its position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VirtualTextPlacement {
+    /// Between the chars around the position, pushing the rest of the line right
+    Inline,
+    /// After the end of the position's line
+    EndOfLine,
+}
+
+/// Identifier of a piece of virtual text within its buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VirtualTextId(u64);
+
+/// Virtual text whose position is tracked by a buffer anchor
+#[derive(Debug, Clone)]
+pub(crate) struct VirtualText {
+    pub kind: VirtualTextKind,
+    pub text: String,
+    pub placement: VirtualTextPlacement,
+    pub anchor: AnchorId,
+}
+
+/// Virtual text resolved to its current position
+#[derive(Debug, Clone, PartialEq)]
+pub struct VirtualTextSpan<'a> {
+    pub id: VirtualTextId,
+    pub kind: &'a VirtualTextKind,
+    pub text: &'a str,
+    pub placement: VirtualTextPlacement,
+    /// Char column the text is shown before; only the line matters for end of line text
+    pub position: Position,
+}
+
+/// Virtual text of a buffer, in the order it was added
+#[derive(Debug, Clone, Default)]
+pub(crate) struct VirtualTextSet {
+    texts: BTreeMap<VirtualTextId, VirtualText>,
+    /// Ids ordered by the offset of their anchor
+    ///
+    /// Virtual text anchors all share the same gravity, so edits move them without
+    /// ever reordering them and the order only has to be kept on insertion.
+    by_offset: Vec<VirtualTextId>,
+    next_id: u64,
+    generation: u64,
+}
+
+impl VirtualTextSet {
+    /// Add virtual text, with `offset` resolving anchors to their char offset
+    pub fn insert(
+        &mut self,
+        text: VirtualText,
+        offset: impl Fn(AnchorId) -> usize,
+    ) -> VirtualTextId {
+        let id = VirtualTextId(self.next_id);
+        self.next_id += 1;
+        self.generation += 1;
+
+        let at = offset(text.anchor);
+        let index = self
+            .by_offset
+            .partition_point(|other| offset(self.texts[other].anchor) <= at);
+        self.by_offset.insert(index, id);
+        self.texts.insert(id, text);
+        id
+    }
+
+    pub fn remove(&mut self, id: VirtualTextId) -> Option<VirtualText> {
+        let removed = self.texts.remove(&id);
+        if removed.is_some() {
+            self.by_offset.retain(|other| *other != id);
+            self.generation += 1;
+        }
+        removed
+    }
+
+    /// Remove every piece of virtual text matching a predicate
+    pub fn drain_where(
+        &mut self,
+        mut predicate: impl FnMut(&VirtualText) -> bool,
+    ) -> Vec<VirtualText> {
+        let ids: Vec<VirtualTextId> = self
+            .texts
+            .iter()
+            .filter(|(_, text)| predicate(text))
+            .map(|(id, _)| *id)
+            .collect();
+
+        ids.into_iter().filter_map(|id| self.remove(id)).collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (VirtualTextId, &VirtualText)> {
+        self.texts.iter().map(|(id, text)| (*id, text))
+    }
+
+    /// Iterate over the virtual text within a char range, ordered by offset
+    pub fn in_range(
+        &self,
+        range: Range<usize>,
+        offset: impl Fn(AnchorId) -> usize,
+    ) -> impl Iterator<Item = (VirtualTextId, &VirtualText)> {
+        let position = |bound: usize| {
+            self.by_offset
+                .partition_point(|id| offset(self.texts[id].anchor) < bound)
+        };
+        let start = position(range.start);
+        let end = position(range.end).max(start);
+
+        self.by_offset[start..end]
+            .iter()
+            .map(|id| (*id, &self.texts[id]))
+    }
+
+    /// Counter bumped whenever virtual text is added or removed
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
@@ -0,0 +1,685 @@
+use crate::convert::{
+    char_to_lsp, completion_item_from_json, diagnostic_from_json, position_to_lsp, range_from_json,
+    text_edit_from_json, LspPosition,
+};
+use crate::transport::{read_message, write_message};
+use icedit_core::completion::word_start;
+use icedit_core::{Buffer, CompletionItem, Diagnostic, EditorMessage, Position, TextEdit, Tooltip};
+use ropey::Rope;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufReader, Read, Write};
+use std::ops::Range;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Identifier of a request sent to the server
+pub type RequestId = u64;
+
+/// Errors that can occur while talking to a language server
+#[derive(Debug, Error)]
+pub enum LspError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Language server exited")]
+    ServerExited,
+    #[error("Timed out waiting for the language server")]
+    Timeout,
+    #[error("Document is not open: {0}")]
+    DocumentNotOpen(String),
+    #[error("Language server error: {0}")]
+    Server(String),
+}
+
+/// How the server wants document changes to be sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncKind {
+    None,
+    Full,
+    Incremental,
+}
+
+/// A location in a document; the range uses char columns
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub uri: String,
+    pub range: Range<Position>,
+}
+
+/// Something the server sent that the application may want to act on
+#[derive(Debug, Clone, PartialEq)]
+pub enum LspEvent {
+    Diagnostics {
+        uri: String,
+        diagnostics: Vec<Diagnostic>,
+    },
+    Completion {
+        id: RequestId,
+        uri: String,
+        items: Vec<CompletionItem>,
+    },
+    Hover {
+        id: RequestId,
+        uri: String,
+        /// Position the hover was requested for
+        position: Position,
+        /// Markdown or plain text contents
+        contents: Option<String>,
+        range: Option<Range<Position>>,
+    },
+    Definition {
+        id: RequestId,
+        locations: Vec<Location>,
+    },
+    Formatting {
+        id: RequestId,
+        uri: String,
+        edits: Vec<TextEdit>,
+    },
+    /// A request failed on the server side
+    Error { id: RequestId, message: String },
+}
+
+impl LspEvent {
+    /// Convert the event into a message for the editor showing `uri`, if it maps to one
+    pub fn to_editor_message(&self, uri: &str) -> Option<EditorMessage> {
+        match self {
+            LspEvent::Diagnostics {
+                uri: target,
+                diagnostics,
+            } if target == uri => Some(EditorMessage::SetDiagnostics(diagnostics.clone())),
+            LspEvent::Completion {
+                uri: target, items, ..
+            } if target == uri => Some(EditorMessage::ShowCompletions(items.clone())),
+            LspEvent::Formatting {
+                uri: target, edits, ..
+            } if target == uri => Some(EditorMessage::ApplyEdits(edits.clone())),
+            LspEvent::Hover {
+                uri: target,
+                position,
+                contents: Some(contents),
+                range,
+                ..
+            } if target == uri => {
+                let tooltip = Tooltip::markdown(*position, contents);
+                Some(EditorMessage::ShowTooltip(match range {
+                    Some(range) => tooltip.with_range(range.clone()),
+                    None => tooltip,
+                }))
+            }
+            LspEvent::Definition { locations, .. } => locations
+                .iter()
+                .find(|location| location.uri == uri)
+                .map(|location| EditorMessage::MoveCursorTo(location.range.start)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum RequestKind {
+    Initialize,
+    Shutdown,
+    Completion { uri: String, position: Position },
+    Hover { uri: String, position: Position },
+    Definition,
+    Formatting { uri: String },
+}
+
+/// The server's view of an open document
+#[derive(Debug)]
+struct Document {
+    rope: Rope,
+    version: i64,
+    /// Buffer revision the server has last been told about
+    revision: u64,
+}
+
+/// Client for a language server speaking JSON-RPC over stdio
+pub struct LspClient {
+    /// The server process, when the client started it
+    child: Option<Child>,
+    writer: Box<dyn Write + Send>,
+    incoming: Receiver<Value>,
+    next_id: RequestId,
+    pending: HashMap<RequestId, RequestKind>,
+    queued: VecDeque<LspEvent>,
+    sync_kind: SyncKind,
+    documents: HashMap<String, Document>,
+}
+
+impl LspClient {
+    /// Start a language server process
+    pub fn spawn(program: &str, args: &[&str]) -> Result<Self, LspError> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let stdin = child.stdin.take().ok_or(LspError::ServerExited)?;
+        let stdout = child.stdout.take().ok_or(LspError::ServerExited)?;
+
+        let mut client = Self::connect(stdout, stdin);
+        client.child = Some(child);
+        Ok(client)
+    }
+
+    /// The OS process id of a server started with [`LspClient::spawn`]
+    pub fn process_id(&self) -> Option<u32> {
+        self.child.as_ref().map(Child::id)
+    }
+
+    /// Talk to a server over an already open connection, such as a socket
+    ///
+    /// The server counts as exited once `reader` reaches end of file.
+    pub fn connect(
+        reader: impl Read + Send + 'static,
+        writer: impl Write + Send + 'static,
+    ) -> Self {
+        // Read on a separate thread so polling never blocks the UI
+        let (sender, incoming) = mpsc::channel();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            while let Ok(Some(message)) = read_message(&mut reader) {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            child: None,
+            writer: Box::new(writer),
+            incoming,
+            next_id: 1,
+            pending: HashMap::new(),
+            queued: VecDeque::new(),
+            sync_kind: SyncKind::Full,
+            documents: HashMap::new(),
+        }
+    }
+
+    /// Perform the initialize handshake
+    pub fn initialize(&mut self, root_uri: &str, timeout: Duration) -> Result<(), LspError> {
+        let id = self.request(
+            RequestKind::Initialize,
+            "initialize",
+            json!({
+                "processId": std::process::id(),
+                "rootUri": root_uri,
+                "capabilities": {
+                    "general": { "positionEncodings": ["utf-16"] },
+                    "textDocument": {
+                        "synchronization": { "dynamicRegistration": false },
+                        "publishDiagnostics": {},
+                        "completion": {},
+                        "hover": { "contentFormat": ["plaintext", "markdown"] },
+                        "definition": {},
+                        "formatting": {},
+                    },
+                },
+            }),
+        )?;
+
+        let result = self.wait_response(id, timeout)?;
+        let sync = &result["capabilities"]["textDocumentSync"];
+        let change = sync.get("change").unwrap_or(sync).as_u64();
+        self.sync_kind = match change {
+            Some(0) => SyncKind::None,
+            Some(2) => SyncKind::Incremental,
+            _ => SyncKind::Full,
+        };
+
+        self.notify("initialized", json!({}))
+    }
+
+    /// How document changes are sent to the server
+    pub fn sync_kind(&self) -> SyncKind {
+        self.sync_kind
+    }
+
+    /// Tell the server a document was opened
+    pub fn did_open(
+        &mut self,
+        uri: &str,
+        language_id: &str,
+        buffer: &Buffer,
+    ) -> Result<(), LspError> {
+        self.documents.insert(
+            uri.to_string(),
+            Document {
+                rope: buffer.rope().clone(),
+                version: 0,
+                revision: buffer.revision(),
+            },
+        );
+
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": uri,
+                    "languageId": language_id,
+                    "version": 0,
+                    "text": buffer.text(),
+                },
+            }),
+        )
+    }
+
+    /// Send the edits made to a buffer since the server last saw it
+    ///
+    /// Changes are sent incrementally when the server supports it and the buffer
+    /// still remembers them, otherwise the whole text is sent.
+    pub fn did_change(&mut self, uri: &str, buffer: &Buffer) -> Result<(), LspError> {
+        let sync_kind = self.sync_kind;
+        let document = self
+            .documents
+            .get_mut(uri)
+            .ok_or_else(|| LspError::DocumentNotOpen(uri.to_string()))?;
+
+        if document.revision == buffer.revision() {
+            return Ok(());
+        }
+
+        let changes = match buffer.changes_since(document.revision) {
+            Some(changes) if sync_kind == SyncKind::Incremental => changes
+                .map(|change| {
+                    let start = char_to_lsp(&document.rope, change.old_range.start);
+                    let end = char_to_lsp(&document.rope, change.old_range.end);
+                    document.rope.remove(change.old_range.clone());
+                    document.rope.insert(change.old_range.start, &change.text);
+
+                    json!({
+                        "range": { "start": start.to_json(), "end": end.to_json() },
+                        "text": change.text,
+                    })
+                })
+                .collect(),
+            _ => {
+                document.rope = buffer.rope().clone();
+                vec![json!({ "text": buffer.text() })]
+            }
+        };
+
+        document.version += 1;
+        document.revision = buffer.revision();
+        let version = document.version;
+
+        if sync_kind == SyncKind::None {
+            return Ok(());
+        }
+
+        self.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": uri, "version": version },
+                "contentChanges": changes,
+            }),
+        )
+    }
+
+    /// Tell the server a document was closed
+    pub fn did_close(&mut self, uri: &str) -> Result<(), LspError> {
+        self.documents.remove(uri);
+        self.notify(
+            "textDocument/didClose",
+            json!({ "textDocument": { "uri": uri } }),
+        )
+    }
+
+    /// Request completions at a position
+    pub fn completion(
+        &mut self,
+        uri: &str,
+        buffer: &Buffer,
+        position: Position,
+    ) -> Result<RequestId, LspError> {
+        let params = self.position_params(uri, buffer, position)?;
+        self.request(
+            RequestKind::Completion {
+                uri: uri.to_string(),
+                position,
+            },
+            "textDocument/completion",
+            params,
+        )
+    }
+
+    /// Request hover information at a position
+    pub fn hover(
+        &mut self,
+        uri: &str,
+        buffer: &Buffer,
+        position: Position,
+    ) -> Result<RequestId, LspError> {
+        let params = self.position_params(uri, buffer, position)?;
+        let kind = RequestKind::Hover {
+            uri: uri.to_string(),
+            position,
+        };
+        self.request(kind, "textDocument/hover", params)
+    }
+
+    /// Request the definition of the symbol at a position
+    pub fn definition(
+        &mut self,
+        uri: &str,
+        buffer: &Buffer,
+        position: Position,
+    ) -> Result<RequestId, LspError> {
+        let params = self.position_params(uri, buffer, position)?;
+        self.request(RequestKind::Definition, "textDocument/definition", params)
+    }
+
+    /// Request formatting edits for a whole document
+    pub fn formatting(
+        &mut self,
+        uri: &str,
+        buffer: &Buffer,
+        tab_size: u32,
+        insert_spaces: bool,
+    ) -> Result<RequestId, LspError> {
+        self.did_change(uri, buffer)?;
+        let kind = RequestKind::Formatting {
+            uri: uri.to_string(),
+        };
+        self.request(
+            kind,
+            "textDocument/formatting",
+            json!({
+                "textDocument": { "uri": uri },
+                "options": { "tabSize": tab_size, "insertSpaces": insert_spaces },
+            }),
+        )
+    }
+
+    /// Get all events received so far without blocking
+    pub fn poll_events(&mut self) -> Vec<LspEvent> {
+        while let Ok(message) = self.incoming.try_recv() {
+            // Replies to server requests are best effort; a dead server shows up later
+            let _ = self.handle_message(message);
+        }
+        self.queued.drain(..).collect()
+    }
+
+    /// Wait for the next event
+    pub fn wait_event(&mut self, timeout: Duration) -> Result<LspEvent, LspError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(event) = self.queued.pop_front() {
+                return Ok(event);
+            }
+            let message = self.recv_until(deadline)?;
+            self.handle_message(message)?;
+        }
+    }
+
+    /// Shut the server down gracefully and wait for it to exit
+    pub fn shutdown(&mut self, timeout: Duration) -> Result<(), LspError> {
+        let id = self.request(RequestKind::Shutdown, "shutdown", Value::Null)?;
+        self.wait_response(id, timeout)?;
+        self.notify("exit", Value::Null)?;
+
+        let deadline = Instant::now() + timeout;
+        let Some(child) = &mut self.child else {
+            // Without a process, exiting means closing the connection
+            loop {
+                match self.recv_until(deadline) {
+                    Ok(_) => continue,
+                    Err(LspError::ServerExited) => return Ok(()),
+                    Err(e) => return Err(e),
+                }
+            }
+        };
+        while Instant::now() < deadline {
+            if child.try_wait()?.is_some() {
+                return Ok(());
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        Err(LspError::Timeout)
+    }
+
+    fn position_params(
+        &mut self,
+        uri: &str,
+        buffer: &Buffer,
+        position: Position,
+    ) -> Result<Value, LspError> {
+        self.did_change(uri, buffer)?;
+        Ok(json!({
+            "textDocument": { "uri": uri },
+            "position": position_to_lsp(buffer.rope(), position).to_json(),
+        }))
+    }
+
+    fn request(
+        &mut self,
+        kind: RequestKind,
+        method: &str,
+        params: Value,
+    ) -> Result<RequestId, LspError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending.insert(id, kind);
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))?;
+        Ok(id)
+    }
+
+    fn notify(&mut self, method: &str, params: Value) -> Result<(), LspError> {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    fn send(&mut self, message: Value) -> Result<(), LspError> {
+        write_message(&mut self.writer, &message).map_err(|e| match e.kind() {
+            io::ErrorKind::BrokenPipe => LspError::ServerExited,
+            _ => LspError::Io(e),
+        })
+    }
+
+    fn recv_until(&mut self, deadline: Instant) -> Result<Value, LspError> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        self.incoming.recv_timeout(timeout).map_err(|e| match e {
+            RecvTimeoutError::Timeout => LspError::Timeout,
+            RecvTimeoutError::Disconnected => LspError::ServerExited,
+        })
+    }
+
+    /// Wait for the response to an internal request, queueing anything else
+    fn wait_response(&mut self, id: RequestId, timeout: Duration) -> Result<Value, LspError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let message = self.recv_until(deadline)?;
+            if message.get("method").is_none() && message["id"].as_u64() == Some(id) {
+                self.pending.remove(&id);
+                if let Some(error) = message.get("error") {
+                    return Err(LspError::Server(error_message(error)));
+                }
+                return Ok(message.get("result").cloned().unwrap_or(Value::Null));
+            }
+            self.handle_message(message)?;
+        }
+    }
+
+    fn handle_message(&mut self, message: Value) -> Result<(), LspError> {
+        match (
+            message.get("id"),
+            message.get("method").and_then(Value::as_str),
+        ) {
+            // Requests from the server, e.g. `workspace/configuration`; none are supported
+            (Some(id), Some(_)) => self.send(json!({ "jsonrpc": "2.0", "id": id, "result": null })),
+            (None, Some(method)) => {
+                self.handle_notification(method, &message["params"]);
+                Ok(())
+            }
+            (Some(id), None) => {
+                if let Some(kind) = id.as_u64().and_then(|id| self.pending.remove(&id)) {
+                    let id = id.as_u64().unwrap_or_default();
+                    let event = match message.get("error") {
+                        Some(error) => Some(LspEvent::Error {
+                            id,
+                            message: error_message(error),
+                        }),
+                        None => self.response_event(id, kind, &message["result"]),
+                    };
+                    self.queued.extend(event);
+                }
+                Ok(())
+            }
+            (None, None) => Ok(()),
+        }
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Value) {
+        if method != "textDocument/publishDiagnostics" {
+            return;
+        }
+
+        let Some(uri) = params["uri"].as_str() else {
+            return;
+        };
+        let rope = self.rope_for(uri);
+        let diagnostics = params["diagnostics"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|diagnostic| diagnostic_from_json(&rope, diagnostic))
+            .collect();
+
+        self.queued.push_back(LspEvent::Diagnostics {
+            uri: uri.to_string(),
+            diagnostics,
+        });
+    }
+
+    fn response_event(&self, id: RequestId, kind: RequestKind, result: &Value) -> Option<LspEvent> {
+        match kind {
+            RequestKind::Initialize | RequestKind::Shutdown => None,
+            RequestKind::Completion { uri, position } => {
+                let rope = self.rope_for(&uri);
+                let word = word_start(&rope, position)..position;
+                // Either a plain list or a `CompletionList`
+                let items = result.get("items").unwrap_or(result);
+                let items = items
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|item| completion_item_from_json(&rope, item, word.clone()))
+                    .collect();
+                Some(LspEvent::Completion { id, uri, items })
+            }
+            RequestKind::Hover { uri, position } => {
+                let rope = self.rope_for(&uri);
+                Some(LspEvent::Hover {
+                    id,
+                    uri,
+                    position,
+                    contents: hover_text(&result["contents"]),
+                    range: result
+                        .get("range")
+                        .and_then(|range| range_from_json(&rope, range)),
+                })
+            }
+            RequestKind::Definition => {
+                // Either a single location, a list of them, or a list of links
+                let locations = match result {
+                    Value::Array(locations) => locations.iter().collect(),
+                    Value::Object(_) => vec![result],
+                    _ => Vec::new(),
+                };
+                let locations = locations
+                    .into_iter()
+                    .filter_map(|location| {
+                        let uri = location
+                            .get("uri")
+                            .or_else(|| location.get("targetUri"))?
+                            .as_str()?;
+                        let range = location
+                            .get("range")
+                            .or_else(|| location.get("targetSelectionRange"))?;
+                        Some(Location {
+                            uri: uri.to_string(),
+                            range: self.location_range(uri, range)?,
+                        })
+                    })
+                    .collect();
+                Some(LspEvent::Definition { id, locations })
+            }
+            RequestKind::Formatting { uri } => {
+                let rope = self.rope_for(&uri);
+                let edits = result
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|edit| text_edit_from_json(&rope, edit))
+                    .collect();
+                Some(LspEvent::Formatting { id, uri, edits })
+            }
+        }
+    }
+
+    /// Convert a range in any document, open or not
+    ///
+    /// Without the document text UTF-16 offsets can't be mapped exactly, so they are
+    /// taken as char columns.
+    fn location_range(&self, uri: &str, range: &Value) -> Option<Range<Position>> {
+        if let Some(document) = self.documents.get(uri) {
+            return range_from_json(&document.rope, range);
+        }
+
+        let start = LspPosition::from_json(range.get("start")?)?;
+        let end = LspPosition::from_json(range.get("end")?)?;
+        Some(
+            Position::new(start.line as usize, start.character as usize)
+                ..Position::new(end.line as usize, end.character as usize),
+        )
+    }
+
+    /// The text the server has for a document
+    ///
+    /// Documents that are not open fall back to an empty rope, in which case
+    /// positions are clamped to the start of the document.
+    fn rope_for(&self, uri: &str) -> Rope {
+        self.documents
+            .get(uri)
+            .map(|document| document.rope.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for LspClient {
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Extract readable text from the `contents` of a hover response
+fn hover_text(contents: &Value) -> Option<String> {
+    let text = match contents {
+        Value::String(text) => text.clone(),
+        Value::Object(object) => object.get("value")?.as_str()?.to_string(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(hover_text)
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        _ => return None,
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+fn error_message(error: &Value) -> String {
+    error["message"]
+        .as_str()
+        .unwrap_or("unknown error")
+        .to_string()
+}
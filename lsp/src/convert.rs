@@ -0,0 +1,216 @@
+use icedit_core::{
+    CompletionItem, CompletionKind, Diagnostic, DiagnosticSeverity, Position, TextEdit,
+};
+use ropey::Rope;
+use serde_json::{json, Value};
+use std::ops::Range;
+
+/// A position as the protocol sees it: a line and a UTF-16 code unit offset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LspPosition {
+    pub line: u32,
+    pub character: u32,
+}
+
+impl LspPosition {
+    pub fn new(line: u32, character: u32) -> Self {
+        Self { line, character }
+    }
+
+    pub fn to_json(self) -> Value {
+        json!({ "line": self.line, "character": self.character })
+    }
+
+    pub fn from_json(value: &Value) -> Option<Self> {
+        Some(Self {
+            line: value.get("line")?.as_u64()? as u32,
+            character: value.get("character")?.as_u64()? as u32,
+        })
+    }
+}
+
+/// Convert a char offset into a protocol position
+pub fn char_to_lsp(rope: &Rope, char_idx: usize) -> LspPosition {
+    let char_idx = char_idx.min(rope.len_chars());
+    let line = rope.char_to_line(char_idx);
+    let line_start = rope.line_to_char(line);
+    let character = rope.char_to_utf16_cu(char_idx) - rope.char_to_utf16_cu(line_start);
+    LspPosition::new(line as u32, character as u32)
+}
+
+/// Convert a protocol position into a char offset
+///
+/// Out of range positions are clamped to the end of the line or document.
+pub fn lsp_to_char(rope: &Rope, position: LspPosition) -> usize {
+    let line = position.line as usize;
+    if line >= rope.len_lines() {
+        return rope.len_chars();
+    }
+
+    let line_start = rope.line_to_char(line);
+    let line_end = line_start + line_content_len(rope, line);
+    let utf16_start = rope.char_to_utf16_cu(line_start);
+    let utf16_end = rope.char_to_utf16_cu(line_end);
+    let target = (utf16_start + position.character as usize).min(utf16_end);
+    rope.utf16_cu_to_char(target)
+}
+
+/// Convert an editor position (char column) into a char offset
+pub fn position_to_char(rope: &Rope, position: Position) -> usize {
+    if position.line >= rope.len_lines() {
+        return rope.len_chars();
+    }
+    rope.line_to_char(position.line) + position.column.min(line_content_len(rope, position.line))
+}
+
+/// Convert a char offset into an editor position (char column)
+pub fn char_to_position(rope: &Rope, char_idx: usize) -> Position {
+    let char_idx = char_idx.min(rope.len_chars());
+    let line = rope.char_to_line(char_idx);
+    Position::new(line, char_idx - rope.line_to_char(line))
+}
+
+/// Convert an editor position into a protocol position
+pub fn position_to_lsp(rope: &Rope, position: Position) -> LspPosition {
+    char_to_lsp(rope, position_to_char(rope, position))
+}
+
+/// Convert a protocol position into an editor position
+pub fn lsp_to_position(rope: &Rope, position: LspPosition) -> Position {
+    char_to_position(rope, lsp_to_char(rope, position))
+}
+
+/// Parse a protocol `Range` object into an editor range
+pub fn range_from_json(rope: &Rope, value: &Value) -> Option<Range<Position>> {
+    let start = LspPosition::from_json(value.get("start")?)?;
+    let end = LspPosition::from_json(value.get("end")?)?;
+    Some(lsp_to_position(rope, start)..lsp_to_position(rope, end))
+}
+
+/// Parse a protocol `Diagnostic` object
+pub fn diagnostic_from_json(rope: &Rope, value: &Value) -> Option<Diagnostic> {
+    let severity = match value.get("severity").and_then(Value::as_u64) {
+        Some(2) => DiagnosticSeverity::Warning,
+        Some(3) => DiagnosticSeverity::Information,
+        Some(4) => DiagnosticSeverity::Hint,
+        // The protocol leaves missing severities to the client; treat them as errors
+        _ => DiagnosticSeverity::Error,
+    };
+
+    Some(Diagnostic {
+        range: range_from_json(rope, value.get("range")?)?,
+        severity,
+        message: value.get("message")?.as_str()?.to_string(),
+        source: value
+            .get("source")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+    })
+}
+
+/// Parse a protocol `TextEdit` object
+pub fn text_edit_from_json(rope: &Rope, value: &Value) -> Option<TextEdit> {
+    Some(TextEdit::new(
+        range_from_json(rope, value.get("range")?)?,
+        value.get("newText")?.as_str()?,
+    ))
+}
+
+/// Parse a protocol `CompletionItem` object
+///
+/// Items without a text edit of their own replace `word`, the word being completed.
+pub fn completion_item_from_json(
+    rope: &Rope,
+    value: &Value,
+    word: Range<Position>,
+) -> Option<CompletionItem> {
+    let label = value.get("label")?.as_str()?;
+    let edit = match value.get("textEdit") {
+        // An `InsertReplaceEdit`; inserting keeps the text after the cursor
+        Some(edit) if edit.get("insert").is_some() => TextEdit::new(
+            range_from_json(rope, &edit["insert"])?,
+            edit.get("newText")?.as_str()?,
+        ),
+        Some(edit) => text_edit_from_json(rope, edit)?,
+        None => TextEdit::new(word, value["insertText"].as_str().unwrap_or(label)),
+    };
+
+    let item = CompletionItem::new(label, completion_kind(value["kind"].as_u64()), edit);
+    Some(match value["detail"].as_str() {
+        Some(detail) => item.with_detail(detail),
+        None => item,
+    })
+}
+
+/// Map a protocol `CompletionItemKind` onto the editor's coarser kinds
+fn completion_kind(kind: Option<u64>) -> CompletionKind {
+    match kind {
+        Some(2) => CompletionKind::Method,
+        Some(3 | 4) => CompletionKind::Function,
+        Some(5 | 10) => CompletionKind::Field,
+        Some(6 | 12 | 20 | 21) => CompletionKind::Variable,
+        Some(7 | 8 | 13 | 22 | 25) => CompletionKind::Type,
+        Some(9) => CompletionKind::Module,
+        Some(14) => CompletionKind::Keyword,
+        Some(15) => CompletionKind::Snippet,
+        _ => CompletionKind::Text,
+    }
+}
+
+/// Length of a line in chars, excluding its line break
+fn line_content_len(rope: &Rope, line: usize) -> usize {
+    let slice = rope.line(line);
+    let mut len = slice.len_chars();
+    while len > 0 && matches!(slice.char(len - 1), '\n' | '\r') {
+        len -= 1;
+    }
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utf16_round_trip() {
+        // The emoji takes two UTF-16 code units but a single char
+        let rope = Rope::from_str("a😀b\né\n");
+
+        assert_eq!(char_to_lsp(&rope, 2), LspPosition::new(0, 3));
+        assert_eq!(lsp_to_char(&rope, LspPosition::new(0, 3)), 2);
+        assert_eq!(char_to_lsp(&rope, 4), LspPosition::new(1, 0));
+        assert_eq!(
+            lsp_to_position(&rope, LspPosition::new(1, 1)),
+            Position::new(1, 1)
+        );
+
+        // Positions past the end of a line clamp to the line end
+        assert_eq!(lsp_to_char(&rope, LspPosition::new(0, 99)), 3);
+        assert_eq!(lsp_to_char(&rope, LspPosition::new(9, 0)), rope.len_chars());
+    }
+
+    #[test]
+    fn test_completion_item_edits() {
+        let rope = Rope::from_str("foo.ba(x)\n");
+        let word = Position::new(0, 4)..Position::new(0, 6);
+
+        let item =
+            completion_item_from_json(&rope, &json!({ "label": "bar", "kind": 2 }), word.clone())
+                .unwrap();
+        assert_eq!(item.kind, CompletionKind::Method);
+        assert_eq!(item.edit, TextEdit::new(word.clone(), "bar"));
+
+        // Insert-replace edits insert rather than overwrite the text after the cursor
+        let value = json!({
+            "label": "baz",
+            "textEdit": {
+                "insert": { "start": { "line": 0, "character": 4 }, "end": { "line": 0, "character": 6 } },
+                "replace": { "start": { "line": 0, "character": 4 }, "end": { "line": 0, "character": 9 } },
+                "newText": "baz",
+            },
+        });
+        let item = completion_item_from_json(&rope, &value, word.clone()).unwrap();
+        assert_eq!(item.kind, CompletionKind::Text);
+        assert_eq!(item.edit, TextEdit::new(word, "baz"));
+    }
+}
@@ -0,0 +1,13 @@
+//! Language Server Protocol client for IcEdit
+//!
+//! [`LspClient`] runs a language server as a child process, or talks to one over
+//! an existing connection, and keeps it in sync with an [`icedit_core::Buffer`].
+//! Server responses arrive as [`LspEvent`]s, which can be turned into
+//! [`icedit_core::EditorMessage`]s for the editor.
+
+pub mod client;
+pub mod convert;
+pub mod transport;
+
+pub use client::{Location, LspClient, LspError, LspEvent, RequestId, SyncKind};
+pub use convert::LspPosition;
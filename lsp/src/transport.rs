@@ -0,0 +1,67 @@
+use serde_json::Value;
+use std::io::{self, BufRead, Write};
+
+/// Write a JSON-RPC message with its `Content-Length` header
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+/// Read the next JSON-RPC message, returning `None` at end of stream
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut content_length = None;
+
+    // Headers end with an empty line
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let content_length = content_length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header")
+    })?;
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_message_round_trip() {
+        let message = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}});
+
+        let mut bytes = Vec::new();
+        write_message(&mut bytes, &message).unwrap();
+        write_message(&mut bytes, &json!({"text": "é😀"})).unwrap();
+
+        let mut reader = io::Cursor::new(bytes);
+        assert_eq!(read_message(&mut reader).unwrap(), Some(message));
+        assert_eq!(
+            read_message(&mut reader).unwrap(),
+            Some(json!({"text": "é😀"}))
+        );
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+}
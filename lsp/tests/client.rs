@@ -0,0 +1,183 @@
+use icedit_core::{
+    CompletionKind, DiagnosticSeverity, Editor, EditorMessage, Position, TextEdit, Tooltip,
+};
+use icedit_lsp::{LspClient, LspEvent, SyncKind};
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+#[path = "support/mock_server.rs"]
+mod mock_server;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+const URI: &str = "file:///tmp/main.rs";
+
+/// Connect to the mock server running on its own thread
+fn start_client() -> LspClient {
+    let (server_reader, client_writer) = io::pipe().unwrap();
+    let (client_reader, server_writer) = io::pipe().unwrap();
+    thread::spawn(move || mock_server::run(server_reader, server_writer));
+
+    let mut client = LspClient::connect(client_reader, client_writer);
+    client.initialize("file:///tmp", TIMEOUT).unwrap();
+    client
+}
+
+fn next_event(client: &mut LspClient) -> LspEvent {
+    client.wait_event(TIMEOUT).unwrap()
+}
+
+#[test]
+fn test_document_sync_and_diagnostics() {
+    let mut client = start_client();
+    assert_eq!(client.sync_kind(), SyncKind::Incremental);
+
+    let mut editor = Editor::with_text("fn main() {\n    let s = \"😀\"; // TODO\n}\n");
+    client
+        .did_open(URI, "rust", editor.current_buffer())
+        .unwrap();
+
+    // The emoji is two UTF-16 code units, but a single char column
+    let event = next_event(&mut client);
+    let message = event.to_editor_message(URI).unwrap();
+    let EditorMessage::SetDiagnostics(diagnostics) = &message else {
+        panic!("expected diagnostics, got {message:?}");
+    };
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, DiagnosticSeverity::Warning);
+    assert_eq!(diagnostics[0].range.start, Position::new(1, 20));
+    assert_eq!(diagnostics[0].range.end, Position::new(1, 24));
+
+    editor.handle_message(message);
+    assert_eq!(editor.current_buffer().diagnostics().len(), 1);
+
+    // Insert a second TODO after the emoji, which is sent as an incremental change
+    editor.handle_message(EditorMessage::MoveCursorTo(Position::new(1, 14)));
+    editor.handle_message(EditorMessage::InsertText("TODO".to_string()));
+    client.did_change(URI, editor.current_buffer()).unwrap();
+
+    let LspEvent::Diagnostics { diagnostics, .. } = next_event(&mut client) else {
+        panic!("expected diagnostics");
+    };
+    let starts: Vec<Position> = diagnostics.iter().map(|d| d.range.start).collect();
+    assert_eq!(starts, vec![Position::new(1, 14), Position::new(1, 24)]);
+
+    client.shutdown(TIMEOUT).unwrap();
+}
+
+#[test]
+fn test_requests() {
+    let mut client = start_client();
+
+    let mut editor = Editor::with_text("let x = 1;   \nx  \n");
+    client
+        .did_open(URI, "rust", editor.current_buffer())
+        .unwrap();
+    assert!(matches!(
+        next_event(&mut client),
+        LspEvent::Diagnostics { .. }
+    ));
+
+    let id = client
+        .hover(URI, editor.current_buffer(), Position::new(1, 0))
+        .unwrap();
+    let event = next_event(&mut client);
+    assert_eq!(
+        event,
+        LspEvent::Hover {
+            id,
+            uri: URI.to_string(),
+            position: Position::new(1, 0),
+            contents: Some("x".to_string()),
+            range: None,
+        }
+    );
+    assert_eq!(
+        event.to_editor_message(URI),
+        Some(EditorMessage::ShowTooltip(Tooltip::text(
+            Position::new(1, 0),
+            "x"
+        )))
+    );
+
+    // Completion items without an edit replace the word before the cursor
+    let id = client
+        .completion(URI, editor.current_buffer(), Position::new(1, 1))
+        .unwrap();
+    let event = next_event(&mut client);
+    let LspEvent::Completion {
+        id: reply, items, ..
+    } = &event
+    else {
+        panic!("expected completion");
+    };
+    assert_eq!(*reply, id);
+    assert_eq!(items[0].kind, CompletionKind::Function);
+    assert_eq!(items[0].detail.as_deref(), Some("macro"));
+    assert_eq!(
+        items[0].edit,
+        TextEdit::new(Position::new(1, 0)..Position::new(1, 1), "println!(\"\")")
+    );
+    assert_eq!(items[1].kind, CompletionKind::Method);
+
+    // Jump to the definition
+    editor.handle_message(EditorMessage::MoveCursorTo(Position::new(1, 1)));
+    client
+        .definition(URI, editor.current_buffer(), Position::new(1, 1))
+        .unwrap();
+    let message = next_event(&mut client).to_editor_message(URI).unwrap();
+    editor.handle_message(message);
+    assert_eq!(editor.current_cursor().position(), Position::new(0, 0));
+
+    // Formatting edits are applied as a single undo step
+    client
+        .formatting(URI, editor.current_buffer(), 4, true)
+        .unwrap();
+    let message = next_event(&mut client).to_editor_message(URI).unwrap();
+    editor.handle_message(message);
+    assert_eq!(editor.current_buffer().text(), "let x = 1;\nx\n");
+
+    editor.handle_message(EditorMessage::Undo);
+    assert_eq!(editor.current_buffer().text(), "let x = 1;   \nx  \n");
+
+    // The popup filters the items by the typed word
+    editor.handle_message(EditorMessage::MoveCursorTo(Position::new(1, 1)));
+    editor.handle_message(event.to_editor_message(URI).unwrap());
+    let labels: Vec<&str> = editor
+        .completion()
+        .unwrap()
+        .visible_items()
+        .map(|item| item.label.as_str())
+        .collect();
+    assert_eq!(labels, vec!["xor"]);
+    editor.handle_message(EditorMessage::AcceptCompletion);
+    assert_eq!(editor.current_buffer().text(), "let x = 1;   \nxor()  \n");
+
+    client.did_close(URI).unwrap();
+    client.shutdown(TIMEOUT).unwrap();
+}
+
+#[test]
+fn test_spawned_server() {
+    let program = env!("CARGO_BIN_EXE_icedit-mock-lsp");
+
+    let mut client = LspClient::spawn(program, &[]).unwrap();
+    client.initialize("file:///tmp", TIMEOUT).unwrap();
+    let editor = Editor::with_text("// TODO\n");
+    client
+        .did_open(URI, "rust", editor.current_buffer())
+        .unwrap();
+    let LspEvent::Diagnostics { diagnostics, .. } = next_event(&mut client) else {
+        panic!("expected diagnostics");
+    };
+    assert_eq!(diagnostics.len(), 1);
+    client.shutdown(TIMEOUT).unwrap();
+
+    // Dropping a client without shutting down kills the server
+    let client = LspClient::spawn(program, &[]).unwrap();
+    let pid = client.process_id().unwrap();
+    drop(client);
+    if cfg!(target_os = "linux") {
+        assert!(!std::path::Path::new(&format!("/proc/{pid}")).exists());
+    }
+}
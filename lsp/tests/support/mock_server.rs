@@ -0,0 +1,162 @@
+//! A tiny language server used by the integration tests
+//!
+//! It keeps its own copy of each document, reports a warning for every `TODO`,
+//! and answers completion, hover, definition and formatting requests with
+//! predictable results.
+
+use icedit_lsp::convert::{char_to_lsp, lsp_to_char, LspPosition};
+use icedit_lsp::transport::{read_message, write_message};
+use ropey::Rope;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufReader, Read, Write};
+
+/// Serve requests from `reader` until the client sends `exit` or disconnects
+pub fn run(reader: impl Read, mut writer: impl Write) -> io::Result<()> {
+    let mut reader = BufReader::new(reader);
+    let mut documents: HashMap<String, Rope> = HashMap::new();
+
+    while let Some(message) = read_message(&mut reader)? {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 2,
+                    "completionProvider": {},
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "documentFormattingProvider": true,
+                },
+            }),
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                documents.insert(uri.clone(), Rope::from_str(text));
+                publish_diagnostics(&mut writer, &uri, &documents[&uri])?;
+                continue;
+            }
+            "textDocument/didChange" => {
+                let rope = documents.entry(uri.clone()).or_default();
+                for change in params["contentChanges"].as_array().into_iter().flatten() {
+                    let text = change["text"].as_str().unwrap_or_default();
+                    match change.get("range") {
+                        Some(range) => {
+                            let start = lsp_to_char(rope, position(&range["start"]));
+                            let end = lsp_to_char(rope, position(&range["end"]));
+                            rope.remove(start..end);
+                            rope.insert(start, text);
+                        }
+                        None => *rope = Rope::from_str(text),
+                    }
+                }
+                publish_diagnostics(&mut writer, &uri, rope)?;
+                continue;
+            }
+            "textDocument/completion" => {
+                let at = position(&params["position"]);
+                json!([
+                    { "label": "println!", "kind": 3, "detail": "macro", "insertText": "println!(\"\")" },
+                    {
+                        "label": "xor",
+                        "kind": 2,
+                        "textEdit": {
+                            "range": {
+                                "start": LspPosition::new(at.line, 0).to_json(),
+                                "end": at.to_json(),
+                            },
+                            "newText": "xor()",
+                        },
+                    },
+                ])
+            }
+            "textDocument/hover" => {
+                let line = position(&params["position"]).line as usize;
+                let text = documents[&uri].line(line).to_string();
+                json!({ "contents": { "kind": "plaintext", "value": text.trim_end() } })
+            }
+            "textDocument/definition" => json!({
+                "uri": uri,
+                "range": {
+                    "start": { "line": 0, "character": 0 },
+                    "end": { "line": 0, "character": 2 },
+                },
+            }),
+            "textDocument/formatting" => trailing_whitespace_edits(&documents[&uri]),
+            "shutdown" => Value::Null,
+            "exit" => return Ok(()),
+            _ => continue,
+        };
+
+        write_message(
+            &mut writer,
+            &json!({ "jsonrpc": "2.0", "id": message["id"], "result": result }),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn position(value: &Value) -> LspPosition {
+    LspPosition::from_json(value).unwrap_or(LspPosition::new(0, 0))
+}
+
+fn publish_diagnostics(writer: &mut impl Write, uri: &str, rope: &Rope) -> io::Result<()> {
+    let text = rope.to_string();
+    let diagnostics: Vec<Value> = text
+        .match_indices("TODO")
+        .map(|(byte, _)| {
+            let start = rope.byte_to_char(byte);
+            json!({
+                "range": {
+                    "start": char_to_lsp(rope, start).to_json(),
+                    "end": char_to_lsp(rope, start + 4).to_json(),
+                },
+                "severity": 2,
+                "source": "mock",
+                "message": "unresolved TODO",
+            })
+        })
+        .collect();
+
+    write_message(
+        writer,
+        &json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        }),
+    )
+}
+
+fn trailing_whitespace_edits(rope: &Rope) -> Value {
+    let edits: Vec<Value> = rope
+        .lines()
+        .enumerate()
+        .filter_map(|(line, slice)| {
+            let text = slice.to_string();
+            let content = text.trim_end_matches(['\n', '\r']);
+            let trimmed = content.trim_end();
+            if trimmed.len() == content.len() {
+                return None;
+            }
+
+            let line_start = rope.line_to_char(line);
+            let start = line_start + trimmed.chars().count();
+            let end = line_start + content.chars().count();
+            Some(json!({
+                "range": {
+                    "start": char_to_lsp(rope, start).to_json(),
+                    "end": char_to_lsp(rope, end).to_json(),
+                },
+                "newText": "",
+            }))
+        })
+        .collect();
+
+    Value::Array(edits)
+}
@@ -0,0 +1,12 @@
+//! The mock language server as a standalone process, talking over stdio
+//!
+//! Only built with the `mock-server` feature, which the integration tests turn on.
+
+#[path = "mock_server.rs"]
+mod mock_server;
+
+use std::io;
+
+fn main() -> io::Result<()> {
+    mock_server::run(io::stdin().lock(), io::stdout().lock())
+}
@@ -0,0 +1,423 @@
+use crate::utils;
+use iced::{
+    advanced::{
+        layout::{self, Layout},
+        renderer::{self, Quad},
+        text::{Alignment, Text},
+        widget::Tree,
+        Clipboard, Shell, Widget,
+    },
+    keyboard, mouse, Color, Element, Event, Font, Length, Point, Rectangle, Size, Theme,
+};
+use icedit_core::{CommandAction, CommandMatch, CommandRegistry, EditorMessage, ShortcutManager};
+
+/// Padding around the query and the entries, in pixels
+const PALETTE_PADDING: f32 = 6.0;
+
+/// Query and selection of a palette, kept while it is shown
+#[derive(Debug, Default)]
+struct PaletteState {
+    query: String,
+    /// Index of the selected entry among the matches
+    selected: usize,
+}
+
+/// A fuzzy-searchable list of commands with their key bindings
+///
+/// Typing filters the commands, Up and Down choose one, and Enter or a click
+/// runs it. Show it above the editor, e.g. in a stack; it takes every key
+/// while shown.
+pub struct CommandPalette<'a, Message> {
+    registry: &'a CommandRegistry<Message>,
+    shortcut_manager: Option<&'a ShortcutManager>,
+    on_message: Box<dyn Fn(EditorMessage) -> Message>,
+    on_close: Option<Message>,
+    font_size: f32,
+    char_width: f32,
+    line_height: f32,
+    background_color: Color,
+    text_color: Color,
+    match_color: Color,
+    selection_color: Color,
+}
+
+impl<'a, Message: Clone> CommandPalette<'a, Message> {
+    const DEFAULT_FONT_SIZE: f32 = 14.0;
+    /// Width of the palette in chars
+    const COLUMNS: usize = 64;
+    /// Most entries shown at once
+    pub const MAX_ROWS: usize = 12;
+
+    pub fn new<F>(registry: &'a CommandRegistry<Message>, on_message: F) -> Self
+    where
+        F: Fn(EditorMessage) -> Message + 'static,
+    {
+        let (char_width, line_height) = utils::calculate_char_dimensions(Self::DEFAULT_FONT_SIZE);
+        Self {
+            registry,
+            shortcut_manager: None,
+            on_message: Box::new(on_message),
+            on_close: None,
+            font_size: Self::DEFAULT_FONT_SIZE,
+            char_width,
+            line_height,
+            background_color: Color::from_rgb(0.2, 0.2, 0.2),
+            text_color: Color::from_rgb(0.9, 0.9, 0.9),
+            match_color: Color::from_rgb(1.0, 0.8, 0.2),
+            selection_color: Color::from_rgba(0.3, 0.5, 1.0, 0.3),
+        }
+    }
+
+    /// Show the keys bound to each editor command
+    pub fn shortcut_manager(mut self, shortcut_manager: &'a ShortcutManager) -> Self {
+        self.shortcut_manager = Some(shortcut_manager);
+        self
+    }
+
+    /// Emit a message when Escape is pressed or after a command runs
+    pub fn on_close(mut self, message: Message) -> Self {
+        self.on_close = Some(message);
+        self
+    }
+
+    pub fn font_size(mut self, size: f32) -> Self {
+        self.font_size = size;
+        let (char_width, line_height) = utils::calculate_char_dimensions(size);
+        self.char_width = char_width;
+        self.line_height = line_height;
+        self
+    }
+
+    /// Set the background, text, matched char and selected entry colors
+    pub fn colors(
+        mut self,
+        background: Color,
+        text: Color,
+        matched: Color,
+        selection: Color,
+    ) -> Self {
+        self.background_color = background;
+        self.text_color = text;
+        self.match_color = matched;
+        self.selection_color = selection;
+        self
+    }
+
+    /// Get the first entry shown, so the selected one stays visible
+    fn first_row(selected: usize) -> usize {
+        (selected + 1).saturating_sub(Self::MAX_ROWS)
+    }
+
+    /// Get the bounds of the `row`-th visible entry
+    fn row_bounds(&self, bounds: Rectangle, row: usize) -> Rectangle {
+        Rectangle::new(
+            Point::new(
+                bounds.x + PALETTE_PADDING,
+                bounds.y + PALETTE_PADDING * 2.0 + (row + 1) as f32 * self.line_height,
+            ),
+            Size::new(bounds.width - PALETTE_PADDING * 2.0, self.line_height),
+        )
+    }
+
+    /// Get the keys running a command, e.g. `Ctrl+K Ctrl+U`
+    fn key_label(&self, action: &CommandAction<Message>) -> Option<String> {
+        let CommandAction::Editor(message) = action else {
+            return None;
+        };
+        let keys = self.shortcut_manager?.keys_for(message);
+        let keys = keys.first()?;
+        let labels: Vec<String> = keys.iter().map(ToString::to_string).collect();
+        Some(labels.join(" "))
+    }
+
+    /// Run a command and close the palette
+    fn run(&self, command_match: &CommandMatch<'_, Message>, shell: &mut Shell<'_, Message>) {
+        match &command_match.command.action {
+            CommandAction::Editor(message) => shell.publish((self.on_message)(message.clone())),
+            CommandAction::App(message) => shell.publish(message.clone()),
+        }
+        if let Some(on_close) = &self.on_close {
+            shell.publish(on_close.clone());
+        }
+    }
+
+    fn text(&self, content: String, bounds: Rectangle) -> Text<String, Font> {
+        Text {
+            content,
+            bounds: bounds.size(),
+            size: iced::Pixels(self.font_size),
+            line_height: iced::advanced::text::LineHeight::Absolute(iced::Pixels(self.line_height)),
+            font: Font::MONOSPACE,
+            align_x: Alignment::Left,
+            align_y: iced::alignment::Vertical::Top,
+            shaping: iced::advanced::text::Shaping::Basic,
+            wrapping: iced::advanced::text::Wrapping::None,
+        }
+    }
+
+    /// Draw a label, coloring the chars the query matched
+    fn draw_label<Renderer>(
+        &self,
+        renderer: &mut Renderer,
+        label: &str,
+        positions: &[usize],
+        bounds: Rectangle,
+    ) where
+        Renderer: iced::advanced::text::Renderer<Font = Font>,
+    {
+        let chars: Vec<char> = label.chars().collect();
+        let mut start = 0;
+        while start < chars.len() {
+            // Draw runs of matched or unmatched chars at their monospace offset
+            let matched = positions.contains(&start);
+            let end = (start..chars.len())
+                .find(|index| positions.contains(index) != matched)
+                .unwrap_or(chars.len());
+            let run: String = chars[start..end].iter().collect();
+            let position = Point::new(bounds.x + start as f32 * self.char_width, bounds.y);
+            let color = if matched {
+                self.match_color
+            } else {
+                self.text_color
+            };
+            renderer.fill_text(self.text(run, bounds), position, color, bounds);
+            start = end;
+        }
+    }
+}
+
+impl<Message, Theme, Renderer> Widget<Message, Theme, Renderer> for CommandPalette<'_, Message>
+where
+    Renderer: iced::advanced::Renderer + iced::advanced::text::Renderer<Font = Font>,
+    Message: Clone,
+{
+    fn tag(&self) -> iced::advanced::widget::tree::Tag {
+        iced::advanced::widget::tree::Tag::of::<PaletteState>()
+    }
+
+    fn state(&self) -> iced::advanced::widget::tree::State {
+        iced::advanced::widget::tree::State::new(PaletteState::default())
+    }
+
+    fn size(&self) -> Size<Length> {
+        Size {
+            width: Length::Shrink,
+            height: Length::Shrink,
+        }
+    }
+
+    fn layout(
+        &self,
+        tree: &mut Tree,
+        _renderer: &Renderer,
+        limits: &layout::Limits,
+    ) -> layout::Node {
+        let state = tree.state.downcast_ref::<PaletteState>();
+        let rows = self.registry.search(&state.query).len().min(Self::MAX_ROWS);
+        let size = Size::new(
+            Self::COLUMNS as f32 * self.char_width + PALETTE_PADDING * 2.0,
+            (rows + 1) as f32 * self.line_height + PALETTE_PADDING * 3.0,
+        );
+        layout::Node::new(limits.resolve(Length::Shrink, Length::Shrink, size))
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        _theme: &Theme,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+    ) {
+        let state = tree.state.downcast_ref::<PaletteState>();
+        let bounds = layout.bounds();
+        let matches = self.registry.search(&state.query);
+
+        renderer.fill_quad(
+            Quad {
+                bounds,
+                border: iced::Border {
+                    color: self.selection_color,
+                    width: 1.0,
+                    radius: 4.0.into(),
+                },
+                shadow: iced::Shadow::default(),
+                snap: false,
+            },
+            self.background_color,
+        );
+
+        let query_bounds = Rectangle::new(
+            Point::new(bounds.x + PALETTE_PADDING, bounds.y + PALETTE_PADDING),
+            Size::new(bounds.width - PALETTE_PADDING * 2.0, self.line_height),
+        );
+        let query = format!("> {}", state.query);
+        renderer.fill_text(
+            self.text(query, query_bounds),
+            query_bounds.position(),
+            self.text_color,
+            query_bounds,
+        );
+
+        let first = Self::first_row(state.selected);
+        for (row, command_match) in matches.iter().skip(first).take(Self::MAX_ROWS).enumerate() {
+            let row_bounds = self.row_bounds(bounds, row);
+            if first + row == state.selected {
+                renderer.fill_quad(
+                    Quad {
+                        bounds: row_bounds,
+                        border: iced::Border::default(),
+                        shadow: iced::Shadow::default(),
+                        snap: false,
+                    },
+                    self.selection_color,
+                );
+            }
+
+            let label = command_match.command.label();
+            self.draw_label(renderer, &label, &command_match.positions, row_bounds);
+
+            if let Some(keys) = self.key_label(&command_match.command.action) {
+                let width = keys.chars().count() as f32 * self.char_width;
+                let position = Point::new(row_bounds.x + row_bounds.width - width, row_bounds.y);
+                renderer.fill_text(
+                    self.text(keys, row_bounds),
+                    position,
+                    Color {
+                        a: self.text_color.a * 0.6,
+                        ..self.text_color
+                    },
+                    row_bounds,
+                );
+            }
+        }
+    }
+
+    fn update(
+        &mut self,
+        tree: &mut Tree,
+        event: &Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _renderer: &Renderer,
+        _clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        _viewport: &Rectangle,
+    ) {
+        let state = tree.state.downcast_mut::<PaletteState>();
+        let bounds = layout.bounds();
+
+        match event {
+            Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }) => {
+                let matches = self.registry.search(&state.query);
+                match key {
+                    keyboard::Key::Named(keyboard::key::Named::Escape) => {
+                        if let Some(on_close) = &self.on_close {
+                            shell.publish(on_close.clone());
+                        }
+                    }
+                    keyboard::Key::Named(keyboard::key::Named::Enter) => {
+                        if let Some(command_match) = matches.get(state.selected) {
+                            self.run(command_match, shell);
+                        }
+                    }
+                    keyboard::Key::Named(keyboard::key::Named::ArrowUp) => {
+                        state.selected = state.selected.saturating_sub(1);
+                    }
+                    keyboard::Key::Named(keyboard::key::Named::ArrowDown) => {
+                        state.selected = (state.selected + 1).min(matches.len().saturating_sub(1));
+                    }
+                    keyboard::Key::Named(keyboard::key::Named::Backspace) => {
+                        state.query.pop();
+                        state.selected = 0;
+                        shell.invalidate_layout();
+                    }
+                    keyboard::Key::Named(keyboard::key::Named::Space) => {
+                        state.query.push(' ');
+                        state.selected = 0;
+                        shell.invalidate_layout();
+                    }
+                    keyboard::Key::Character(text)
+                        if !modifiers.control() && !modifiers.alt() && !modifiers.logo() =>
+                    {
+                        state.query.push_str(text);
+                        state.selected = 0;
+                        shell.invalidate_layout();
+                    }
+                    _ => {}
+                }
+                // Keys typed into the palette must not reach the editor below
+                shell.capture_event();
+                shell.request_redraw();
+            }
+            Event::Mouse(mouse::Event::CursorMoved { .. }) => {
+                let Some(point) = cursor.position_over(bounds) else {
+                    return;
+                };
+                let first = Self::first_row(state.selected);
+                let rows = self.registry.search(&state.query).len().min(Self::MAX_ROWS);
+                if let Some(row) =
+                    (0..rows).find(|&row| self.row_bounds(bounds, row).contains(point))
+                {
+                    if state.selected != first + row {
+                        state.selected = first + row;
+                        shell.request_redraw();
+                    }
+                }
+            }
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                let Some(point) = cursor.position_over(bounds) else {
+                    return;
+                };
+                let matches = self.registry.search(&state.query);
+                let first = Self::first_row(state.selected);
+                let rows = matches.len().saturating_sub(first).min(Self::MAX_ROWS);
+                if let Some(row) =
+                    (0..rows).find(|&row| self.row_bounds(bounds, row).contains(point))
+                {
+                    self.run(&matches[first + row], shell);
+                }
+                shell.capture_event();
+            }
+            _ => {}
+        }
+    }
+
+    fn mouse_interaction(
+        &self,
+        _tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+        _renderer: &Renderer,
+    ) -> mouse::Interaction {
+        if cursor.is_over(layout.bounds()) {
+            mouse::Interaction::Pointer
+        } else {
+            mouse::Interaction::None
+        }
+    }
+}
+
+impl<'a, Message: Clone + 'a> From<CommandPalette<'a, Message>>
+    for Element<'a, Message, Theme, iced::Renderer>
+{
+    fn from(palette: CommandPalette<'a, Message>) -> Self {
+        Element::new(palette)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selected_row_stays_visible() {
+        let max_rows = CommandPalette::<()>::MAX_ROWS;
+        assert_eq!(CommandPalette::<()>::first_row(0), 0);
+        assert_eq!(CommandPalette::<()>::first_row(max_rows - 1), 0);
+        assert_eq!(CommandPalette::<()>::first_row(max_rows + 2), 3);
+    }
+}
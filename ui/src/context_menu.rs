@@ -0,0 +1,242 @@
+use iced::{
+    advanced::{
+        renderer::Quad,
+        text::{Alignment, Text},
+    },
+    Color, Font, Point, Rectangle, Size,
+};
+use icedit_core::EditorMessage;
+
+/// Padding around the entries of a context menu, in pixels
+const MENU_PADDING: f32 = 4.0;
+
+/// What choosing a context menu entry does
+#[derive(Debug, Clone)]
+pub enum ContextMenuAction<Message> {
+    /// Send a message to the editor through the widget's `on_message`
+    Editor(EditorMessage),
+    /// Publish an app message as is
+    App(Message),
+}
+
+/// An entry of a context menu
+#[derive(Debug, Clone)]
+pub struct ContextMenuItem<Message> {
+    pub label: String,
+    pub action: ContextMenuAction<Message>,
+}
+
+/// Entries shown when right-clicking the editor
+#[derive(Debug, Clone)]
+pub struct ContextMenu<Message> {
+    items: Vec<ContextMenuItem<Message>>,
+}
+
+impl<Message> ContextMenu<Message> {
+    /// Create a menu without entries; an empty menu is never shown
+    pub fn new() -> Self {
+        Self { items: Vec::new() }
+    }
+
+    /// Create the menu with the standard Cut, Copy, Paste and Select All entries
+    pub fn standard() -> Self {
+        Self::new()
+            .item("Cut", EditorMessage::Cut)
+            .item("Copy", EditorMessage::Copy)
+            .item("Paste", EditorMessage::Paste)
+            .item("Select All", EditorMessage::SelectAll)
+    }
+
+    /// Add an entry sending a message to the editor
+    pub fn item(mut self, label: impl Into<String>, message: EditorMessage) -> Self {
+        self.items.push(ContextMenuItem {
+            label: label.into(),
+            action: ContextMenuAction::Editor(message),
+        });
+        self
+    }
+
+    /// Add an entry publishing an app message
+    pub fn app_item(mut self, label: impl Into<String>, message: Message) -> Self {
+        self.items.push(ContextMenuItem {
+            label: label.into(),
+            action: ContextMenuAction::App(message),
+        });
+        self
+    }
+
+    pub fn items(&self) -> &[ContextMenuItem<Message>] {
+        &self.items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Lay the menu out at a point, keeping it within `bounds`
+    pub fn layout(
+        &self,
+        origin: Point,
+        bounds: Rectangle,
+        char_width: f32,
+        line_height: f32,
+    ) -> ContextMenuLayout {
+        let longest_label = self
+            .items
+            .iter()
+            .map(|item| item.label.chars().count())
+            .max()
+            .unwrap_or(0);
+        // Leave room for a couple of spaces on either side of the label
+        let size = Size::new(
+            (longest_label + 4) as f32 * char_width + MENU_PADDING * 2.0,
+            self.items.len() as f32 * line_height + MENU_PADDING * 2.0,
+        );
+
+        // Open towards the top or left when there is no room below or to the right
+        let x = if origin.x + size.width > bounds.x + bounds.width {
+            origin.x - size.width
+        } else {
+            origin.x
+        };
+        let y = if origin.y + size.height > bounds.y + bounds.height {
+            origin.y - size.height
+        } else {
+            origin.y
+        };
+
+        ContextMenuLayout {
+            bounds: Rectangle::new(Point::new(x.max(bounds.x), y.max(bounds.y)), size),
+            row_height: line_height,
+            item_count: self.items.len(),
+        }
+    }
+}
+
+impl<Message> Default for ContextMenu<Message> {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+/// Geometry of an open context menu
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContextMenuLayout {
+    /// Bounds of the whole menu
+    pub bounds: Rectangle,
+    pub row_height: f32,
+    pub item_count: usize,
+}
+
+impl ContextMenuLayout {
+    /// Get the bounds of an entry
+    pub fn row_bounds(&self, index: usize) -> Rectangle {
+        Rectangle::new(
+            Point::new(
+                self.bounds.x + MENU_PADDING,
+                self.bounds.y + MENU_PADDING + index as f32 * self.row_height,
+            ),
+            Size::new(self.bounds.width - MENU_PADDING * 2.0, self.row_height),
+        )
+    }
+
+    /// Find the entry under a point
+    pub fn item_at(&self, point: Point) -> Option<usize> {
+        (0..self.item_count).find(|&index| self.row_bounds(index).contains(point))
+    }
+}
+
+/// Colors used to draw a context menu
+#[derive(Debug, Clone, Copy)]
+pub struct ContextMenuStyle {
+    pub background: Color,
+    pub border: Color,
+    pub text: Color,
+    pub hovered: Color,
+    pub font_size: f32,
+}
+
+impl<Message> ContextMenu<Message> {
+    /// Draw the menu on top of everything else, highlighting the hovered entry
+    pub fn draw<Renderer>(
+        &self,
+        renderer: &mut Renderer,
+        layout: &ContextMenuLayout,
+        hovered: Option<usize>,
+        style: ContextMenuStyle,
+    ) where
+        Renderer: iced::advanced::Renderer + iced::advanced::text::Renderer<Font = Font>,
+    {
+        renderer.with_layer(layout.bounds, |renderer| {
+            renderer.fill_quad(
+                Quad {
+                    bounds: layout.bounds,
+                    border: iced::Border {
+                        color: style.border,
+                        width: 1.0,
+                        radius: 3.0.into(),
+                    },
+                    shadow: iced::Shadow::default(),
+                    snap: false,
+                },
+                style.background,
+            );
+
+            for (index, item) in self.items.iter().enumerate() {
+                let row_bounds = layout.row_bounds(index);
+                if hovered == Some(index) {
+                    renderer.fill_quad(
+                        Quad {
+                            bounds: row_bounds,
+                            border: iced::Border::default(),
+                            shadow: iced::Shadow::default(),
+                            snap: false,
+                        },
+                        style.hovered,
+                    );
+                }
+
+                renderer.fill_text(
+                    Text {
+                        content: format!("  {}", item.label),
+                        bounds: row_bounds.size(),
+                        size: iced::Pixels(style.font_size),
+                        line_height: iced::advanced::text::LineHeight::Absolute(iced::Pixels(
+                            layout.row_height,
+                        )),
+                        font: Font::MONOSPACE,
+                        align_x: Alignment::Left,
+                        align_y: iced::alignment::Vertical::Top,
+                        shaping: iced::advanced::text::Shaping::Basic,
+                        wrapping: iced::advanced::text::Wrapping::None,
+                    },
+                    row_bounds.position(),
+                    style.text,
+                    row_bounds,
+                );
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_stays_in_bounds() {
+        let menu: ContextMenu<()> = ContextMenu::standard().app_item("Rename Symbol", ());
+        let bounds = Rectangle::new(Point::ORIGIN, Size::new(400.0, 300.0));
+
+        let layout = menu.layout(Point::new(10.0, 20.0), bounds, 8.0, 20.0);
+        assert_eq!(layout.bounds.position(), Point::new(10.0, 20.0));
+        assert_eq!(layout.bounds.size(), Size::new(144.0, 108.0));
+        assert_eq!(layout.item_at(Point::new(20.0, 30.0)), Some(0));
+        assert_eq!(layout.item_at(Point::new(20.0, 110.0)), Some(4));
+        assert_eq!(layout.item_at(Point::new(200.0, 30.0)), None);
+
+        // Near the bottom right corner the menu opens up and to the left
+        let layout = menu.layout(Point::new(390.0, 290.0), bounds, 8.0, 20.0);
+        assert_eq!(layout.bounds.position(), Point::new(246.0, 182.0));
+    }
+}
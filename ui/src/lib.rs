@@ -1,3 +1,6 @@
+pub mod command_palette;
+pub mod context_menu;
+pub mod minimap;
 pub mod renderer;
 pub mod utils;
 pub mod viewport;
@@ -7,6 +10,9 @@ pub mod widget;
 pub use icedit_core::*;
 
 // Export UI-specific types
+pub use command_palette::*;
+pub use context_menu::*;
+pub use minimap::*;
 pub use renderer::*;
 pub use utils::*;
 pub use viewport::*;
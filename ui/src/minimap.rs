@@ -0,0 +1,338 @@
+use crate::Viewport;
+use iced::{advanced::renderer::Quad, Color, Point, Rectangle, Size};
+use icedit_core::{Editor, Rope, TextChange};
+use std::collections::BTreeMap;
+
+/// Height of a single buffer line in the minimap, in pixels
+pub const MINIMAP_LINE_HEIGHT: f32 = 2.0;
+
+/// Width of a single character in the minimap, in pixels
+pub const MINIMAP_CHAR_WIDTH: f32 = 1.0;
+
+/// Horizontal padding inside the minimap column
+const MINIMAP_PADDING: f32 = 4.0;
+
+/// A run of non-whitespace characters on a minimap line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinimapSegment {
+    /// Visual column where the run starts (tabs expanded)
+    pub start: usize,
+    /// Visual length of the run
+    pub len: usize,
+}
+
+/// Summarize a line into runs of non-whitespace characters
+///
+/// Tabs are expanded to `tab_width` columns and runs are cut off at `max_columns`,
+/// since anything past the minimap width is never drawn.
+pub fn summarize_line(line: &str, tab_width: usize, max_columns: usize) -> Vec<MinimapSegment> {
+    let mut segments = Vec::new();
+    let mut column = 0;
+    let mut run_start: Option<usize> = None;
+
+    for ch in line.chars() {
+        if column >= max_columns {
+            break;
+        }
+
+        if ch.is_whitespace() {
+            if let Some(start) = run_start.take() {
+                segments.push(MinimapSegment {
+                    start,
+                    len: column - start,
+                });
+            }
+
+            if ch == '\t' {
+                column = (column / tab_width + 1) * tab_width;
+            } else if ch != '\n' && ch != '\r' {
+                column += 1;
+            }
+        } else {
+            run_start.get_or_insert(column);
+            column += 1;
+        }
+    }
+
+    if let Some(start) = run_start {
+        segments.push(MinimapSegment {
+            start,
+            len: column.min(max_columns) - start,
+        });
+    }
+
+    segments
+}
+
+/// Cached per-line summaries used to draw the minimap
+///
+/// Summaries are computed lazily for the lines the minimap actually shows. When the
+/// buffer changes, only the edited lines are dropped and the ones below are shifted,
+/// so large files never get re-scanned as a whole.
+#[derive(Debug, Default)]
+pub struct MinimapCache {
+    revision: Option<u64>,
+    // Text at `revision`, to map the char ranges of later changes to lines
+    rope: Rope,
+    max_columns: usize,
+    lines: BTreeMap<usize, Vec<MinimapSegment>>,
+}
+
+impl MinimapCache {
+    /// Drop the summaries of lines changed since the last sync
+    fn sync(&mut self, editor: &Editor, max_columns: usize) {
+        let buffer = editor.current_buffer();
+        if self.max_columns != max_columns {
+            self.lines.clear();
+            self.max_columns = max_columns;
+        }
+
+        match self.revision {
+            Some(revision) if revision == buffer.revision() => return,
+            Some(revision) => match buffer.changes_since(revision) {
+                Some(changes) => {
+                    for change in changes {
+                        self.apply_change(change);
+                    }
+                }
+                None => self.lines.clear(),
+            },
+            None => self.lines.clear(),
+        }
+
+        self.revision = Some(buffer.revision());
+        self.rope = buffer.rope().clone();
+    }
+
+    fn apply_change(&mut self, change: &TextChange) {
+        let start_line = self.rope.char_to_line(change.old_range.start);
+        let old_end_line = self.rope.char_to_line(change.old_range.end);
+        self.rope.remove(change.old_range.clone());
+        self.rope.insert(change.old_range.start, &change.text);
+        let new_end_line = self.rope.char_to_line(change.new_range.end);
+
+        let below = self.lines.split_off(&(old_end_line + 1));
+        self.lines.split_off(&start_line);
+        self.lines.extend(
+            below
+                .into_iter()
+                .map(|(line, segments)| (line + new_end_line - old_end_line, segments)),
+        );
+    }
+
+    /// Get the summary for a line, computing it if needed
+    fn line(&mut self, editor: &Editor, line: usize) -> &[MinimapSegment] {
+        let max_columns = self.max_columns;
+        self.lines.entry(line).or_insert_with(|| {
+            editor
+                .current_buffer()
+                .rope()
+                .get_line(line)
+                .map(|text| summarize_line(&text.to_string(), 4, max_columns))
+                .unwrap_or_default()
+        })
+    }
+}
+
+/// Geometry of the minimap column for the current scroll position
+///
+/// When the whole document does not fit, the minimap scrolls proportionally
+/// to the editor so the visible region always stays inside the column.
+#[derive(Debug, Clone, Copy)]
+pub struct MinimapLayout {
+    /// Bounds of the minimap column
+    pub bounds: Rectangle,
+    /// How far the minimap content is scrolled, in minimap pixels
+    pub scroll: f32,
+    /// Number of lines in the buffer
+    pub line_count: usize,
+}
+
+impl MinimapLayout {
+    pub fn new(bounds: Rectangle, line_count: usize, viewport: &Viewport) -> Self {
+        let minimap_height = line_count as f32 * MINIMAP_LINE_HEIGHT;
+        let content_height = line_count as f32 * viewport.line_height;
+        let max_scroll = (content_height - viewport.size.1).max(0.0);
+        let scroll_ratio = if max_scroll > 0.0 {
+            (viewport.scroll_offset.1 / max_scroll).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        Self {
+            bounds,
+            scroll: (minimap_height - bounds.height).max(0.0) * scroll_ratio,
+            line_count,
+        }
+    }
+
+    /// Get the buffer line under a screen Y coordinate
+    pub fn line_at(&self, y: f32) -> usize {
+        let line = ((y - self.bounds.y + self.scroll) / MINIMAP_LINE_HEIGHT).max(0.0) as usize;
+        line.min(self.line_count.saturating_sub(1))
+    }
+
+    /// Get the rectangle showing the part of the buffer visible in the editor
+    pub fn viewport_bounds(&self, viewport: &Viewport) -> Rectangle {
+        let scale = MINIMAP_LINE_HEIGHT / viewport.line_height;
+        let top = viewport.scroll_offset.1 * scale - self.scroll;
+        let height = (viewport.size.1 * scale).min(self.bounds.height);
+
+        Rectangle::new(
+            Point::new(self.bounds.x, self.bounds.y + top),
+            Size::new(self.bounds.width, height),
+        )
+    }
+}
+
+/// Draws a scaled-down overview of the whole buffer
+pub struct Minimap {
+    background_color: Color,
+    text_color: Color,
+    viewport_color: Color,
+}
+
+impl Minimap {
+    /// Default width of the minimap column in pixels
+    pub const DEFAULT_WIDTH: f32 = 80.0;
+
+    /// Create a minimap matching the editor colors
+    ///
+    /// The editor has no syntax highlighting yet, so text is drawn in a dimmed text color.
+    pub fn new(background_color: Color, text_color: Color) -> Self {
+        Self {
+            background_color,
+            text_color: Color {
+                a: text_color.a * 0.5,
+                ..text_color
+            },
+            viewport_color: Color {
+                a: 0.15,
+                ..text_color
+            },
+        }
+    }
+
+    /// Draw the minimap into its column
+    pub fn draw<Renderer>(
+        &self,
+        renderer: &mut Renderer,
+        layout: &MinimapLayout,
+        editor: &Editor,
+        viewport: &Viewport,
+        cache: &mut MinimapCache,
+    ) where
+        Renderer: iced::advanced::Renderer,
+    {
+        let bounds = layout.bounds;
+        renderer.fill_quad(Self::quad(bounds), self.background_color);
+
+        let max_columns = ((bounds.width - MINIMAP_PADDING * 2.0) / MINIMAP_CHAR_WIDTH).max(0.0);
+        cache.sync(editor, max_columns as usize);
+
+        let first_line = (layout.scroll / MINIMAP_LINE_HEIGHT).floor() as usize;
+        let visible_lines = (bounds.height / MINIMAP_LINE_HEIGHT).ceil() as usize + 1;
+        let last_line = (first_line + visible_lines).min(layout.line_count);
+
+        for line in first_line..last_line {
+            let y = bounds.y + line as f32 * MINIMAP_LINE_HEIGHT - layout.scroll;
+            if y + MINIMAP_LINE_HEIGHT > bounds.y + bounds.height {
+                break;
+            }
+
+            for segment in cache.line(editor, line) {
+                let segment_bounds = Rectangle::new(
+                    Point::new(
+                        bounds.x + MINIMAP_PADDING + segment.start as f32 * MINIMAP_CHAR_WIDTH,
+                        y,
+                    ),
+                    // Leave a small gap between lines so text rows stay distinguishable
+                    Size::new(
+                        segment.len as f32 * MINIMAP_CHAR_WIDTH,
+                        MINIMAP_LINE_HEIGHT * 0.75,
+                    ),
+                );
+                renderer.fill_quad(Self::quad(segment_bounds), self.text_color);
+            }
+        }
+
+        // Visible region on top of the text
+        let viewport_bounds = layout.viewport_bounds(viewport);
+        if let Some(visible) = viewport_bounds.intersection(&bounds) {
+            renderer.fill_quad(Self::quad(visible), self.viewport_color);
+        }
+    }
+
+    fn quad(bounds: Rectangle) -> Quad {
+        Quad {
+            bounds,
+            border: iced::Border::default(),
+            shadow: iced::Shadow::default(),
+            snap: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use icedit_core::{EditorMessage, Position};
+
+    #[test]
+    fn test_summarize_line_runs() {
+        let segments = summarize_line("let x = 1;\n", 4, 100);
+        assert_eq!(
+            segments,
+            vec![
+                MinimapSegment { start: 0, len: 3 },
+                MinimapSegment { start: 4, len: 1 },
+                MinimapSegment { start: 6, len: 1 },
+                MinimapSegment { start: 8, len: 2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_summarize_line_tabs_and_clipping() {
+        // Tab expands to the next tab stop
+        let segments = summarize_line("\tfoo", 4, 100);
+        assert_eq!(segments, vec![MinimapSegment { start: 4, len: 3 }]);
+
+        // Runs are cut at the minimap width
+        let segments = summarize_line("abcdefgh", 4, 5);
+        assert_eq!(segments, vec![MinimapSegment { start: 0, len: 5 }]);
+
+        assert!(summarize_line("   \n", 4, 100).is_empty());
+    }
+
+    #[test]
+    fn test_cache_keeps_unchanged_lines() {
+        let mut editor = Editor::with_text("one\ntwo\nthree\nfour");
+        let mut cache = MinimapCache::default();
+        cache.sync(&editor, 100);
+        for line in 0..4 {
+            cache.line(&editor, line);
+        }
+
+        editor.handle_message(EditorMessage::MoveCursorTo(Position::new(1, 3)));
+        editor.handle_message(EditorMessage::InsertText("\nnew".to_string()));
+        cache.sync(&editor, 100);
+
+        // The edited line is dropped and the lines below move down
+        assert_eq!(
+            cache.lines.keys().copied().collect::<Vec<_>>(),
+            vec![0, 3, 4]
+        );
+        assert_eq!(cache.line(&editor, 2), summarize_line("new\n", 4, 100));
+        assert_eq!(cache.line(&editor, 4), summarize_line("four", 4, 100));
+
+        editor.handle_message(EditorMessage::SetSelection(
+            Position::new(0, 1),
+            Position::new(2, 1),
+        ));
+        editor.handle_message(EditorMessage::DeleteSelection);
+        cache.sync(&editor, 100);
+        assert_eq!(cache.lines.keys().copied().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(cache.line(&editor, 2), summarize_line("four", 4, 100));
+    }
+}
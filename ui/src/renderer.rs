@@ -6,8 +6,12 @@ use iced::{
     },
     Color, Font, Point, Rectangle, Size,
 };
-use icedit_core::{Editor, Position, Selection};
-use std::collections::VecDeque;
+use icedit_core::{
+    CompletionKind, CompletionSession, CursorStyle, DecorationLayer, DecorationShape, Diagnostic,
+    DiagnosticSeverity, Editor, InlineSuggestion, Position, Selection, Tooltip, TooltipBlock,
+    VirtualTextPlacement,
+};
+use std::collections::{HashMap, VecDeque};
 
 /// Information about visible columns in a line for horizontal scrolling optimization
 #[derive(Debug, Clone, Copy)]
@@ -57,7 +61,79 @@ impl Default for ScrollbarInfo {
     }
 }
 
+/// What an overview ruler marker points at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverviewMarkerKind {
+    /// The line containing the cursor
+    Cursor,
+    /// A search match
+    SearchMatch,
+    /// The lines covered by the selection
+    Selection,
+    /// An app-provided annotation (errors, warnings, bookmarks...) drawn in its own color
+    Annotation(Color),
+}
+
+/// A marker drawn on the vertical scrollbar track
+///
+/// Markers are placed proportionally to their line in the buffer, so they stay
+/// visible regardless of the scroll position. Clicking a marker jumps to `position`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OverviewMarker {
+    pub kind: OverviewMarkerKind,
+    /// Position to jump to when the marker is clicked
+    pub position: Position,
+    /// Last line covered by the marker (inclusive)
+    pub end_line: usize,
+}
+
+impl OverviewMarker {
+    pub fn new(kind: OverviewMarkerKind, position: Position) -> Self {
+        Self {
+            kind,
+            position,
+            end_line: position.line,
+        }
+    }
+
+    /// Create an annotation marker with a custom color
+    pub fn annotation(position: Position, color: Color) -> Self {
+        Self::new(OverviewMarkerKind::Annotation(color), position)
+    }
+
+    /// Extend the marker down to `end_line`
+    pub fn spanning(mut self, end_line: usize) -> Self {
+        self.end_line = end_line.max(self.position.line);
+        self
+    }
+}
+
+/// Styling inputs the editor renderer is built from
+///
+/// The widget compares these with its persisted renderer on every frame and only
+/// builds a new renderer, dropping its caches, when they differ.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RendererStyle {
+    pub font_size: f32,
+    pub line_height: f32,
+    pub char_width: f32,
+    pub background_color: Color,
+    pub text_color: Color,
+    pub cursor_color: Color,
+    pub selection_color: Color,
+    pub gutter_width: f32,
+    pub gutter_background_color: Color,
+    pub line_number_color: Color,
+    pub current_line_number_color: Color,
+    pub gutter_padding: f32,
+    pub minimap_width: f32,
+}
+
 /// Extremely optimized renderer for the editor widget using advanced techniques
+///
+/// The widget keeps its renderer in the widget tree, so the caches below survive
+/// across frames. They are invalidated by the buffer revision from icedit-core.
+#[derive(Debug)]
 pub struct EditorRenderer {
     // Core rendering properties
     font_size: f32,
@@ -81,38 +157,81 @@ pub struct EditorRenderer {
     scrollbar_thumb_color: Color,
     min_scrollbar_thumb_size: f32,
 
+    // Width reserved on the right for the minimap column
+    minimap_width: f32,
+
+    // Overview ruler marker colors
+    cursor_marker_color: Color,
+    search_marker_color: Color,
+    selection_marker_color: Color,
+
+    // Diagnostic severity colors
+    error_color: Color,
+    warning_color: Color,
+    info_color: Color,
+    hint_color: Color,
+
+    /// Color of virtual text such as inlay hints, a dimmed text color
+    virtual_text_color: Color,
+
     // Optimization caches and pools
     text_operation_pool: VecDeque<TextOperation>,
+    selection_quad_pool: Vec<Quad>,
+    decorations_below_text: Vec<(Quad, Color)>,
+    decorations_above_text: Vec<(Quad, Color)>,
+    /// Most severe diagnostic starting on each visible line, for gutter icons
+    gutter_diagnostics: HashMap<usize, DiagnosticSeverity>,
+    /// Inline virtual text of each visible line as `(column, width)` pairs
+    inline_virtual_text: HashMap<usize, Vec<(usize, f32)>>,
     last_viewport: Option<Viewport>,
-    last_cursor_position: Option<Position>,
     last_selection: Option<Selection>,
+    last_bounds: Option<Rectangle>,
+    last_editor_bounds: Rectangle,
+    last_revision: Option<u64>,
+    /// Decoration, diagnostic and virtual text generations the prepared operations were built from
+    last_decorations_generation: Option<(u64, u64, u64)>,
 
     // Scrollbar state caching
     last_vertical_scrollbar: Option<ScrollbarInfo>,
     last_horizontal_scrollbar: Option<ScrollbarInfo>,
     last_content_dimensions: Option<(f32, f32)>, // (width, height)
 
+    /// How the cursor is drawn, set by the widget on each frame
+    cursor_style: CursorStyle,
+
     // Pre-computed constants for hot paths
     cursor_width: f32,
     tab_width: f32,
 
     // Frame-based caching
     frame_counter: u64,
-    last_render_frame: u64,
 
     // Content width caching for optimization
-    cached_max_line_width: Option<f32>,
-    cached_max_line_index: Option<usize>,
     content_width_dirty: bool,
 }
 
+#[derive(Debug)]
 struct TextOperation {
     content: String,
     position: Point,
     bounds: Rectangle,
+    color: Color,
 }
 
 impl EditorRenderer {
+    /// Width of both scrollbars in pixels
+    pub const SCROLLBAR_WIDTH: f32 = 12.0;
+
+    /// Minimum height of an overview ruler marker in pixels
+    const MIN_MARKER_HEIGHT: f32 = 3.0;
+
+    /// How often (in frames) pools are trimmed back to their usual size
+    const POOL_CLEANUP_INTERVAL: u64 = 600;
+    /// Most items shown at once in the completion popup
+    pub const MAX_COMPLETION_ROWS: usize = 10;
+    /// Width in chars at which tooltip prose is wrapped
+    const TOOLTIP_WRAP_COLUMNS: usize = 72;
+
     pub fn new(
         font_size: f32,
         line_height: f32,
@@ -144,86 +263,187 @@ impl EditorRenderer {
             gutter_padding,
 
             // Scrollbar styling
-            scrollbar_width: 12.0,
+            scrollbar_width: Self::SCROLLBAR_WIDTH,
             scrollbar_track_color: Color::from_rgba(0.5, 0.5, 0.5, 0.2),
             scrollbar_thumb_color: Color::from_rgba(0.6, 0.6, 0.6, 0.8),
             min_scrollbar_thumb_size: 16.0,
 
+            minimap_width: 0.0,
+
+            // Overview ruler markers
+            cursor_marker_color: Color::from_rgba(0.9, 0.9, 0.9, 0.9),
+            search_marker_color: Color::from_rgba(1.0, 0.65, 0.0, 0.9),
+            selection_marker_color: Color::from_rgba(0.3, 0.5, 1.0, 0.7),
+
+            // Diagnostics
+            error_color: Color::from_rgb(0.95, 0.3, 0.3),
+            warning_color: Color::from_rgb(0.95, 0.75, 0.2),
+            info_color: Color::from_rgb(0.35, 0.6, 1.0),
+            hint_color: Color::from_rgba(0.6, 0.6, 0.6, 0.8),
+
+            virtual_text_color: Color {
+                a: text_color.a * 0.5,
+                ..text_color
+            },
+
             // Initialize pools with reasonable capacity
             text_operation_pool: VecDeque::with_capacity(64),
+            selection_quad_pool: Vec::with_capacity(16),
+            decorations_below_text: Vec::new(),
+            decorations_above_text: Vec::new(),
+            gutter_diagnostics: HashMap::new(),
+            inline_virtual_text: HashMap::new(),
             last_viewport: None,
-            last_cursor_position: None,
             last_selection: None,
+            last_bounds: None,
+            last_editor_bounds: Rectangle::default(),
+            last_revision: None,
+            last_decorations_generation: None,
 
             // Scrollbar cache
             last_vertical_scrollbar: None,
             last_horizontal_scrollbar: None,
             last_content_dimensions: None,
 
+            cursor_style: CursorStyle::Line,
+
             // Pre-compute constants
             cursor_width: 2.0,
             tab_width: char_width * 4.0,
 
             frame_counter: 0,
-            last_render_frame: 0,
 
             // Content width caching for optimization
-            cached_max_line_width: None,
-            cached_max_line_index: None,
             content_width_dirty: true,
         }
     }
 
+    /// Reserve space on the right for the minimap column (0.0 disables it)
+    pub fn set_minimap_width(&mut self, width: f32) {
+        self.minimap_width = width;
+    }
+
+    /// Set how the cursor is drawn; this does not invalidate any cache
+    pub fn set_cursor_style(&mut self, style: CursorStyle) {
+        self.cursor_style = style;
+    }
+
+    /// Create a renderer configured with the given styling
+    pub fn with_style(style: RendererStyle) -> Self {
+        let mut renderer = Self::new(
+            style.font_size,
+            style.line_height,
+            style.char_width,
+            style.background_color,
+            style.text_color,
+            style.cursor_color,
+            style.selection_color,
+            style.gutter_width,
+            style.gutter_background_color,
+            style.line_number_color,
+            style.current_line_number_color,
+            style.gutter_padding,
+        );
+        renderer.set_minimap_width(style.minimap_width);
+        renderer
+    }
+
+    /// Check whether the renderer was configured with the given styling
+    ///
+    /// The widget uses this to decide whether its persisted renderer (and caches)
+    /// can be reused for the current frame.
+    pub fn has_style(&self, style: &RendererStyle) -> bool {
+        self.font_size == style.font_size
+            && self.line_height == style.line_height
+            && self.char_width == style.char_width
+            && self.background_color == style.background_color
+            && self.text_color == style.text_color
+            && self.cursor_color == style.cursor_color
+            && self.selection_color == style.selection_color
+            && self.gutter_width == style.gutter_width
+            && self.gutter_background_color == style.gutter_background_color
+            && self.line_number_color == style.line_number_color
+            && self.current_line_number_color == style.current_line_number_color
+            && self.gutter_padding == style.gutter_padding
+            && self.minimap_width == style.minimap_width
+    }
+
     /// Ultra-optimized render method with dirty region tracking and object pooling
+    ///
+    /// Everything is drawn on every frame, but when neither the text, the viewport
+    /// nor the selection changed, the prepared text and selection operations from the
+    /// previous frame are reused instead of re-reading the buffer.
     pub fn render<Renderer>(
         &mut self,
         editor: &Editor,
         viewport: &Viewport,
         renderer: &mut Renderer,
         bounds: Rectangle,
+        overview_markers: &[OverviewMarker],
     ) where
         Renderer: iced::advanced::Renderer + iced::advanced::text::Renderer<Font = Font>,
     {
         self.frame_counter += 1;
 
-        // Fast path: check if anything actually changed
-        let selection = editor.current_selection();
-        let cursor_position = editor.current_cursor().position();
+        // Any change to the text invalidates everything derived from it
+        let revision = editor.current_buffer().revision();
+        if self.last_revision != Some(revision) {
+            self.invalidate_content_cache();
+            self.last_revision = Some(revision);
+        }
 
-        let needs_full_render = self.check_full_render_needed(viewport, selection);
+        // Decorations, diagnostics and virtual text can change without the text changing
+        let buffer = editor.current_buffer();
+        let decorations_generation = (
+            buffer.decorations_generation(),
+            buffer.diagnostics_generation(),
+            buffer.virtual_text_generation(),
+        );
+        let decorations_changed = self.last_decorations_generation != Some(decorations_generation);
 
-        if !needs_full_render && self.last_render_frame == self.frame_counter - 1 {
-            // Only render cursor if it changed position
-            if self.last_cursor_position.as_ref() != Some(&cursor_position) {
-                self.draw_cursor(renderer, bounds, cursor_position, viewport, editor);
-                self.last_cursor_position = Some(cursor_position);
-            }
-            return;
+        let selection = editor.current_selection();
+        if decorations_changed || self.check_full_render_needed(viewport, selection, bounds) {
+            self.prepare_frame(editor, viewport, bounds);
+
+            // Update cache state
+            self.last_viewport = Some(viewport.clone());
+            self.last_selection = selection.cloned();
+            self.last_bounds = Some(bounds);
+            self.last_decorations_generation = Some(decorations_generation);
         }
 
-        // Full render path with extreme optimizations
-        self.render_optimized(
-            editor,
-            viewport,
-            renderer,
-            bounds,
-            selection,
-            cursor_position,
-        );
+        self.draw_frame(editor, viewport, renderer, bounds, overview_markers);
 
-        // Update cache state
-        self.last_viewport = Some(viewport.clone());
-        self.last_selection = selection.cloned();
-        self.last_cursor_position = Some(cursor_position);
-        self.last_render_frame = self.frame_counter;
+        if self
+            .frame_counter
+            .is_multiple_of(Self::POOL_CLEANUP_INTERVAL)
+        {
+            self.cleanup_pools();
+        }
     }
 
     #[inline]
-    fn check_full_render_needed(&self, viewport: &Viewport, selection: Option<&Selection>) -> bool {
+    fn check_full_render_needed(
+        &self,
+        viewport: &Viewport,
+        selection: Option<&Selection>,
+        bounds: Rectangle,
+    ) -> bool {
+        // Check if the text changed since the operations were prepared
+        if self.content_width_dirty {
+            return true;
+        }
+
+        // Check if the widget moved or was resized
+        if self.last_bounds != Some(bounds) {
+            return true;
+        }
+
         // Check if viewport changed
         if let Some(last_viewport) = &self.last_viewport {
             if last_viewport.scroll_offset != viewport.scroll_offset
                 || last_viewport.size != viewport.size
+                || last_viewport.virtual_lines != viewport.virtual_lines
             {
                 return true;
             }
@@ -241,19 +461,10 @@ impl EditorRenderer {
         false
     }
 
-    fn render_optimized<Renderer>(
-        &mut self,
-        editor: &Editor,
-        viewport: &Viewport,
-        renderer: &mut Renderer,
-        bounds: Rectangle,
-        selection: Option<&Selection>,
-        cursor_position: Position,
-    ) where
-        Renderer: iced::advanced::Renderer + iced::advanced::text::Renderer<Font = Font>,
-    {
+    /// Rebuild the cached scrollbars and text/selection operations for the current frame
+    fn prepare_frame(&mut self, editor: &Editor, viewport: &Viewport, bounds: Rectangle) {
         // Calculate content dimensions for scrollbar visibility
-        let content_dimensions = self.calculate_content_dimensions(editor);
+        let content_dimensions = self.calculate_content_dimensions(editor, viewport);
 
         // Calculate scrollbar info (lazy - only if needed)
         let (vertical_scrollbar, horizontal_scrollbar) =
@@ -266,61 +477,91 @@ impl EditorRenderer {
             horizontal_scrollbar.visible,
         );
 
+        // Get visible lines from buffer directly
+        let visible_lines = self.get_visible_lines_with_partial(editor, viewport);
+        self.inline_virtual_text.clear();
+        for (_, partial_line) in &visible_lines {
+            let line = partial_line.line_index;
+            let virtual_text = utils::inline_virtual_text_widths(editor, line, self.char_width);
+            if !virtual_text.is_empty() {
+                self.inline_virtual_text.insert(line, virtual_text);
+            }
+        }
+
+        // Batch all operations into the pools
+        self.prepare_render_operations(
+            &visible_lines,
+            editor_bounds,
+            viewport,
+            editor.current_selection(),
+        );
+        self.prepare_virtual_text(editor, &visible_lines, editor_bounds, viewport);
+        self.prepare_decorations(editor, &visible_lines, editor_bounds, viewport);
+
+        // Update scrollbar cache
+        self.last_vertical_scrollbar = Some(vertical_scrollbar);
+        self.last_horizontal_scrollbar = Some(horizontal_scrollbar);
+        self.last_content_dimensions = Some(content_dimensions);
+        self.last_editor_bounds = editor_bounds;
+    }
+
+    /// Draw a frame from the prepared operations
+    fn draw_frame<Renderer>(
+        &self,
+        editor: &Editor,
+        viewport: &Viewport,
+        renderer: &mut Renderer,
+        bounds: Rectangle,
+        overview_markers: &[OverviewMarker],
+    ) where
+        Renderer: iced::advanced::Renderer + iced::advanced::text::Renderer<Font = Font>,
+    {
+        let cursor_position = editor.current_cursor().position();
+
         // Step 1: Draw background
         self.draw_background(renderer, bounds);
 
         // Step 2: Draw gutter if enabled
         self.draw_gutter(renderer, bounds, editor, viewport, cursor_position);
 
-        // Step 3: Get visible lines from buffer directly
-        let visible_lines = self.get_visible_lines_with_partial(editor, viewport);
-
-        // Step 4: Batch all operations with zero-allocation hot path
-        let (text_ops, selection_quads) =
-            self.prepare_render_operations(&visible_lines, editor_bounds, viewport, selection);
-
-        // Step 5: Batch render selections first (behind text)
-        self.render_selections_batched(renderer, &selection_quads);
-
-        // Step 6: Batch render all text operations
-        self.render_text_batched(renderer, &text_ops);
+        // Step 3: Batch render decorations and selections first (behind text)
+        Self::render_decorations(renderer, &self.decorations_below_text);
+        self.render_selections_batched(renderer);
 
-        // Step 7: Draw cursor (on top of text)
-        self.draw_cursor(renderer, editor_bounds, cursor_position, viewport, editor);
+        // Step 4: Batch render all text operations
+        self.render_text_batched(renderer);
+        Self::render_decorations(renderer, &self.decorations_above_text);
 
-        // Step 8: Draw scrollbars last (on top of everything)
-        self.render_scrollbars(renderer, vertical_scrollbar, horizontal_scrollbar);
+        // Step 5: Draw cursor (on top of text)
+        self.draw_cursor(
+            renderer,
+            self.last_editor_bounds,
+            cursor_position,
+            viewport,
+            editor,
+        );
 
-        // Update scrollbar cache
-        self.last_vertical_scrollbar = Some(vertical_scrollbar);
-        self.last_horizontal_scrollbar = Some(horizontal_scrollbar);
-        self.last_content_dimensions = Some(content_dimensions);
+        // Step 6: Draw scrollbars last (on top of everything)
+        self.render_scrollbars(
+            renderer,
+            self.last_vertical_scrollbar.unwrap_or_default(),
+            self.last_horizontal_scrollbar.unwrap_or_default(),
+            overview_markers,
+            editor.current_buffer().line_count(),
+        );
     }
 
     /// Calculate the total content dimensions for scrollbar calculations with caching
-    fn calculate_content_dimensions(&mut self, editor: &Editor) -> (f32, f32) {
+    fn calculate_content_dimensions(&mut self, editor: &Editor, viewport: &Viewport) -> (f32, f32) {
         let rope = editor.current_buffer().rope();
         let line_count = rope.len_lines();
 
-        // Calculate content height
-        let content_height = line_count as f32 * self.line_height;
-
-        // Use cached content width if available and not dirty
-        let content_width = if self.content_width_dirty || self.cached_max_line_width.is_none() {
-            // Use the common utility function with increased limit for better accuracy
-            let max_width = utils::calculate_max_content_width(editor, self.char_width, 2000);
-
-            // Cache the result (without padding since the utility already adds it)
-            let max_width_without_padding = max_width - self.char_width * 2.0;
-            self.cached_max_line_width = Some(max_width_without_padding);
-            self.cached_max_line_index = Some(0); // We don't track line index in the utility
-            self.content_width_dirty = false;
+        // Calculate content height, including rows of virtual lines
+        let content_height = (line_count + viewport.virtual_row_count()) as f32 * self.line_height;
 
-            max_width
-        } else {
-            // Use cached value with padding
-            self.cached_max_line_width.unwrap() + self.char_width * 2.0
-        };
+        // Line widths are tracked by the buffer, so this is exact and cheap
+        let content_width = utils::calculate_max_content_width(editor, self.char_width);
+        self.content_width_dirty = false;
 
         (content_width, content_height)
     }
@@ -348,8 +589,10 @@ impl EditorRenderer {
             // Use cache if content dimensions and viewport haven't changed significantly
             if (last_content.0 - content_width).abs() < 1.0
                 && (last_content.1 - content_height).abs() < 1.0
+                && self.last_bounds == Some(bounds)
                 && self.last_viewport.as_ref().map_or(false, |v| {
-                    (v.size.0 - viewport.size.0).abs() < 1.0
+                    v.scroll_offset == viewport.scroll_offset
+                        && (v.size.0 - viewport.size.0).abs() < 1.0
                         && (v.size.1 - viewport.size.1).abs() < 1.0
                 })
             {
@@ -451,11 +694,12 @@ impl EditorRenderer {
         vertical_scrollbar_visible: bool,
         horizontal_scrollbar_visible: bool,
     ) -> Rectangle {
-        let width_reduction = if vertical_scrollbar_visible {
-            self.scrollbar_width
-        } else {
-            0.0
-        };
+        let width_reduction = self.minimap_width
+            + if vertical_scrollbar_visible {
+                self.scrollbar_width
+            } else {
+                0.0
+            };
         let height_reduction = if horizontal_scrollbar_visible {
             self.scrollbar_width
         } else {
@@ -474,18 +718,26 @@ impl EditorRenderer {
         )
     }
 
-    /// Render both scrollbars
+    /// Render both scrollbars, with overview ruler markers on the vertical track
     fn render_scrollbars<Renderer>(
         &self,
         renderer: &mut Renderer,
         vertical_scrollbar: ScrollbarInfo,
         horizontal_scrollbar: ScrollbarInfo,
+        overview_markers: &[OverviewMarker],
+        line_count: usize,
     ) where
         Renderer: iced::advanced::Renderer,
     {
         // Render vertical scrollbar
         if vertical_scrollbar.visible {
             self.render_single_scrollbar(renderer, vertical_scrollbar);
+            self.render_overview_markers(
+                renderer,
+                vertical_scrollbar.track_bounds,
+                overview_markers,
+                line_count,
+            );
         }
 
         // Render horizontal scrollbar
@@ -538,6 +790,65 @@ impl EditorRenderer {
         renderer.fill_quad(thumb_quad, self.scrollbar_thumb_color);
     }
 
+    /// Render overview ruler markers on top of the vertical scrollbar track
+    fn render_overview_markers<Renderer>(
+        &self,
+        renderer: &mut Renderer,
+        track_bounds: Rectangle,
+        markers: &[OverviewMarker],
+        line_count: usize,
+    ) where
+        Renderer: iced::advanced::Renderer,
+    {
+        for marker in markers {
+            let color = match marker.kind {
+                OverviewMarkerKind::Cursor => self.cursor_marker_color,
+                OverviewMarkerKind::SearchMatch => self.search_marker_color,
+                OverviewMarkerKind::Selection => self.selection_marker_color,
+                OverviewMarkerKind::Annotation(color) => color,
+            };
+
+            let marker_quad = Quad {
+                bounds: Self::overview_marker_bounds(marker, track_bounds, line_count),
+                border: iced::Border::default(),
+                shadow: iced::Shadow::default(),
+                snap: false,
+            };
+            renderer.fill_quad(marker_quad, color);
+        }
+    }
+
+    /// Calculate where a marker is drawn on the vertical scrollbar track
+    ///
+    /// Each marker kind gets its own lane so overlapping markers stay distinguishable:
+    /// selections on the left, annotations in the middle, search matches on the right
+    /// and the cursor across the whole track.
+    pub fn overview_marker_bounds(
+        marker: &OverviewMarker,
+        track_bounds: Rectangle,
+        line_count: usize,
+    ) -> Rectangle {
+        let line_count = line_count.max(1) as f32;
+        let start_y = marker.position.line as f32 / line_count * track_bounds.height;
+        let end_y = (marker.end_line + 1) as f32 / line_count * track_bounds.height;
+        let height = (end_y - start_y).max(Self::MIN_MARKER_HEIGHT);
+
+        let lane_width = track_bounds.width / 3.0;
+        let (x_offset, width) = match marker.kind {
+            OverviewMarkerKind::Cursor => (0.0, track_bounds.width),
+            OverviewMarkerKind::Selection => (0.0, lane_width),
+            OverviewMarkerKind::Annotation(_) => (lane_width, lane_width),
+            OverviewMarkerKind::SearchMatch => (lane_width * 2.0, lane_width),
+        };
+
+        let y = (track_bounds.y + start_y).min(track_bounds.y + track_bounds.height - height);
+
+        Rectangle::new(
+            Point::new(track_bounds.x + x_offset, y),
+            Size::new(width, height),
+        )
+    }
+
     /// Get visible lines with partial line information for smooth scrolling
     fn get_visible_lines_with_partial(
         &self,
@@ -565,14 +876,10 @@ impl EditorRenderer {
         bounds: Rectangle,
         viewport: &Viewport,
         selection: Option<&Selection>,
-    ) -> (Vec<TextOperation>, Vec<Quad>) {
+    ) {
         // Clear pools without deallocating
         self.text_operation_pool.clear();
-
-        // Reserve capacity based on visible lines
-        let line_count = visible_lines.len();
-        let mut text_ops = Vec::with_capacity(line_count);
-        let mut selection_quads = Vec::with_capacity(line_count.min(16));
+        self.selection_quad_pool.clear();
 
         // Single-pass preparation with minimal allocations
         for (line_content, partial_line) in visible_lines {
@@ -598,28 +905,41 @@ impl EditorRenderer {
             // Create text operation with proper clipping bounds
             let text_bounds_y = y_position + partial_line.clip_top;
 
-            text_ops.push(TextOperation {
-                content: if let Some(col_view) = column_view {
-                    // Only render the visible portion of the line
-                    self.extract_visible_line_content(line_content, &col_view)
-                } else {
-                    line_content.clone()
-                },
-                position: Point::new(
-                    x_position + column_view.map_or(0.0, |cv| cv.x_offset),
-                    y_position,
-                ),
-                bounds: Rectangle::new(
-                    Point::new(
+            if self.inline_virtual_text.contains_key(&line_index) {
+                // Lines with inline virtual text are drawn in pieces around it
+                self.push_line_segments(
+                    line_content,
+                    line_index,
+                    Point::new(x_position, y_position),
+                    text_bounds_y,
+                    visible_height,
+                    bounds,
+                );
+            } else {
+                self.text_operation_pool.push_back(TextOperation {
+                    content: if let Some(col_view) = column_view {
+                        // Only render the visible portion of the line
+                        self.extract_visible_line_content(line_content, &col_view)
+                    } else {
+                        line_content.clone()
+                    },
+                    position: Point::new(
                         x_position + column_view.map_or(0.0, |cv| cv.x_offset),
-                        text_bounds_y,
+                        y_position,
                     ),
-                    Size::new(
-                        column_view.map_or(bounds.width, |cv| cv.visible_width),
-                        visible_height,
+                    bounds: Rectangle::new(
+                        Point::new(
+                            x_position + column_view.map_or(0.0, |cv| cv.x_offset),
+                            text_bounds_y,
+                        ),
+                        Size::new(
+                            column_view.map_or(bounds.width, |cv| cv.visible_width),
+                            visible_height,
+                        ),
                     ),
-                ),
-            });
+                    color: self.text_color,
+                });
+            }
 
             // Handle selection rendering for this line
             if let Some(selection) = selection {
@@ -635,14 +955,15 @@ impl EditorRenderer {
                         line_content.chars().count()
                     };
 
-                    let start_x = self.calculate_x_position_fast(start_col, line_content);
-                    let end_x = self.calculate_x_position_fast(end_col, line_content);
+                    let start_x =
+                        self.calculate_x_position_fast(line_index, start_col, line_content);
+                    let end_x = self.calculate_x_position_fast(line_index, end_col, line_content);
 
                     let selection_y = text_bounds_y;
                     let selection_width = end_x - start_x;
 
                     if selection_width > 0.0 {
-                        selection_quads.push(Quad {
+                        self.selection_quad_pool.push(Quad {
                             bounds: Rectangle::new(
                                 Point::new(
                                     start_x + bounds.x - viewport.scroll_offset.0,
@@ -658,13 +979,797 @@ impl EditorRenderer {
                 }
             }
         }
+    }
+
+    /// Queue the pieces of a line between its inline virtual text
+    ///
+    /// Each piece is shifted right by the virtual text before it. The virtual text
+    /// itself is queued by [`Self::prepare_virtual_text`].
+    fn push_line_segments(
+        &mut self,
+        line_content: &str,
+        line_index: usize,
+        line_origin: Point,
+        clip_y: f32,
+        clip_height: f32,
+        bounds: Rectangle,
+    ) {
+        let line_content = line_content.trim_end_matches(['\n', '\r']);
+        let chars: Vec<char> = line_content.chars().collect();
+        let mut columns: Vec<usize> = self.inline_virtual_text[&line_index]
+            .iter()
+            .map(|(column, _)| *column)
+            .filter(|column| *column > 0 && *column < chars.len())
+            .collect();
+        columns.dedup();
+
+        let mut start = 0;
+        for end in columns.into_iter().chain(std::iter::once(chars.len())) {
+            let x = line_origin.x
+                + utils::calculate_column_x_position(start, line_content, self.char_width)
+                + utils::virtual_text_offset(start, &self.inline_virtual_text[&line_index], true);
+            let content: String = chars[start..end].iter().collect();
+            self.push_clipped_text(
+                content,
+                Point::new(x, line_origin.y),
+                clip_y,
+                clip_height,
+                bounds,
+                self.text_color,
+            );
+            start = end;
+        }
+    }
+
+    /// Queue the virtual text of visible lines, dimmed
+    ///
+    /// Inline text is drawn in the gap left by [`Self::push_line_segments`]; end of
+    /// line text is drawn after the last char.
+    fn prepare_virtual_text(
+        &mut self,
+        editor: &Editor,
+        visible_lines: &[(String, PartialLineView)],
+        bounds: Rectangle,
+        viewport: &Viewport,
+    ) {
+        let buffer = editor.current_buffer();
+        for (line_content, partial_line) in visible_lines {
+            let line_index = partial_line.line_index;
+            let visible_height =
+                self.line_height - partial_line.clip_top - partial_line.clip_bottom;
+            if visible_height <= 0.0 {
+                continue;
+            }
+
+            let line_content = line_content.trim_end_matches(['\n', '\r']);
+            let line_len = line_content.chars().count();
+            let y_position = bounds.y + partial_line.y_offset;
+            let clip_y = y_position + partial_line.clip_top;
+            let line_x = bounds.x - viewport.scroll_offset.0;
+            let inline_width = self
+                .inline_virtual_text
+                .get(&line_index)
+                .map_or(0.0, |virtual_text| {
+                    utils::virtual_text_offset(line_len, virtual_text, true)
+                });
+            let mut end_of_line_x = line_x
+                + utils::calculate_column_x_position(line_len, line_content, self.char_width)
+                + inline_width
+                + self.char_width * 2.0;
+
+            for span in buffer.virtual_text_on_line(line_index) {
+                let x = match span.placement {
+                    VirtualTextPlacement::Inline => {
+                        line_x
+                            + self.calculate_x_position_fast(
+                                line_index,
+                                span.position.column,
+                                line_content,
+                            )
+                    }
+                    VirtualTextPlacement::EndOfLine => {
+                        let x = end_of_line_x;
+                        end_of_line_x += (span.text.chars().count() + 1) as f32 * self.char_width;
+                        x
+                    }
+                };
+                self.push_clipped_text(
+                    span.text.to_string(),
+                    Point::new(x, y_position),
+                    clip_y,
+                    visible_height,
+                    bounds,
+                    self.virtual_text_color,
+                );
+            }
+        }
+    }
+
+    /// Queue text starting at `position`, clipped to the editor area
+    fn push_clipped_text(
+        &mut self,
+        content: String,
+        position: Point,
+        clip_y: f32,
+        clip_height: f32,
+        bounds: Rectangle,
+        color: Color,
+    ) {
+        let clip_x = position.x.max(bounds.x);
+        let clip_width = bounds.x + bounds.width - clip_x;
+        if content.is_empty() || clip_width <= 0.0 {
+            return;
+        }
+
+        self.text_operation_pool.push_back(TextOperation {
+            content,
+            position,
+            bounds: Rectangle::new(
+                Point::new(clip_x, clip_y),
+                Size::new(clip_width, clip_height),
+            ),
+            color,
+        });
+    }
+
+    /// Build quads for the decorations and diagnostics on visible lines
+    fn prepare_decorations(
+        &mut self,
+        editor: &Editor,
+        visible_lines: &[(String, PartialLineView)],
+        bounds: Rectangle,
+        viewport: &Viewport,
+    ) {
+        self.decorations_below_text.clear();
+        self.decorations_above_text.clear();
+        self.gutter_diagnostics.clear();
+
+        let buffer = editor.current_buffer();
+        for span in buffer.decorations() {
+            let quads = self.range_quads(
+                &span.range,
+                span.style.shape,
+                utils::rgba_to_color(span.style.color),
+                visible_lines,
+                bounds,
+                viewport,
+            );
+
+            match span.style.layer {
+                DecorationLayer::BelowText => self.decorations_below_text.extend(quads),
+                DecorationLayer::AboveText => self.decorations_above_text.extend(quads),
+            }
+        }
+
+        // Diagnostics are drawn as squiggles above the text, most severe last
+        let mut diagnostics = buffer.diagnostics();
+        diagnostics.sort_by_key(|diagnostic| std::cmp::Reverse(diagnostic.severity));
+        for diagnostic in &diagnostics {
+            let quads = self.range_quads(
+                &diagnostic.range,
+                DecorationShape::WavyUnderline,
+                self.severity_color(diagnostic.severity),
+                visible_lines,
+                bounds,
+                viewport,
+            );
+            self.decorations_above_text.extend(quads);
+
+            self.gutter_diagnostics
+                .entry(diagnostic.range.start.line)
+                .and_modify(|severity| *severity = (*severity).min(diagnostic.severity))
+                .or_insert(diagnostic.severity);
+        }
+    }
+
+    /// Get the quads painting a shape over a range, one visible line at a time
+    fn range_quads(
+        &self,
+        range: &std::ops::Range<Position>,
+        shape: DecorationShape,
+        color: Color,
+        visible_lines: &[(String, PartialLineView)],
+        bounds: Rectangle,
+        viewport: &Viewport,
+    ) -> Vec<(Quad, Color)> {
+        let mut quads = Vec::new();
+
+        for (line_content, partial_line) in visible_lines {
+            let line_index = partial_line.line_index;
+            if line_index < range.start.line || line_index > range.end.line {
+                continue;
+            }
+
+            let start_col = if line_index == range.start.line {
+                range.start.column
+            } else {
+                0
+            };
+            let end_col = if line_index == range.end.line {
+                range.end.column
+            } else {
+                line_content.trim_end_matches(['\n', '\r']).chars().count()
+            };
+
+            let line_x = bounds.x - viewport.scroll_offset.0;
+            let start_x =
+                line_x + self.calculate_x_position_fast(line_index, start_col, line_content);
+            let mut end_x =
+                line_x + self.calculate_x_position_fast(line_index, end_col, line_content);
+            if end_x <= start_x {
+                // Keep empty squiggles (e.g. a missing semicolon) visible
+                if shape != DecorationShape::WavyUnderline {
+                    continue;
+                }
+                end_x = start_x + self.char_width;
+            }
+
+            let line_bounds = Rectangle::new(
+                Point::new(start_x, bounds.y + partial_line.y_offset),
+                Size::new(end_x - start_x, self.line_height),
+            );
+            quads.extend(Self::decoration_quads(shape, line_bounds, color, bounds));
+        }
+
+        quads
+    }
+
+    /// Get the color used for a diagnostic severity
+    pub fn severity_color(&self, severity: DiagnosticSeverity) -> Color {
+        match severity {
+            DiagnosticSeverity::Error => self.error_color,
+            DiagnosticSeverity::Warning => self.warning_color,
+            DiagnosticSeverity::Information => self.info_color,
+            DiagnosticSeverity::Hint => self.hint_color,
+        }
+    }
+
+    /// Draw a popup with a diagnostic message below the start of its range
+    ///
+    /// The popup is drawn on its own layer so it stays on top of the editor content.
+    pub fn draw_diagnostic_popup<Renderer>(
+        &self,
+        renderer: &mut Renderer,
+        diagnostic: &Diagnostic,
+        editor: &Editor,
+        viewport: &Viewport,
+        bounds: Rectangle,
+    ) where
+        Renderer: iced::advanced::Renderer + iced::advanced::text::Renderer<Font = Font>,
+    {
+        let severity = match diagnostic.severity {
+            DiagnosticSeverity::Error => "error",
+            DiagnosticSeverity::Warning => "warning",
+            DiagnosticSeverity::Information => "info",
+            DiagnosticSeverity::Hint => "hint",
+        };
+        let content = match &diagnostic.source {
+            Some(source) => format!("{severity}: {} ({source})", diagnostic.message),
+            None => format!("{severity}: {}", diagnostic.message),
+        };
+
+        let padding = 6.0;
+        let line_count = content.lines().count().max(1);
+        let longest_line = content
+            .lines()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        let size = Size::new(
+            (longest_line as f32 * self.char_width + padding * 2.0).min(bounds.width),
+            (line_count as f32 * self.line_height + padding * 2.0).min(bounds.height),
+        );
 
-        (text_ops, selection_quads)
+        let popup_bounds =
+            self.popup_bounds(diagnostic.range.start, size, editor, viewport, bounds);
+        let Point { x, y } = popup_bounds.position();
+
+        renderer.with_layer(popup_bounds, |renderer| {
+            renderer.fill_quad(
+                Quad {
+                    bounds: popup_bounds,
+                    border: iced::Border {
+                        color: self.severity_color(diagnostic.severity),
+                        width: 1.0,
+                        radius: 3.0.into(),
+                    },
+                    shadow: iced::Shadow::default(),
+                    snap: false,
+                },
+                self.gutter_background_color,
+            );
+
+            let text_position = Point::new(x + padding, y + padding);
+            let text_bounds = Rectangle::new(
+                text_position,
+                Size::new(size.width - padding * 2.0, size.height - padding * 2.0),
+            );
+            renderer.fill_text(
+                Text {
+                    content,
+                    bounds: text_bounds.size(),
+                    size: iced::Pixels(self.font_size),
+                    line_height: iced::advanced::text::LineHeight::Absolute(iced::Pixels(
+                        self.line_height,
+                    )),
+                    font: Font::MONOSPACE,
+                    align_x: Alignment::Left,
+                    align_y: iced::alignment::Vertical::Top,
+                    shaping: iced::advanced::text::Shaping::Basic,
+                    wrapping: iced::advanced::text::Wrapping::None,
+                },
+                text_position,
+                self.text_color,
+                text_bounds,
+            );
+        });
+    }
+
+    /// Draw a tooltip below its position, returning where it was drawn
+    ///
+    /// Prose is wrapped; code blocks are shown as is on a darker background.
+    pub fn draw_tooltip<Renderer>(
+        &self,
+        renderer: &mut Renderer,
+        tooltip: &Tooltip,
+        editor: &Editor,
+        viewport: &Viewport,
+        bounds: Rectangle,
+    ) -> Rectangle
+    where
+        Renderer: iced::advanced::Renderer + iced::advanced::text::Renderer<Font = Font>,
+    {
+        // Lines of each block, and whether it is code
+        let blocks: Vec<(Vec<String>, bool)> = tooltip
+            .blocks
+            .iter()
+            .map(|block| match block {
+                TooltipBlock::Text(text) => {
+                    (utils::wrap_words(text, Self::TOOLTIP_WRAP_COLUMNS), false)
+                }
+                TooltipBlock::Code(code) => (code.lines().map(str::to_string).collect(), true),
+            })
+            .filter(|(lines, _)| !lines.is_empty())
+            .collect();
+        if blocks.is_empty() {
+            return Rectangle::default();
+        }
+
+        let padding = 6.0;
+        let line_count: usize = blocks.iter().map(|(lines, _)| lines.len()).sum();
+        let longest_line = blocks
+            .iter()
+            .flat_map(|(lines, _)| lines)
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        let size = Size::new(
+            (longest_line as f32 * self.char_width + padding * 2.0).min(bounds.width),
+            (line_count as f32 * self.line_height + (blocks.len() + 1) as f32 * padding)
+                .min(bounds.height),
+        );
+        let popup_bounds = self.popup_bounds(tooltip.position, size, editor, viewport, bounds);
+
+        renderer.with_layer(popup_bounds, |renderer| {
+            renderer.fill_quad(
+                Quad {
+                    bounds: popup_bounds,
+                    border: iced::Border {
+                        color: self.line_number_color,
+                        width: 1.0,
+                        radius: 3.0.into(),
+                    },
+                    shadow: iced::Shadow::default(),
+                    snap: false,
+                },
+                self.gutter_background_color,
+            );
+
+            let mut y = popup_bounds.y + padding;
+            for (lines, is_code) in blocks {
+                let block_bounds = Rectangle::new(
+                    Point::new(popup_bounds.x + padding, y),
+                    Size::new(
+                        popup_bounds.width - padding * 2.0,
+                        lines.len() as f32 * self.line_height,
+                    ),
+                );
+                if is_code {
+                    renderer.fill_quad(
+                        Quad {
+                            bounds: block_bounds,
+                            border: iced::Border::default(),
+                            shadow: iced::Shadow::default(),
+                            snap: false,
+                        },
+                        self.background_color,
+                    );
+                }
+
+                renderer.fill_text(
+                    Text {
+                        content: lines.join("\n"),
+                        bounds: block_bounds.size(),
+                        size: iced::Pixels(self.font_size),
+                        line_height: iced::advanced::text::LineHeight::Absolute(iced::Pixels(
+                            self.line_height,
+                        )),
+                        font: Font::MONOSPACE,
+                        align_x: Alignment::Left,
+                        align_y: iced::alignment::Vertical::Top,
+                        shaping: iced::advanced::text::Shaping::Advanced,
+                        wrapping: iced::advanced::text::Wrapping::None,
+                    },
+                    block_bounds.position(),
+                    self.text_color,
+                    block_bounds,
+                );
+                y += block_bounds.height + padding;
+            }
+        });
+
+        popup_bounds
+    }
+
+    /// Draw the keys typed so far towards a chord in the bottom right corner
+    pub fn draw_key_status<Renderer>(
+        &self,
+        renderer: &mut Renderer,
+        status: &str,
+        bounds: Rectangle,
+    ) where
+        Renderer: iced::advanced::Renderer + iced::advanced::text::Renderer<Font = Font>,
+    {
+        let padding = 4.0;
+        let size = Size::new(
+            status.chars().count() as f32 * self.char_width + padding * 2.0,
+            self.line_height + padding * 2.0,
+        );
+        // Keep clear of the vertical scrollbar and the minimap
+        let status_bounds = Rectangle::new(
+            Point::new(
+                (bounds.x + bounds.width
+                    - size.width
+                    - Self::SCROLLBAR_WIDTH
+                    - self.minimap_width
+                    - padding)
+                    .max(bounds.x),
+                (bounds.y + bounds.height - size.height - Self::SCROLLBAR_WIDTH - padding)
+                    .max(bounds.y),
+            ),
+            size,
+        );
+
+        renderer.with_layer(status_bounds, |renderer| {
+            renderer.fill_quad(
+                Quad {
+                    bounds: status_bounds,
+                    border: iced::Border {
+                        color: self.line_number_color,
+                        width: 1.0,
+                        radius: 3.0.into(),
+                    },
+                    shadow: iced::Shadow::default(),
+                    snap: false,
+                },
+                self.gutter_background_color,
+            );
+
+            let text_bounds = status_bounds.shrink(padding);
+            renderer.fill_text(
+                Text {
+                    content: status.to_string(),
+                    bounds: text_bounds.size(),
+                    size: iced::Pixels(self.font_size),
+                    line_height: iced::advanced::text::LineHeight::Absolute(iced::Pixels(
+                        self.line_height,
+                    )),
+                    font: Font::MONOSPACE,
+                    align_x: Alignment::Left,
+                    align_y: iced::alignment::Vertical::Top,
+                    shaping: iced::advanced::text::Shaping::Advanced,
+                    wrapping: iced::advanced::text::Wrapping::None,
+                },
+                text_bounds.position(),
+                self.text_color,
+                text_bounds,
+            );
+        });
+    }
+
+    /// Place a popup of the given size below a text position
+    ///
+    /// The popup flips above the line when it would overflow the bottom of the
+    /// editor, and is shifted left to stay within its bounds.
+    fn popup_bounds(
+        &self,
+        anchor: Position,
+        size: Size,
+        editor: &Editor,
+        viewport: &Viewport,
+        bounds: Rectangle,
+    ) -> Rectangle {
+        let line_x = utils::position_x(editor, anchor, self.char_width);
+        let content_bounds = self.last_editor_bounds;
+        let line_y =
+            content_bounds.y + anchor.line as f32 * self.line_height - viewport.scroll_offset.1;
+        let below = line_y + self.line_height;
+        let y = if below + size.height > bounds.y + bounds.height {
+            (line_y - size.height).max(bounds.y)
+        } else {
+            below
+        };
+        let x = (content_bounds.x + line_x - viewport.scroll_offset.0)
+            .min(bounds.x + bounds.width - size.width)
+            .max(bounds.x);
+
+        Rectangle::new(Point::new(x, y), size)
+    }
+
+    /// Draw the lines of a multi-line inline suggestion below the cursor line
+    ///
+    /// The first line is drawn as virtual text by [`Self::render`]. The others go in
+    /// the rows the viewport leaves free with [`Viewport::set_virtual_lines`].
+    pub fn draw_inline_suggestion<Renderer>(
+        &self,
+        renderer: &mut Renderer,
+        suggestion: &InlineSuggestion,
+        viewport: &Viewport,
+    ) where
+        Renderer: iced::advanced::Renderer + iced::advanced::text::Renderer<Font = Font>,
+    {
+        let lines: Vec<&str> = suggestion.lines().skip(1).collect();
+        if lines.is_empty() {
+            return;
+        }
+
+        let content_bounds = self.last_editor_bounds;
+        let longest_line = lines
+            .iter()
+            .map(|line| self.calculate_line_width(line))
+            .fold(0.0, f32::max);
+        let first_y = content_bounds.y + (suggestion.position.line + 1) as f32 * self.line_height
+            - viewport.scroll_offset.1;
+        let x = content_bounds.x - viewport.scroll_offset.0;
+        let suggestion_bounds = Rectangle::new(
+            Point::new(x, first_y),
+            Size::new(
+                longest_line + self.char_width,
+                lines.len() as f32 * self.line_height,
+            ),
+        );
+        let Some(visible_bounds) = suggestion_bounds.intersection(&content_bounds) else {
+            return;
+        };
+
+        renderer.with_layer(visible_bounds, |renderer| {
+            for (index, line) in lines.into_iter().enumerate() {
+                let line_position = Point::new(x, first_y + index as f32 * self.line_height);
+                renderer.fill_text(
+                    Text {
+                        content: line.to_string(),
+                        bounds: Size::new(suggestion_bounds.width, self.line_height),
+                        size: iced::Pixels(self.font_size),
+                        line_height: iced::advanced::text::LineHeight::Absolute(iced::Pixels(
+                            self.line_height,
+                        )),
+                        font: Font::MONOSPACE,
+                        align_x: Alignment::Left,
+                        align_y: iced::alignment::Vertical::Top,
+                        shaping: iced::advanced::text::Shaping::Advanced,
+                        wrapping: iced::advanced::text::Wrapping::None,
+                    },
+                    line_position,
+                    self.virtual_text_color,
+                    visible_bounds,
+                );
+            }
+        });
+    }
+
+    /// Draw the completion list below the word being completed
+    ///
+    /// At most [`Self::MAX_COMPLETION_ROWS`] items are shown, scrolled so the
+    /// selected item stays visible.
+    pub fn draw_completion_popup<Renderer>(
+        &self,
+        renderer: &mut Renderer,
+        session: &CompletionSession,
+        editor: &Editor,
+        viewport: &Viewport,
+        bounds: Rectangle,
+    ) where
+        Renderer: iced::advanced::Renderer + iced::advanced::text::Renderer<Font = Font>,
+    {
+        let selected = session.selected_index();
+        let first_row = (selected + 1).saturating_sub(Self::MAX_COMPLETION_ROWS);
+        let rows: Vec<(usize, String)> = session
+            .visible_items()
+            .enumerate()
+            .skip(first_row)
+            .take(Self::MAX_COMPLETION_ROWS)
+            .map(|(index, item)| {
+                let kind = completion_kind_label(item.kind);
+                let row = match &item.detail {
+                    Some(detail) => format!("{kind:<4}{}  {detail}", item.label),
+                    None => format!("{kind:<4}{}", item.label),
+                };
+                (index, row)
+            })
+            .collect();
+        if rows.is_empty() {
+            return;
+        }
+
+        let padding = 4.0;
+        let longest_row = rows
+            .iter()
+            .map(|(_, row)| row.chars().count())
+            .max()
+            .unwrap_or(0);
+        let size = Size::new(
+            (longest_row as f32 * self.char_width + padding * 2.0).min(bounds.width),
+            (rows.len() as f32 * self.line_height + padding * 2.0).min(bounds.height),
+        );
+        let popup_bounds = self.popup_bounds(session.word_start(), size, editor, viewport, bounds);
+
+        renderer.with_layer(popup_bounds, |renderer| {
+            renderer.fill_quad(
+                Quad {
+                    bounds: popup_bounds,
+                    border: iced::Border {
+                        color: self.line_number_color,
+                        width: 1.0,
+                        radius: 3.0.into(),
+                    },
+                    shadow: iced::Shadow::default(),
+                    snap: false,
+                },
+                self.gutter_background_color,
+            );
+
+            for (row_index, (index, row)) in rows.into_iter().enumerate() {
+                let row_position = Point::new(
+                    popup_bounds.x + padding,
+                    popup_bounds.y + padding + row_index as f32 * self.line_height,
+                );
+                let row_bounds = Rectangle::new(
+                    row_position,
+                    Size::new(popup_bounds.width - padding * 2.0, self.line_height),
+                );
+
+                if index == selected {
+                    renderer.fill_quad(
+                        Quad {
+                            bounds: row_bounds,
+                            border: iced::Border::default(),
+                            shadow: iced::Shadow::default(),
+                            snap: false,
+                        },
+                        self.selection_color,
+                    );
+                }
+
+                renderer.fill_text(
+                    Text {
+                        content: row,
+                        bounds: row_bounds.size(),
+                        size: iced::Pixels(self.font_size),
+                        line_height: iced::advanced::text::LineHeight::Absolute(iced::Pixels(
+                            self.line_height,
+                        )),
+                        font: Font::MONOSPACE,
+                        align_x: Alignment::Left,
+                        align_y: iced::alignment::Vertical::Top,
+                        shaping: iced::advanced::text::Shaping::Basic,
+                        wrapping: iced::advanced::text::Wrapping::None,
+                    },
+                    row_position,
+                    self.text_color,
+                    row_bounds,
+                );
+            }
+        });
+    }
+
+    /// Get the quads (with fill colors) that paint a decoration shape on one line
+    fn decoration_quads(
+        shape: DecorationShape,
+        line_bounds: Rectangle,
+        color: Color,
+        clip_bounds: Rectangle,
+    ) -> Vec<(Quad, Color)> {
+        let Rectangle {
+            x,
+            y,
+            width,
+            height,
+        } = line_bounds;
+        let fill = |rect: Rectangle| {
+            rect.intersection(&clip_bounds).map(|bounds| {
+                (
+                    Quad {
+                        bounds,
+                        border: iced::Border::default(),
+                        shadow: iced::Shadow::default(),
+                        snap: false,
+                    },
+                    color,
+                )
+            })
+        };
+
+        match shape {
+            DecorationShape::Background => fill(line_bounds).into_iter().collect(),
+            DecorationShape::Underline => fill(Rectangle::new(
+                Point::new(x, y + height - 2.0),
+                Size::new(width, 1.0),
+            ))
+            .into_iter()
+            .collect(),
+            DecorationShape::Strikethrough => fill(Rectangle::new(
+                Point::new(x, y + height / 2.0),
+                Size::new(width, 1.0),
+            ))
+            .into_iter()
+            .collect(),
+            DecorationShape::WavyUnderline => {
+                // Approximate the wave with short segments alternating between two rows
+                let step = 2.0;
+                let segments = (width / step).ceil() as usize;
+                (0..segments)
+                    .filter_map(|i| {
+                        let segment_x = x + i as f32 * step;
+                        let row = if i % 2 == 0 { 3.0 } else { 2.0 };
+                        fill(Rectangle::new(
+                            Point::new(segment_x, y + height - row),
+                            Size::new(step.min(x + width - segment_x), 1.0),
+                        ))
+                    })
+                    .collect()
+            }
+            DecorationShape::Border => line_bounds
+                .intersection(&clip_bounds)
+                .map(|bounds| {
+                    (
+                        Quad {
+                            bounds,
+                            border: iced::Border {
+                                color,
+                                width: 1.0,
+                                radius: 2.0.into(),
+                            },
+                            shadow: iced::Shadow::default(),
+                            snap: false,
+                        },
+                        Color::TRANSPARENT,
+                    )
+                })
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    fn render_decorations<Renderer>(renderer: &mut Renderer, quads: &[(Quad, Color)])
+    where
+        Renderer: iced::advanced::Renderer,
+    {
+        for (quad, color) in quads {
+            renderer.fill_quad(*quad, *color);
+        }
     }
 
     #[inline]
-    fn calculate_x_position_fast(&self, column: usize, line_content: &str) -> f32 {
-        utils::calculate_column_x_position(column, line_content, self.char_width)
+    fn calculate_x_position_fast(&self, line: usize, column: usize, line_content: &str) -> f32 {
+        match self.inline_virtual_text.get(&line) {
+            Some(virtual_text) => utils::column_x_with_virtual_text(
+                column,
+                line_content,
+                self.char_width,
+                virtual_text,
+            ),
+            None => utils::calculate_column_x_position(column, line_content, self.char_width),
+        }
     }
 
     /// Calculate which columns are visible given horizontal scroll offset and viewport width
@@ -751,22 +1856,22 @@ impl EditorRenderer {
         chars[start..end].iter().collect()
     }
 
-    fn render_selections_batched<Renderer>(&self, renderer: &mut Renderer, quads: &[Quad])
+    fn render_selections_batched<Renderer>(&self, renderer: &mut Renderer)
     where
         Renderer: iced::advanced::Renderer,
     {
         // Batch render all selection quads in one call
-        for quad in quads {
+        for quad in &self.selection_quad_pool {
             renderer.fill_quad(*quad, self.selection_color);
         }
     }
 
-    fn render_text_batched<Renderer>(&self, renderer: &mut Renderer, text_ops: &[TextOperation])
+    fn render_text_batched<Renderer>(&self, renderer: &mut Renderer)
     where
         Renderer: iced::advanced::Renderer + iced::advanced::text::Renderer<Font = Font>,
     {
         // Batch render all text operations
-        for text_op in text_ops {
+        for text_op in &self.text_operation_pool {
             let text = Text {
                 content: text_op.content.clone(),
                 bounds: text_op.bounds.size(),
@@ -781,7 +1886,7 @@ impl EditorRenderer {
                 wrapping: iced::advanced::text::Wrapping::None,
             };
 
-            renderer.fill_text(text, text_op.position, self.text_color, text_op.bounds);
+            renderer.fill_text(text, text_op.position, text_op.color, text_op.bounds);
         }
     }
 
@@ -814,23 +1919,8 @@ impl EditorRenderer {
 
         // Get the line content to calculate accurate X position with tab handling
         // Note: bounds.x already includes gutter offset from calculate_editor_content_bounds
-        let cursor_x = {
-            let rope = editor.current_buffer().rope();
-            if cursor_position.line < rope.len_lines() {
-                if let Some(line) = rope.get_line(cursor_position.line) {
-                    let line_str = line.to_string();
-                    utils::calculate_column_x_position(
-                        cursor_position.column,
-                        &line_str,
-                        self.char_width,
-                    ) - viewport.scroll_offset.0
-                } else {
-                    cursor_position.column as f32 * self.char_width - viewport.scroll_offset.0
-                }
-            } else {
-                cursor_position.column as f32 * self.char_width - viewport.scroll_offset.0
-            }
-        };
+        let cursor_x =
+            utils::position_x(editor, cursor_position, self.char_width) - viewport.scroll_offset.0;
 
         // Only draw if cursor is visible in viewport
         if cursor_x >= -self.cursor_width
@@ -838,17 +1928,57 @@ impl EditorRenderer {
             && cursor_y >= -self.line_height
             && cursor_y <= bounds.height
         {
-            let cursor_quad = Quad {
-                bounds: Rectangle::new(
-                    Point::new(bounds.x + cursor_x, bounds.y + cursor_y),
-                    Size::new(self.cursor_width, self.line_height),
+            // Block and underline cursors cover the char after the cursor, or one
+            // char's width at the end of a line
+            let cell_width = || {
+                let next = Position::new(cursor_position.line, cursor_position.column + 1);
+                let next_x =
+                    utils::position_x(editor, next, self.char_width) - viewport.scroll_offset.0;
+                if next_x > cursor_x {
+                    next_x - cursor_x
+                } else {
+                    self.char_width
+                }
+            };
+            let (cursor_bounds, color) = match self.cursor_style {
+                CursorStyle::Line => (
+                    Rectangle::new(
+                        Point::new(bounds.x + cursor_x, bounds.y + cursor_y),
+                        Size::new(self.cursor_width, self.line_height),
+                    ),
+                    self.cursor_color,
                 ),
+                CursorStyle::Block => (
+                    Rectangle::new(
+                        Point::new(bounds.x + cursor_x, bounds.y + cursor_y),
+                        Size::new(cell_width(), self.line_height),
+                    ),
+                    // Translucent so the char under the cursor stays readable
+                    Color {
+                        a: self.cursor_color.a * 0.5,
+                        ..self.cursor_color
+                    },
+                ),
+                CursorStyle::Underline => (
+                    Rectangle::new(
+                        Point::new(
+                            bounds.x + cursor_x,
+                            bounds.y + cursor_y + self.line_height - self.cursor_width,
+                        ),
+                        Size::new(cell_width(), self.cursor_width),
+                    ),
+                    self.cursor_color,
+                ),
+            };
+
+            let cursor_quad = Quad {
+                bounds: cursor_bounds,
                 border: iced::Border::default(),
                 shadow: iced::Shadow::default(),
                 snap: false,
             };
 
-            renderer.fill_quad(cursor_quad, self.cursor_color);
+            renderer.fill_quad(cursor_quad, color);
         }
     }
 
@@ -923,6 +2053,31 @@ impl EditorRenderer {
             };
 
             renderer.fill_text(text, text_position, color, text_bounds);
+
+            // Diagnostic icon in the padding on the right of the line number
+            if let Some(severity) = self.gutter_diagnostics.get(&line_index) {
+                let icon_size = (self.gutter_padding - 2.0).clamp(2.0, self.line_height * 0.5);
+                let icon_bounds = Rectangle::new(
+                    Point::new(
+                        bounds.x + self.gutter_width - self.gutter_padding
+                            + (self.gutter_padding - icon_size) / 2.0,
+                        y_position + (self.line_height - icon_size) / 2.0,
+                    ),
+                    Size::new(icon_size, icon_size),
+                );
+                renderer.fill_quad(
+                    Quad {
+                        bounds: icon_bounds,
+                        border: iced::Border {
+                            radius: (icon_size / 2.0).into(),
+                            ..iced::Border::default()
+                        },
+                        shadow: iced::Shadow::default(),
+                        snap: false,
+                    },
+                    self.severity_color(*severity),
+                );
+            }
         }
     }
 
@@ -931,18 +2086,74 @@ impl EditorRenderer {
         if self.text_operation_pool.capacity() > 128 {
             self.text_operation_pool.shrink_to(64);
         }
+        if self.selection_quad_pool.capacity() > 128 {
+            self.selection_quad_pool.shrink_to(16);
+        }
     }
 
     /// Invalidate content width cache when editor content changes
     pub fn invalidate_content_cache(&mut self) {
         self.content_width_dirty = true;
-        self.cached_max_line_width = None;
-        self.cached_max_line_index = None;
     }
 
     /// Get the maximum content width for limiting horizontal scrolling
     pub fn get_max_content_width(&self) -> Option<f32> {
-        self.cached_max_line_width
-            .map(|w| w + self.char_width * 2.0)
+        self.last_content_dimensions.map(|(width, _)| width)
+    }
+}
+
+/// Short tag shown before a completion label
+fn completion_kind_label(kind: CompletionKind) -> &'static str {
+    match kind {
+        CompletionKind::Text => "abc",
+        CompletionKind::Keyword => "kw",
+        CompletionKind::Function => "fn",
+        CompletionKind::Method => "fn",
+        CompletionKind::Variable => "var",
+        CompletionKind::Field => "fld",
+        CompletionKind::Type => "ty",
+        CompletionKind::Module => "mod",
+        CompletionKind::Snippet => "snp",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overview_marker_bounds() {
+        let track = Rectangle::new(Point::new(100.0, 10.0), Size::new(12.0, 128.0));
+
+        // Each kind gets its own lane
+        let cursor = OverviewMarker::new(OverviewMarkerKind::Cursor, Position::new(32, 0));
+        let bounds = EditorRenderer::overview_marker_bounds(&cursor, track, 64);
+        assert_eq!(
+            bounds,
+            Rectangle::new(Point::new(100.0, 74.0), Size::new(12.0, 3.0))
+        );
+
+        let search = OverviewMarker::new(OverviewMarkerKind::SearchMatch, Position::new(0, 0));
+        let bounds = EditorRenderer::overview_marker_bounds(&search, track, 64);
+        assert_eq!(
+            bounds,
+            Rectangle::new(Point::new(108.0, 10.0), Size::new(4.0, 3.0))
+        );
+
+        // Spanning markers cover their lines, and the last line stays on the track
+        let selection =
+            OverviewMarker::new(OverviewMarkerKind::Selection, Position::new(10, 0)).spanning(29);
+        let bounds = EditorRenderer::overview_marker_bounds(&selection, track, 64);
+        assert_eq!(
+            bounds,
+            Rectangle::new(Point::new(100.0, 30.0), Size::new(4.0, 40.0))
+        );
+
+        let last = OverviewMarker::annotation(Position::new(63, 0), Color::WHITE);
+        let bounds = EditorRenderer::overview_marker_bounds(&last, track, 64);
+        assert_eq!(
+            bounds,
+            Rectangle::new(Point::new(104.0, 135.0), Size::new(4.0, 3.0))
+        );
     }
 }
@@ -1,4 +1,5 @@
-use icedit_core::Editor;
+use iced::Color;
+use icedit_core::{Editor, Position, Rgba, VirtualTextPlacement};
 
 /// Utility functions for text measurement and content calculations
 /// shared between renderer and widget components.
@@ -29,40 +30,27 @@ pub fn calculate_line_width(line: &str, char_width: f32, tab_width: f32) -> f32
 
 /// Calculate the maximum content width for an editor buffer
 ///
+/// Line widths are tracked incrementally by the buffer, so this is exact for any
+/// buffer size and does not scan the text.
+///
 /// # Arguments
 /// * `editor` - Reference to the editor instance
 /// * `char_width` - Width of a single character
-/// * `max_lines_to_check` - Maximum number of lines to check for performance
 ///
 /// # Returns
 /// The maximum content width with padding
-pub fn calculate_max_content_width(
-    editor: &Editor,
-    char_width: f32,
-    max_lines_to_check: usize,
-) -> f32 {
-    let rope = editor.current_buffer().rope();
-    let line_count = rope.len_lines();
-    let mut max_width: f32 = 0.0;
-    let tab_width = char_width * 4.0; // 4 spaces per tab
-
-    // Check up to max_lines_to_check lines for performance
-    let lines_to_check = line_count.min(max_lines_to_check);
-
-    for line_idx in 0..lines_to_check {
-        if let Some(line) = rope.get_line(line_idx) {
-            let line_str = line.to_string();
-            let width = calculate_line_width(&line_str, char_width, tab_width);
-            if width > max_width {
-                max_width = width;
-            }
-        }
-    }
+pub fn calculate_max_content_width(editor: &Editor, char_width: f32) -> f32 {
+    let max_width = editor.current_buffer().max_line_width_with_virtual_text() as f32 * char_width;
 
     // Add padding to prevent clipping
     max_width + char_width * 2.0
 }
 
+/// Convert a color from icedit-core into an iced color
+pub fn rgba_to_color(color: Rgba) -> Color {
+    Color::from_rgba(color.r, color.g, color.b, color.a)
+}
+
 /// Calculate character dimensions for a given font size
 /// Uses improved calculations based on typical monospace font characteristics
 ///
@@ -192,6 +180,131 @@ pub fn calculate_column_range_width(
     end_x - start_x
 }
 
+/// Break text into lines of at most `max_columns` chars, at spaces where possible
+///
+/// Existing line breaks are kept; words longer than a line are split.
+pub fn wrap_words(text: &str, max_columns: usize) -> Vec<String> {
+    let max_columns = max_columns.max(1);
+    let mut lines = Vec::new();
+
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        let mut line_len = 0;
+        for word in paragraph.split(' ') {
+            let word_len = word.chars().count();
+            if line_len > 0 && line_len + 1 + word_len > max_columns {
+                lines.push(std::mem::take(&mut line));
+                line_len = 0;
+            }
+            if line_len > 0 {
+                line.push(' ');
+                line_len += 1;
+            }
+
+            let mut chars = word.chars().peekable();
+            while chars.peek().is_some() {
+                if line_len == max_columns {
+                    lines.push(std::mem::take(&mut line));
+                    line_len = 0;
+                }
+                let take = max_columns - line_len;
+                line.extend(chars.by_ref().take(take));
+                line_len = line.chars().count();
+            }
+        }
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Get the inline virtual text on a line as `(column, width)` pairs, ordered by column
+pub fn inline_virtual_text_widths(
+    editor: &Editor,
+    line: usize,
+    char_width: f32,
+) -> Vec<(usize, f32)> {
+    editor
+        .current_buffer()
+        .virtual_text_on_line(line)
+        .iter()
+        .filter(|span| span.placement == VirtualTextPlacement::Inline)
+        .map(|span| {
+            (
+                span.position.column,
+                span.text.chars().count() as f32 * char_width,
+            )
+        })
+        .collect()
+}
+
+/// Calculate the width of the inline virtual text drawn before a column
+///
+/// With `inclusive`, virtual text attached to the column itself is counted too. That
+/// is where the char at the column is drawn, while a cursor at the column stays in
+/// front of the virtual text.
+pub fn virtual_text_offset(column: usize, virtual_text: &[(usize, f32)], inclusive: bool) -> f32 {
+    virtual_text
+        .iter()
+        .take_while(|(text_column, _)| {
+            *text_column < column || (inclusive && *text_column == column)
+        })
+        .map(|(_, width)| width)
+        .sum()
+}
+
+/// Calculate the X position of a column, accounting for tabs and inline virtual text
+pub fn column_x_with_virtual_text(
+    column: usize,
+    line_content: &str,
+    char_width: f32,
+    virtual_text: &[(usize, f32)],
+) -> f32 {
+    calculate_column_x_position(column, line_content, char_width)
+        + virtual_text_offset(column, virtual_text, false)
+}
+
+/// Convert a visual X position to a column, skipping over inline virtual text
+///
+/// Positions on top of virtual text map to the column it is attached to, so the
+/// virtual text behaves as if it were not there.
+pub fn x_to_column_with_virtual_text(
+    x_position: f32,
+    line_content: &str,
+    char_width: f32,
+    virtual_text: &[(usize, f32)],
+) -> usize {
+    let mut offset = 0.0;
+    for &(column, width) in virtual_text {
+        let start = calculate_column_x_position(column, line_content, char_width) + offset;
+        if x_position < start {
+            break;
+        }
+        if x_position < start + width {
+            return column;
+        }
+        offset += width;
+    }
+
+    x_position_to_column(x_position - offset, line_content, char_width)
+}
+
+/// Calculate the X position of a buffer position within its line
+///
+/// Accounts for tabs and inline virtual text, so the result matches where the
+/// renderer draws the cursor.
+pub fn position_x(editor: &Editor, position: Position, char_width: f32) -> f32 {
+    match editor.current_buffer().rope().get_line(position.line) {
+        Some(line) => column_x_with_virtual_text(
+            position.column,
+            &line.to_string(),
+            char_width,
+            &inline_virtual_text_widths(editor, position.line, char_width),
+        ),
+        None => position.column as f32 * char_width,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -345,4 +458,46 @@ mod tests {
         assert_eq!(calculate_column_x_position(3, line2, char_width), 72.0); // third tab
         assert_eq!(calculate_column_x_position(4, line2, char_width), 96.0); // 'b'
     }
+
+    #[test]
+    fn test_wrap_words() {
+        assert_eq!(
+            wrap_words("Returns the sum of both values\n\nPanics never", 12),
+            vec!["Returns the", "sum of both", "values", "", "Panics never"]
+        );
+        assert_eq!(wrap_words("abcdefgh", 3), vec!["abc", "def", "gh"]);
+    }
+
+    #[test]
+    fn test_virtual_text_is_skipped() {
+        let char_width = 8.0;
+        let line = "let x = 1;";
+        // ": i32" after `x`, five chars wide
+        let virtual_text = [(5, 40.0)];
+
+        // The cursor at the hint's column stays in front of it; later columns move right
+        assert_eq!(
+            column_x_with_virtual_text(5, line, char_width, &virtual_text),
+            40.0
+        );
+        assert_eq!(
+            column_x_with_virtual_text(6, line, char_width, &virtual_text),
+            88.0
+        );
+        assert_eq!(virtual_text_offset(5, &virtual_text, true), 40.0);
+
+        // Clicks on the hint land on its column, clicks after it skip its width
+        assert_eq!(
+            x_to_column_with_virtual_text(36.0, line, char_width, &virtual_text),
+            4
+        );
+        assert_eq!(
+            x_to_column_with_virtual_text(60.0, line, char_width, &virtual_text),
+            5
+        );
+        assert_eq!(
+            x_to_column_with_virtual_text(90.0, line, char_width, &virtual_text),
+            6
+        );
+    }
 }
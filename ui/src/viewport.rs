@@ -30,6 +30,8 @@ pub struct Viewport {
     pub visible_lines: (usize, usize),
     /// Information about partially visible lines at top and bottom
     pub partial_lines: Vec<PartialLineView>,
+    /// Rows of virtual lines shown after a line, as `(line, count)`
+    pub virtual_lines: Option<(usize, usize)>,
 }
 
 impl Viewport {
@@ -41,6 +43,7 @@ impl Viewport {
             line_height: 18.0,
             visible_lines: (0, 0),
             partial_lines: Vec::new(),
+            virtual_lines: None,
         }
     }
 
@@ -63,6 +66,34 @@ impl Viewport {
         self.update_visible_lines();
     }
 
+    /// Make room for rows of virtual lines after a line, as `(line, count)`
+    ///
+    /// The buffer lines below are pushed down by `count` rows.
+    pub fn set_virtual_lines(&mut self, virtual_lines: Option<(usize, usize)>) {
+        if self.virtual_lines != virtual_lines {
+            self.virtual_lines = virtual_lines;
+            self.update_visible_lines();
+        }
+    }
+
+    /// Number of rows taken by virtual lines
+    pub fn virtual_row_count(&self) -> usize {
+        self.virtual_lines.map_or(0, |(_, count)| count)
+    }
+
+    /// Total height of `line_count` buffer lines plus the rows of virtual lines
+    pub fn content_height(&self, line_count: usize) -> f32 {
+        (line_count + self.virtual_row_count()) as f32 * self.line_height
+    }
+
+    /// Get the buffer line shown in a row, or `None` for a row of virtual lines
+    fn line_at_row(&self, row: usize) -> Option<usize> {
+        match self.virtual_lines {
+            Some((line, count)) if row > line => row.checked_sub(count).filter(|&l| l > line),
+            _ => Some(row),
+        }
+    }
+
     /// Calculate which lines are visible based on scroll and viewport, including partial lines
     fn update_visible_lines(&mut self) {
         let scroll_y = self.scroll_offset.1;
@@ -79,48 +110,26 @@ impl Viewport {
         let viewport_top = scroll_y;
         let viewport_bottom = scroll_y + viewport_height;
 
-        // Calculate the exact line indices that intersect with the viewport
-        let first_line_f = scroll_y / line_height;
-        let last_line_f = (scroll_y + viewport_height) / line_height;
+        // Calculate the exact rows that intersect with the viewport
+        let start_row = (scroll_y / line_height).floor() as usize;
+        let end_row = ((scroll_y + viewport_height) / line_height).ceil() as usize;
 
-        let start_line = first_line_f.floor() as usize;
-        let end_line = last_line_f.ceil() as usize;
-
-        self.visible_lines = (start_line, end_line);
         self.partial_lines.clear();
-
-        // Optimization: Only process lines that actually need partial calculations
-        // Most lines are either fully visible or not visible at all
-
-        if start_line >= end_line {
-            return;
-        }
-
-        // Check first line - might be partially clipped at top
-        let first_line_y_top = start_line as f32 * line_height;
-        let first_line_y_bottom = first_line_y_top + line_height;
-
-        if first_line_y_bottom > viewport_top && first_line_y_top < viewport_bottom {
-            let y_offset = first_line_y_top - viewport_top;
-            let clip_top = if first_line_y_top < viewport_top {
-                viewport_top - first_line_y_top
-            } else {
-                0.0
-            };
-
-            let clip_bottom = if first_line_y_bottom > viewport_bottom {
-                first_line_y_bottom - viewport_bottom
-            } else {
-                0.0
+        for row in start_row..end_row {
+            let Some(line_index) = self.line_at_row(row) else {
+                continue;
             };
 
-            let visible_height = line_height - clip_top - clip_bottom;
-            let visible_fraction = visible_height / line_height;
+            // Only the first and last rows can be clipped
+            let line_y_top = row as f32 * line_height;
+            let clip_top = (viewport_top - line_y_top).max(0.0);
+            let clip_bottom = (line_y_top + line_height - viewport_bottom).max(0.0);
+            let visible_fraction = (line_height - clip_top - clip_bottom) / line_height;
 
             if visible_fraction > 0.0 {
                 self.partial_lines.push(PartialLineView {
-                    line_index: start_line,
-                    y_offset,
+                    line_index,
+                    y_offset: line_y_top - viewport_top,
                     visible_fraction,
                     clip_top,
                     clip_bottom,
@@ -128,54 +137,10 @@ impl Viewport {
             }
         }
 
-        // Add fully visible lines in between (if any)
-        for line_idx in (start_line + 1)..(end_line.saturating_sub(1)) {
-            let line_y_top = line_idx as f32 * line_height;
-            let y_offset = line_y_top - viewport_top;
-
-            self.partial_lines.push(PartialLineView {
-                line_index: line_idx,
-                y_offset,
-                visible_fraction: 1.0, // Fully visible
-                clip_top: 0.0,
-                clip_bottom: 0.0,
-            });
-        }
-
-        // Check last line - might be partially clipped at bottom (if different from first line)
-        if end_line > start_line + 1 {
-            let last_line_idx = end_line - 1;
-            let last_line_y_top = last_line_idx as f32 * line_height;
-            let last_line_y_bottom = last_line_y_top + line_height;
-
-            if last_line_y_bottom > viewport_top && last_line_y_top < viewport_bottom {
-                let y_offset = last_line_y_top - viewport_top;
-                let clip_top = if last_line_y_top < viewport_top {
-                    viewport_top - last_line_y_top
-                } else {
-                    0.0
-                };
-
-                let clip_bottom = if last_line_y_bottom > viewport_bottom {
-                    last_line_y_bottom - viewport_bottom
-                } else {
-                    0.0
-                };
-
-                let visible_height = line_height - clip_top - clip_bottom;
-                let visible_fraction = visible_height / line_height;
-
-                if visible_fraction > 0.0 {
-                    self.partial_lines.push(PartialLineView {
-                        line_index: last_line_idx,
-                        y_offset,
-                        visible_fraction,
-                        clip_top,
-                        clip_bottom,
-                    });
-                }
-            }
-        }
+        self.visible_lines = match (self.partial_lines.first(), self.partial_lines.last()) {
+            (Some(first), Some(last)) => (first.line_index, last.line_index + 1),
+            _ => (start_row, start_row),
+        };
     }
 
     /// Check if a line is currently visible
@@ -208,7 +173,7 @@ impl Viewport {
     /// Get scroll bounds to prevent over-scrolling
     pub fn clamp_scroll_offset(&self, offset: (f32, f32), content_lines: usize) -> (f32, f32) {
         let (x, y) = offset;
-        let content_height = content_lines as f32 * self.line_height;
+        let content_height = self.content_height(content_lines);
 
         let clamped_x = x.max(0.0);
         let clamped_y = if content_height > self.size.1 {
@@ -226,3 +191,51 @@ impl Default for Viewport {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_virtual_lines_push_lines_down() {
+        let mut viewport = Viewport::new();
+        viewport.set_char_dimensions(8.0, 10.0);
+        viewport.set_size(100.0, 60.0);
+        viewport.set_virtual_lines(Some((1, 2)));
+
+        // Rows 2 and 3 are left free for the virtual lines
+        let lines: Vec<(usize, f32)> = viewport
+            .partial_lines
+            .iter()
+            .map(|line| (line.line_index, line.y_offset))
+            .collect();
+        assert_eq!(lines, vec![(0, 0.0), (1, 10.0), (2, 40.0), (3, 50.0)]);
+        assert_eq!(viewport.visible_lines, (0, 4));
+
+        viewport.set_virtual_lines(None);
+        assert_eq!(viewport.partial_lines.len(), 6);
+        assert_eq!(viewport.partial_lines[2].y_offset, 20.0);
+    }
+
+    #[test]
+    fn test_scroll_to_bottom_with_virtual_lines_on_last_line() {
+        let mut viewport = Viewport::new();
+        viewport.set_char_dimensions(8.0, 10.0);
+        viewport.set_size(100.0, 40.0);
+        viewport.set_virtual_lines(Some((9, 3)));
+
+        // Ten lines plus three virtual rows can scroll 90px, not 60px
+        assert_eq!(viewport.content_height(10), 130.0);
+        let (_, y) = viewport.clamp_scroll_offset((0.0, f32::MAX), 10);
+        assert_eq!(y, 90.0);
+
+        // The last line sits on top of its virtual rows at the bottom
+        viewport.set_scroll_offset(0.0, y);
+        let lines: Vec<(usize, f32)> = viewport
+            .partial_lines
+            .iter()
+            .map(|line| (line.line_index, line.y_offset))
+            .collect();
+        assert_eq!(lines, vec![(9, 0.0)]);
+    }
+}
@@ -1,4 +1,9 @@
-use crate::{renderer::EditorRenderer, utils, Viewport};
+use crate::{
+    context_menu::{ContextMenu, ContextMenuAction, ContextMenuStyle},
+    minimap::{Minimap, MinimapCache, MinimapLayout},
+    renderer::{EditorRenderer, OverviewMarker, OverviewMarkerKind, RendererStyle},
+    utils, Viewport,
+};
 use iced::{
     advanced::{
         layout::{self, Layout},
@@ -6,11 +11,16 @@ use iced::{
         widget::Tree,
         Clipboard, Shell, Widget,
     },
-    mouse, Color, Element, Event, Font, Length, Point, Rectangle, Size, Theme, Vector,
+    mouse,
+    time::{Duration, Instant},
+    window, Color, Element, Event, Font, Length, Point, Rectangle, Size, Theme, Vector,
 };
 use icedit_core::{
-    Editor, EditorMessage, Key, KeyEvent, Modifiers, NamedKey, Position, Selection, ShortcutManager,
+    ChordState, CursorStyle, Editor, EditorMessage, Key, KeyContext, KeyEvent, Modifiers, NamedKey,
+    Position, Selection, ShortcutManager, Vim,
 };
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
 
 /// State that should be passed from outside to the widget
 #[derive(Debug, Clone)]
@@ -64,6 +74,26 @@ struct WidgetState {
     auto_scroll_delta: Vector,
     /// Whether auto-scrolling is currently active
     is_auto_scrolling: bool,
+    /// Whether the minimap viewport rectangle is being dragged
+    is_dragging_minimap: bool,
+    /// Per-line minimap summaries, kept across frames
+    minimap_cache: RefCell<MinimapCache>,
+    /// Renderer kept across frames so its caches are not rebuilt on every draw
+    editor_renderer: RefCell<Option<EditorRenderer>>,
+    /// Text position under the mouse, used to show diagnostic popups
+    hover_position: Option<Position>,
+    /// When the mouse came to rest on `hover_position`, until a hover event is emitted for it
+    hover_since: Option<Instant>,
+    /// Where the open tooltip was last drawn, so it stays open while the mouse is over it
+    tooltip_bounds: Cell<Option<Rectangle>>,
+    /// Where the open context menu was requested, in screen coordinates
+    context_menu_origin: Option<Point>,
+    /// Context menu entry under the mouse
+    context_menu_hovered: Option<usize>,
+    /// Keys typed so far towards a multi-key shortcut
+    chord: ChordState,
+    /// Mode, registers and pending keys of the Vim layer
+    vim: Vim,
 }
 
 impl<Message, Theme, Renderer> Widget<Message, Theme, Renderer> for EditorWidget<'_, Message>
@@ -108,25 +138,123 @@ where
     ) {
         let bounds = layout.bounds();
         let widget_state = tree.state.downcast_ref::<WidgetState>();
+        let viewport = self.suggestion_viewport(&widget_state.viewport);
+
+        let style = RendererStyle {
+            font_size: self.font_size,
+            line_height: self.line_height,
+            char_width: self.char_width,
+            background_color: self.background_color,
+            text_color: self.text_color,
+            cursor_color: self.cursor_color,
+            selection_color: self.selection_color,
+            gutter_width: self.calculate_gutter_width(),
+            gutter_background_color: self.gutter_background_color,
+            line_number_color: self.line_number_color,
+            current_line_number_color: self.current_line_number_color,
+            gutter_padding: self.gutter_padding,
+            minimap_width: self.minimap_width(),
+        };
+        let cursor_style = if self.vim_mode {
+            widget_state.vim.mode().cursor_style()
+        } else if self.editor.is_overwrite() {
+            self.overwrite_cursor_style
+        } else {
+            self.cursor_style
+        };
 
-        // Create renderer with current styling
-        let mut editor_renderer = EditorRenderer::new(
-            self.font_size,
-            self.line_height,
-            self.char_width,
-            self.background_color,
-            self.text_color,
-            self.cursor_color,
-            self.selection_color,
-            self.calculate_gutter_width(),
-            self.gutter_background_color,
-            self.line_number_color,
-            self.current_line_number_color,
-            self.gutter_padding,
-        );
+        // Reuse the renderer from the previous frame so its caches survive,
+        // unless the styling changed since then
+        let mut cached_renderer = widget_state.editor_renderer.borrow_mut();
+        let editor_renderer = match &mut *cached_renderer {
+            Some(editor_renderer) if editor_renderer.has_style(&style) => editor_renderer,
+            slot => slot.insert(EditorRenderer::with_style(style)),
+        };
+        editor_renderer.set_cursor_style(cursor_style);
 
         // Render the editor content
-        editor_renderer.render(self.editor, &widget_state.viewport, renderer, bounds);
+        let overview_markers = self.collect_overview_markers();
+        editor_renderer.render(self.editor, &viewport, renderer, bounds, &overview_markers);
+
+        if let Some(suggestion) = self.editor.inline_suggestion() {
+            editor_renderer.draw_inline_suggestion(renderer, suggestion, &viewport);
+        }
+
+        if let Some(minimap_bounds) = self.minimap_bounds(bounds) {
+            let layout = MinimapLayout::new(
+                minimap_bounds,
+                self.editor.current_buffer().line_count(),
+                &viewport,
+            );
+            Minimap::new(self.background_color, self.text_color).draw(
+                renderer,
+                &layout,
+                self.editor,
+                &viewport,
+                &mut widget_state.minimap_cache.borrow_mut(),
+            );
+        }
+
+        let status = widget_state
+            .chord
+            .status()
+            .or_else(|| self.vim_mode.then(|| widget_state.vim.status()).flatten());
+        if let Some(status) = status {
+            editor_renderer.draw_key_status(renderer, &status, bounds);
+        }
+
+        // An open context menu takes the place of popups
+        widget_state.tooltip_bounds.set(None);
+        if let Some(origin) = widget_state.context_menu_origin {
+            let layout =
+                self.context_menu
+                    .layout(origin, bounds, self.char_width, self.line_height);
+            let style = ContextMenuStyle {
+                background: self.background_color,
+                border: self.line_number_color,
+                text: self.text_color,
+                hovered: self.selection_color,
+                font_size: self.font_size,
+            };
+            self.context_menu
+                .draw(renderer, &layout, widget_state.context_menu_hovered, style);
+            return;
+        }
+
+        // The completion popup takes the place of other popups while it is open
+        if let Some(session) = self.editor.completion() {
+            editor_renderer.draw_completion_popup(
+                renderer,
+                session,
+                self.editor,
+                &viewport,
+                bounds,
+            );
+            return;
+        }
+
+        if let Some(tooltip) = self.editor.tooltip() {
+            let tooltip_bounds =
+                editor_renderer.draw_tooltip(renderer, tooltip, self.editor, &viewport, bounds);
+            widget_state.tooltip_bounds.set(Some(tooltip_bounds));
+            return;
+        }
+
+        // Diagnostic under the mouse, or else under the cursor
+        let buffer = self.editor.current_buffer();
+        if let Some(diagnostic) = widget_state
+            .hover_position
+            .and_then(|position| buffer.diagnostic_at(position))
+            .or_else(|| buffer.diagnostic_at(self.editor.current_cursor().position()))
+        {
+            editor_renderer.draw_diagnostic_popup(
+                renderer,
+                &diagnostic,
+                self.editor,
+                &viewport,
+                bounds,
+            );
+        }
     }
 
     fn update(
@@ -144,12 +272,15 @@ where
         let bounds = layout.bounds();
 
         // Check if viewport needs to be initialized or updated
-        if widget_state.viewport_bounds.size() != bounds.size() {
+        let text_width = bounds.width - self.minimap_width();
+        if widget_state.viewport_bounds.size() != bounds.size()
+            || widget_state.viewport.size.0 != text_width
+        {
             widget_state.viewport_bounds = bounds;
             widget_state
                 .viewport
                 .set_char_dimensions(self.char_width, self.line_height);
-            widget_state.viewport.set_size(bounds.width, bounds.height);
+            widget_state.viewport.set_size(text_width, bounds.height);
         }
 
         // Handle continuous auto-scrolling if we're dragging outside bounds
@@ -159,11 +290,43 @@ where
             shell.request_redraw();
         }
 
+        // An open context menu gets the first look at events
+        if widget_state.context_menu_origin.is_some()
+            && self.update_context_menu(widget_state, event, cursor, bounds, shell)
+        {
+            return;
+        }
+
         match event {
             Event::Mouse(mouse_event) => {
                 match mouse_event {
+                    mouse::Event::ButtonPressed(mouse::Button::Right) => {
+                        if let Some(point) = cursor.position_in(bounds) {
+                            self.open_context_menu(widget_state, point, cursor, shell);
+                        }
+                    }
                     mouse::Event::ButtonPressed(mouse::Button::Left) => {
-                        if let Some(position) = cursor.position_in(bounds) {
+                        // Clicking an overview ruler marker jumps to it instead of placing the cursor
+                        if let Some(marker) = cursor
+                            .position()
+                            .and_then(|point| self.overview_marker_at(point, bounds))
+                        {
+                            self.jump_to_marker(widget_state, &marker, bounds, shell);
+                        } else if let Some(minimap_bounds) = cursor.position().and_then(|point| {
+                            self.minimap_bounds(bounds).filter(|mb| mb.contains(point))
+                        }) {
+                            // Start dragging the minimap viewport
+                            widget_state.is_dragging_minimap = true;
+                            if let Some(point) = cursor.position() {
+                                self.scroll_minimap_to(
+                                    widget_state,
+                                    minimap_bounds,
+                                    point.y,
+                                    bounds,
+                                );
+                                shell.request_redraw();
+                            }
+                        } else if let Some(position) = cursor.position_in(bounds) {
                             let editor_position =
                                 self.point_to_position(position, &widget_state.viewport);
 
@@ -182,6 +345,7 @@ where
                         }
                     }
                     mouse::Event::ButtonReleased(mouse::Button::Left) => {
+                        widget_state.is_dragging_minimap = false;
                         widget_state.is_dragging = false;
                         widget_state.drag_start_position = None;
                         widget_state.current_mouse_position = None;
@@ -189,8 +353,21 @@ where
                     }
                     mouse::Event::CursorMoved { .. } => {
                         widget_state.current_mouse_position = cursor.position();
-
-                        if widget_state.is_dragging {
+                        self.update_hover(widget_state, cursor, bounds, shell);
+
+                        if widget_state.is_dragging_minimap {
+                            if let (Some(minimap_bounds), Some(point)) =
+                                (self.minimap_bounds(bounds), cursor.position())
+                            {
+                                self.scroll_minimap_to(
+                                    widget_state,
+                                    minimap_bounds,
+                                    point.y,
+                                    bounds,
+                                );
+                                shell.request_redraw();
+                            }
+                        } else if widget_state.is_dragging {
                             if let Some(position) = cursor.position_in(bounds) {
                                 // Mouse is within bounds - stop auto-scrolling
                                 widget_state.is_auto_scrolling = false;
@@ -214,6 +391,9 @@ where
                             }
                         }
                     }
+                    mouse::Event::CursorLeft => {
+                        self.update_hover(widget_state, cursor, bounds, shell);
+                    }
                     mouse::Event::WheelScrolled { delta } => {
                         if cursor.is_over(bounds) {
                             let scroll_delta = match delta {
@@ -231,10 +411,7 @@ where
                             );
 
                             // Clamp scroll offset to reasonable bounds
-                            let buffer = self.editor.current_buffer();
-                            let line_count = buffer.line_count();
-                            let content_height = line_count as f32 * self.line_height;
-                            let max_scroll_y = (content_height - bounds.height).max(0.0);
+                            let max_scroll_y = (self.content_height() - bounds.height).max(0.0);
                             let max_content_width = self.calculate_max_content_width();
                             let max_scroll_x = (max_content_width - bounds.width).max(0.0);
 
@@ -256,27 +433,48 @@ where
             }
             Event::Keyboard(keyboard_event) => match keyboard_event {
                 iced::keyboard::Event::KeyPressed { key, modifiers, .. } => {
-                    if let Some(editor_message) = self.handle_keyboard_input(key, modifiers) {
-                        // Check if this is a cursor movement command that should ensure cursor visibility
-                        let should_ensure_cursor_visible =
-                            self.is_cursor_movement_command(&editor_message);
+                    if self.handle_vim_key(widget_state, key, modifiers, bounds, shell) {
+                        return;
+                    }
 
-                        let message = (self.on_message)(editor_message);
-                        shell.publish(message);
+                    let was_pending = widget_state.chord.is_pending();
+                    let editor_messages =
+                        self.handle_keyboard_input(&mut widget_state.chord, key, modifiers);
+                    if let Some(expires_at) = widget_state.chord.expires_at() {
+                        shell.request_redraw_at(expires_at);
+                    } else if was_pending {
+                        shell.request_redraw();
+                    }
 
-                        // After publishing the editor message, ensure cursor is visible if needed
-                        if should_ensure_cursor_visible {
-                            self.ensure_cursor_visible(widget_state, bounds, shell);
-                        }
+                    for editor_message in editor_messages {
+                        self.publish_key_message(editor_message, widget_state, bounds, shell);
                     }
                 }
                 _ => {}
             },
+            Event::Window(window::Event::RedrawRequested(now)) => {
+                self.emit_hover(widget_state, *now, shell);
+
+                // A chord that timed out runs the binding of the keys typed so far
+                if widget_state.chord.is_expired(*now) {
+                    let context = self.key_context.clone().with_editor(self.editor);
+                    if let Some(editor_message) = self
+                        .shortcut_manager
+                        .flush_chord(&mut widget_state.chord, &context)
+                    {
+                        self.publish_key_message(editor_message, widget_state, bounds, shell);
+                    }
+                    shell.request_redraw();
+                }
+            }
             _ => {}
         }
     }
 }
 
+/// Builds the message emitted on right click from the clicked position and selection
+type ContextMenuRequest<Message> = Box<dyn Fn(Position, Option<Selection>) -> Message>;
+
 /// The editor widget that renders text using custom drawing
 ///
 /// This widget automatically ensures the cursor remains visible when moving
@@ -292,15 +490,27 @@ pub struct EditorWidget<'a, Message> {
     cursor_color: Color,
     selection_color: Color,
     shortcut_manager: ShortcutManager,
+    key_context: KeyContext,
+    vim_mode: bool,
+    on_vim_command: Option<Box<dyn Fn(String) -> Message>>,
+    cursor_style: CursorStyle,
+    overwrite_cursor_style: CursorStyle,
     on_message: Box<dyn Fn(EditorMessage) -> Message>,
+    on_hover: Option<Box<dyn Fn(Position) -> Message>>,
+    hover_delay: Duration,
+    context_menu: ContextMenu<Message>,
+    on_context_menu: Option<ContextMenuRequest<Message>>,
     gutter_background_color: Color,
     line_number_color: Color,
     current_line_number_color: Color,
     gutter_padding: f32,
+    overview_annotations: Vec<OverviewMarker>,
+    show_minimap: bool,
 }
 
 impl<'a, Message> EditorWidget<'a, Message> {
     const DEFAULT_FONT_SIZE: f32 = 14.0;
+    const DEFAULT_HOVER_DELAY: Duration = Duration::from_millis(500);
 
     pub fn new<F>(editor: &'a Editor, on_message: F) -> Self
     where
@@ -318,11 +528,22 @@ impl<'a, Message> EditorWidget<'a, Message> {
             cursor_color: Color::from_rgb(1.0, 1.0, 1.0),
             selection_color: Color::from_rgba(0.3, 0.5, 1.0, 0.3),
             shortcut_manager: ShortcutManager::new(),
+            key_context: KeyContext::new(),
+            vim_mode: false,
+            on_vim_command: None,
+            cursor_style: CursorStyle::Line,
+            overwrite_cursor_style: CursorStyle::Block,
             on_message: Box::new(on_message),
+            on_hover: None,
+            hover_delay: Self::DEFAULT_HOVER_DELAY,
+            context_menu: ContextMenu::standard(),
+            on_context_menu: None,
             gutter_background_color: Color::from_rgba(0.2, 0.2, 0.2, 0.0),
             line_number_color: Color::from_rgb(0.7, 0.7, 0.7),
             current_line_number_color: Color::from_rgb(1.0, 0.8, 0.2),
             gutter_padding: 8.0,
+            overview_annotations: Vec::new(),
+            show_minimap: false,
         };
 
         widget
@@ -365,6 +586,164 @@ impl<'a, Message> EditorWidget<'a, Message> {
         self
     }
 
+    /// Add app-provided markers (errors, bookmarks...) to the overview ruler
+    pub fn overview_annotations(mut self, markers: Vec<OverviewMarker>) -> Self {
+        self.overview_annotations = markers;
+        self
+    }
+
+    /// Emit a message with the text position under the mouse once it rests there
+    ///
+    /// Apps can answer with [`EditorMessage::ShowTooltip`], e.g. with hover docs
+    /// from a language server.
+    pub fn on_hover<F>(mut self, on_hover: F) -> Self
+    where
+        F: Fn(Position) -> Message + 'static,
+    {
+        self.on_hover = Some(Box::new(on_hover));
+        self
+    }
+
+    /// Set how long the mouse must rest on a position before a hover event is emitted
+    pub fn hover_delay(mut self, delay: Duration) -> Self {
+        self.hover_delay = delay;
+        self
+    }
+
+    /// Use custom key bindings, including multi-key chords
+    pub fn shortcut_manager(mut self, shortcut_manager: ShortcutManager) -> Self {
+        self.shortcut_manager = shortcut_manager;
+        self
+    }
+
+    /// Set app-defined context keys and the read-only flag for conditional key bindings
+    ///
+    /// Editor-derived flags, such as whether the completion popup is open, are
+    /// filled in by the widget on each key.
+    pub fn key_context(mut self, key_context: KeyContext) -> Self {
+        self.key_context = key_context;
+        self
+    }
+
+    /// Handle keys with the Vim layer before the key bindings
+    ///
+    /// Keys typed in insert mode, and Ctrl shortcuts Vim does not use, still go
+    /// through the key bindings. The cursor is drawn as a block in normal mode.
+    pub fn vim_mode(mut self, enabled: bool) -> Self {
+        self.vim_mode = enabled;
+        self
+    }
+
+    /// Emit a message for `:` commands the Vim layer does not run itself, such as `:w`
+    pub fn on_vim_command<F>(mut self, on_vim_command: F) -> Self
+    where
+        F: Fn(String) -> Message + 'static,
+    {
+        self.on_vim_command = Some(Box::new(on_vim_command));
+        self
+    }
+
+    /// Set how the cursor is drawn when Vim mode is off
+    pub fn cursor_style(mut self, style: CursorStyle) -> Self {
+        self.cursor_style = style;
+        self
+    }
+
+    /// Set how the cursor is drawn in overwrite mode, a block by default
+    pub fn overwrite_cursor_style(mut self, style: CursorStyle) -> Self {
+        self.overwrite_cursor_style = style;
+        self
+    }
+
+    /// Set the entries shown on right click, replacing the standard ones
+    ///
+    /// Pass an empty menu to only emit [`Self::on_context_menu`] requests.
+    pub fn context_menu(mut self, menu: ContextMenu<Message>) -> Self {
+        self.context_menu = menu;
+        self
+    }
+
+    /// Emit a message with the clicked position and the selection on right click
+    pub fn on_context_menu<F>(mut self, on_context_menu: F) -> Self
+    where
+        F: Fn(Position, Option<Selection>) -> Message + 'static,
+    {
+        self.on_context_menu = Some(Box::new(on_context_menu));
+        self
+    }
+
+    /// Show a scaled-down overview of the whole buffer on the right side
+    pub fn minimap(mut self, enabled: bool) -> Self {
+        self.show_minimap = enabled;
+        self
+    }
+
+    /// Width taken by the minimap column, or 0.0 when it is hidden
+    fn minimap_width(&self) -> f32 {
+        if self.show_minimap {
+            Minimap::DEFAULT_WIDTH
+        } else {
+            0.0
+        }
+    }
+
+    /// Calculate the minimap column bounds (left of the vertical scrollbar)
+    fn minimap_bounds(&self, bounds: Rectangle) -> Option<Rectangle> {
+        if !self.show_minimap {
+            return None;
+        }
+
+        let scrollbar_width = if self.vertical_track_bounds(bounds).is_some() {
+            EditorRenderer::SCROLLBAR_WIDTH
+        } else {
+            0.0
+        };
+        let horizontal_visible =
+            self.calculate_max_content_width() > bounds.width - self.minimap_width();
+        let height = bounds.height
+            - if horizontal_visible {
+                EditorRenderer::SCROLLBAR_WIDTH
+            } else {
+                0.0
+            };
+
+        Some(Rectangle::new(
+            Point::new(
+                bounds.x + bounds.width - scrollbar_width - Minimap::DEFAULT_WIDTH,
+                bounds.y,
+            ),
+            Size::new(Minimap::DEFAULT_WIDTH, height),
+        ))
+    }
+
+    /// Scroll so that the buffer line under the minimap Y coordinate is centered
+    fn scroll_minimap_to(
+        &self,
+        widget_state: &mut WidgetState,
+        minimap_bounds: Rectangle,
+        y: f32,
+        bounds: Rectangle,
+    ) {
+        let layout = MinimapLayout::new(
+            minimap_bounds,
+            self.editor.current_buffer().line_count(),
+            &widget_state.viewport,
+        );
+        let line = layout.line_at(y);
+        self.center_on_line(widget_state, line, bounds);
+    }
+
+    /// Scroll vertically so that a line sits in the middle of the viewport
+    fn center_on_line(&self, widget_state: &mut WidgetState, line: usize, bounds: Rectangle) {
+        let max_scroll_y = (self.content_height() - bounds.height).max(0.0);
+        let target_y = line as f32 * self.line_height - bounds.height / 2.0;
+
+        widget_state.viewport.set_scroll_offset(
+            widget_state.viewport.scroll_offset.0,
+            target_y.max(0.0).min(max_scroll_y),
+        );
+    }
+
     /// Calculate the gutter width based on the number of lines in the editor
     pub fn calculate_gutter_width(&self) -> f32 {
         // Auto-calculate based on line count
@@ -393,11 +772,300 @@ impl<'a, Message> EditorWidget<'a, Message> {
 
     /// Calculate the maximum content width for horizontal scroll limiting
     fn calculate_max_content_width(&self) -> f32 {
-        utils::calculate_max_content_width(self.editor, self.char_width, 1000)
+        utils::calculate_max_content_width(self.editor, self.char_width)
+    }
+
+    /// Collect the markers shown on the vertical scrollbar track
+    fn collect_overview_markers(&self) -> Vec<OverviewMarker> {
+        let search_results = self.editor.search_results();
+        let mut markers =
+            Vec::with_capacity(search_results.len() + self.overview_annotations.len() + 2);
+
+        if let Some(selection) = self.editor.current_selection() {
+            if !selection.is_empty() {
+                markers.push(
+                    OverviewMarker::new(OverviewMarkerKind::Selection, selection.start)
+                        .spanning(selection.end.line),
+                );
+            }
+        }
+
+        markers.extend(
+            search_results
+                .iter()
+                .map(|position| OverviewMarker::new(OverviewMarkerKind::SearchMatch, *position)),
+        );
+        markers.extend(self.overview_annotations.iter().copied());
+
+        // Cursor marker last so it is drawn on top
+        markers.push(OverviewMarker::new(
+            OverviewMarkerKind::Cursor,
+            self.editor.current_cursor().position(),
+        ));
+
+        markers
+    }
+
+    /// Calculate the vertical scrollbar track bounds, if the vertical scrollbar is shown
+    fn vertical_track_bounds(&self, bounds: Rectangle) -> Option<Rectangle> {
+        if self.content_height() <= bounds.height {
+            return None;
+        }
+
+        let horizontal_visible =
+            self.calculate_max_content_width() > bounds.width - self.minimap_width();
+        let track_height = bounds.height
+            - if horizontal_visible {
+                EditorRenderer::SCROLLBAR_WIDTH
+            } else {
+                0.0
+            };
+
+        Some(Rectangle::new(
+            Point::new(
+                bounds.x + bounds.width - EditorRenderer::SCROLLBAR_WIDTH,
+                bounds.y,
+            ),
+            Size::new(EditorRenderer::SCROLLBAR_WIDTH, track_height),
+        ))
+    }
+
+    /// Find the overview ruler marker under an absolute screen point
+    fn overview_marker_at(&self, point: Point, bounds: Rectangle) -> Option<OverviewMarker> {
+        let track_bounds = self.vertical_track_bounds(bounds)?;
+        if !track_bounds.contains(point) {
+            return None;
+        }
+
+        // Markers are tiny, so allow a little slack around them when hit-testing
+        const HIT_SLOP: f32 = 2.0;
+        let line_count = self.editor.current_buffer().line_count();
+
+        self.collect_overview_markers()
+            .into_iter()
+            .rev()
+            .filter(|marker| marker.kind != OverviewMarkerKind::Cursor)
+            .find(|marker| {
+                let marker_bounds =
+                    EditorRenderer::overview_marker_bounds(marker, track_bounds, line_count);
+                point.y >= marker_bounds.y - HIT_SLOP
+                    && point.y <= marker_bounds.y + marker_bounds.height + HIT_SLOP
+            })
+    }
+
+    /// Move the cursor to a marker and center its line in the viewport
+    fn jump_to_marker(
+        &self,
+        widget_state: &mut WidgetState,
+        marker: &OverviewMarker,
+        bounds: Rectangle,
+        shell: &mut Shell<'_, Message>,
+    ) {
+        let message = (self.on_message)(EditorMessage::MoveCursorTo(marker.position));
+        shell.publish(message);
+
+        self.center_on_line(widget_state, marker.position.line, bounds);
+        shell.request_redraw();
+    }
+
+    /// Track the text position under the mouse, redrawing when the hovered diagnostic changes
+    ///
+    /// Also restarts the hover delay when the position changes, and closes the open
+    /// tooltip once the mouse leaves both it and the text it is about.
+    fn update_hover(
+        &self,
+        widget_state: &mut WidgetState,
+        cursor: mouse::Cursor,
+        bounds: Rectangle,
+        shell: &mut Shell<'_, Message>,
+    ) {
+        let over_tooltip = widget_state
+            .tooltip_bounds
+            .get()
+            .zip(cursor.position())
+            .is_some_and(|(tooltip_bounds, point)| tooltip_bounds.contains(point));
+        if over_tooltip {
+            return;
+        }
+
+        let hover_position = cursor
+            .position_in(bounds)
+            .filter(|point| {
+                point.x >= self.calculate_gutter_width()
+                    && point.x < widget_state.viewport.size.0 - EditorRenderer::SCROLLBAR_WIDTH
+            })
+            .map(|point| self.point_to_position(point, &widget_state.viewport));
+
+        let buffer = self.editor.current_buffer();
+        let diagnostic_at = |position: Option<Position>| {
+            position.and_then(|position| buffer.diagnostic_at(position))
+        };
+        if diagnostic_at(hover_position) != diagnostic_at(widget_state.hover_position) {
+            shell.request_redraw();
+        }
+
+        if hover_position != widget_state.hover_position {
+            widget_state.hover_since = hover_position.map(|_| Instant::now());
+            if let Some(since) = widget_state.hover_since.filter(|_| self.on_hover.is_some()) {
+                shell.request_redraw_at(since + self.hover_delay);
+            }
+        }
+        widget_state.hover_position = hover_position;
+
+        if let Some(tooltip) = self.editor.tooltip() {
+            if !hover_position.is_some_and(|position| tooltip.covers(position)) {
+                shell.publish((self.on_message)(EditorMessage::HideTooltip));
+            }
+        }
+    }
+
+    /// Move the cursor to a right click outside the selection, then request and open the menu
+    fn open_context_menu(
+        &self,
+        widget_state: &mut WidgetState,
+        point: Point,
+        cursor: mouse::Cursor,
+        shell: &mut Shell<'_, Message>,
+    ) {
+        let position = self.point_to_position(point, &widget_state.viewport);
+        let selection = self
+            .editor
+            .current_selection()
+            .filter(|selection| !selection.is_empty() && selection.contains(position))
+            .cloned();
+        if selection.is_none() {
+            shell.publish((self.on_message)(EditorMessage::MoveCursorTo(position)));
+        }
+
+        if let Some(on_context_menu) = &self.on_context_menu {
+            shell.publish(on_context_menu(position, selection));
+        }
+
+        if !self.context_menu.is_empty() {
+            widget_state.context_menu_origin = cursor.position();
+            widget_state.context_menu_hovered = None;
+            shell.request_redraw();
+        }
+    }
+
+    /// Handle an event while the context menu is open, returning whether it was consumed
+    ///
+    /// Clicking an entry runs it; clicking elsewhere, scrolling or pressing a key
+    /// closes the menu.
+    fn update_context_menu(
+        &self,
+        widget_state: &mut WidgetState,
+        event: &Event,
+        cursor: mouse::Cursor,
+        bounds: Rectangle,
+        shell: &mut Shell<'_, Message>,
+    ) -> bool
+    where
+        Message: Clone,
+    {
+        let Some(origin) = widget_state.context_menu_origin else {
+            return false;
+        };
+        let layout = self
+            .context_menu
+            .layout(origin, bounds, self.char_width, self.line_height);
+        let item_under_mouse = cursor.position().and_then(|point| layout.item_at(point));
+
+        match event {
+            Event::Mouse(mouse::Event::CursorMoved { .. }) => {
+                if widget_state.context_menu_hovered != item_under_mouse {
+                    widget_state.context_menu_hovered = item_under_mouse;
+                    shell.request_redraw();
+                }
+                true
+            }
+            Event::Mouse(mouse::Event::ButtonPressed(button)) => {
+                widget_state.context_menu_origin = None;
+                shell.request_redraw();
+
+                match item_under_mouse.map(|index| &self.context_menu.items()[index].action) {
+                    Some(ContextMenuAction::Editor(message)) => {
+                        shell.publish((self.on_message)(message.clone()));
+                        true
+                    }
+                    Some(ContextMenuAction::App(message)) => {
+                        shell.publish(message.clone());
+                        true
+                    }
+                    // A right click elsewhere reopens the menu there
+                    None => *button != mouse::Button::Right,
+                }
+            }
+            Event::Keyboard(iced::keyboard::Event::KeyPressed { key, .. }) => {
+                widget_state.context_menu_origin = None;
+                shell.request_redraw();
+                *key == iced::keyboard::Key::Named(iced::keyboard::key::Named::Escape)
+            }
+            Event::Mouse(mouse::Event::WheelScrolled { .. }) => {
+                widget_state.context_menu_origin = None;
+                shell.request_redraw();
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Emit the hover event once the mouse has rested long enough
+    fn emit_hover(
+        &self,
+        widget_state: &mut WidgetState,
+        now: Instant,
+        shell: &mut Shell<'_, Message>,
+    ) {
+        let (Some(on_hover), Some(since), Some(position)) = (
+            &self.on_hover,
+            widget_state.hover_since,
+            widget_state.hover_position,
+        ) else {
+            return;
+        };
+
+        let due = since + self.hover_delay;
+        if now >= due {
+            widget_state.hover_since = None;
+            shell.publish(on_hover(position));
+        } else {
+            shell.request_redraw_at(due);
+        }
+    }
+
+    /// Rows taken by the lines of a multi-line inline suggestion, as `(line, count)`
+    fn suggestion_virtual_lines(&self) -> Option<(usize, usize)> {
+        self.editor.inline_suggestion().and_then(|suggestion| {
+            let count = suggestion.lines().count() - 1;
+            (count > 0).then_some((suggestion.position.line, count))
+        })
+    }
+
+    /// Total content height, including the rows of a multi-line inline suggestion
+    fn content_height(&self) -> f32 {
+        let rows = self.editor.current_buffer().line_count()
+            + self
+                .suggestion_virtual_lines()
+                .map_or(0, |(_, count)| count);
+        rows as f32 * self.line_height
+    }
+
+    /// The viewport with rows left free for the lines of a multi-line inline suggestion
+    fn suggestion_viewport<'v>(&self, viewport: &'v Viewport) -> Cow<'v, Viewport> {
+        let virtual_lines = self.suggestion_virtual_lines();
+        if viewport.virtual_lines == virtual_lines {
+            return Cow::Borrowed(viewport);
+        }
+
+        let mut viewport = viewport.clone();
+        viewport.set_virtual_lines(virtual_lines);
+        Cow::Owned(viewport)
     }
 
     /// Convert screen point to editor position (line/column)
     fn point_to_position(&self, point: Point, viewport: &Viewport) -> Position {
+        let viewport = &*self.suggestion_viewport(viewport);
         let gutter_width = self.calculate_gutter_width();
 
         // If click is within the gutter area, position cursor at start of line
@@ -443,8 +1111,15 @@ impl<'a, Message> EditorWidget<'a, Message> {
                 let line_str = line_text.to_string();
                 let click_x = adjusted_point.x + viewport.scroll_offset.0;
 
-                // Use the utility function for accurate tab-aware column calculation
-                utils::x_position_to_column(click_x, &line_str, self.char_width)
+                // Tab-aware, and skips over inline virtual text
+                let virtual_text =
+                    utils::inline_virtual_text_widths(self.editor, line, self.char_width);
+                utils::x_to_column_with_virtual_text(
+                    click_x,
+                    &line_str,
+                    self.char_width,
+                    &virtual_text,
+                )
             } else {
                 0
             }
@@ -455,19 +1130,75 @@ impl<'a, Message> EditorWidget<'a, Message> {
         Position::new(line, column)
     }
 
+    /// Publish an editor message produced by a key, scrolling to the cursor if it moves
+    fn publish_key_message(
+        &self,
+        editor_message: EditorMessage,
+        widget_state: &mut WidgetState,
+        bounds: Rectangle,
+        shell: &mut Shell<'_, Message>,
+    ) {
+        // Check if this is a cursor movement command that should ensure cursor visibility
+        let should_ensure_cursor_visible = self.is_cursor_movement_command(&editor_message);
+
+        let message = (self.on_message)(editor_message);
+        shell.publish(message);
+
+        // After publishing the editor message, ensure cursor is visible if needed
+        if should_ensure_cursor_visible {
+            self.ensure_cursor_visible(widget_state, bounds, shell);
+        }
+    }
+
+    /// Run a key through the Vim layer, returning whether it consumed the key
+    fn handle_vim_key<T: AsRef<str>>(
+        &self,
+        widget_state: &mut WidgetState,
+        key: &iced::keyboard::Key<T>,
+        modifiers: &iced::keyboard::Modifiers,
+        bounds: Rectangle,
+        shell: &mut Shell<'_, Message>,
+    ) -> bool {
+        // A pending chord gets the rest of its keys
+        if !self.vim_mode || widget_state.chord.is_pending() {
+            return false;
+        }
+        let Some(key_event) = self.convert_key_event(key, modifiers) else {
+            return false;
+        };
+
+        let output = widget_state.vim.handle_key(self.editor, key_event);
+        for editor_message in output.messages {
+            self.publish_key_message(editor_message, widget_state, bounds, shell);
+        }
+        if let Some(on_vim_command) = &self.on_vim_command {
+            for command in output.commands {
+                shell.publish(on_vim_command(command));
+            }
+        }
+        // The mode shows in the cursor and the status badge
+        shell.request_redraw();
+        output.handled
+    }
+
     /// Handle keyboard input and convert to editor messages
+    ///
+    /// Several messages may come from one key when it interrupts a pending chord.
     fn handle_keyboard_input<T: AsRef<str>>(
         &self,
+        chord: &mut ChordState,
         key: &iced::keyboard::Key<T>,
         modifiers: &iced::keyboard::Modifiers,
-    ) -> Option<EditorMessage> {
+    ) -> Vec<EditorMessage> {
         // Convert iced key event to our key event format
-        if let Some(key_event) = self.convert_key_event(key, modifiers) {
-            // Handle with shortcut manager - it already handles character input properly
-            self.shortcut_manager.handle_key_event(key_event)
-        } else {
-            None
-        }
+        let Some(key_event) = self.convert_key_event(key, modifiers) else {
+            return Vec::new();
+        };
+
+        // Bindings for open popups, snippets and suggestions apply through the context
+        let context = self.key_context.clone().with_editor(self.editor);
+        self.shortcut_manager
+            .handle_key_sequence(chord, key_event, &context)
     }
 
     /// Check if the editor message is a cursor movement command that should trigger cursor visibility check
@@ -478,6 +1209,10 @@ impl<'a, Message> EditorWidget<'a, Message> {
                 | EditorMessage::MoveCursorWithSelection(_)
                 | EditorMessage::MoveCursorTo(_)
                 | EditorMessage::ScrollToLine(_)
+                | EditorMessage::NextDiagnostic
+                | EditorMessage::PreviousDiagnostic
+                | EditorMessage::FindNext
+                | EditorMessage::FindPrevious
         )
     }
 
@@ -488,23 +1223,7 @@ impl<'a, Message> EditorWidget<'a, Message> {
         viewport: &Viewport,
     ) -> bool {
         let cursor_y = cursor_position.line as f32 * self.line_height;
-        let cursor_x = {
-            let rope = self.editor.current_buffer().rope();
-            if cursor_position.line < rope.len_lines() {
-                if let Some(line) = rope.get_line(cursor_position.line) {
-                    let line_str = line.to_string();
-                    utils::calculate_column_x_position(
-                        cursor_position.column,
-                        &line_str,
-                        self.char_width,
-                    )
-                } else {
-                    cursor_position.column as f32 * self.char_width
-                }
-            } else {
-                cursor_position.column as f32 * self.char_width
-            }
-        };
+        let cursor_x = utils::position_x(self.editor, cursor_position, self.char_width);
 
         // Define margins for comfortable scrolling
         let scroll_margin_v = self.line_height * 2.0;
@@ -545,23 +1264,7 @@ impl<'a, Message> EditorWidget<'a, Message> {
 
         // Calculate cursor position in viewport coordinates
         let cursor_y = cursor_position.line as f32 * self.line_height;
-        let cursor_x = {
-            let rope = self.editor.current_buffer().rope();
-            if cursor_position.line < rope.len_lines() {
-                if let Some(line) = rope.get_line(cursor_position.line) {
-                    let line_str = line.to_string();
-                    utils::calculate_column_x_position(
-                        cursor_position.column,
-                        &line_str,
-                        self.char_width,
-                    )
-                } else {
-                    cursor_position.column as f32 * self.char_width
-                }
-            } else {
-                cursor_position.column as f32 * self.char_width
-            }
-        };
+        let cursor_x = utils::position_x(self.editor, cursor_position, self.char_width);
 
         // Calculate current scroll offset
         let mut new_scroll_x = viewport.scroll_offset.0;
@@ -607,10 +1310,7 @@ impl<'a, Message> EditorWidget<'a, Message> {
 
         // Apply scroll bounds to prevent over-scrolling
         if scroll_changed {
-            let buffer = self.editor.current_buffer();
-            let line_count = buffer.line_count();
-            let content_height = line_count as f32 * self.line_height;
-            let max_scroll_y = (content_height - bounds.height).max(0.0);
+            let max_scroll_y = (self.content_height() - bounds.height).max(0.0);
             let max_content_width = self.calculate_max_content_width();
             let max_scroll_x = (max_content_width - bounds.width).max(0.0);
 
@@ -648,6 +1348,7 @@ impl<'a, Message> EditorWidget<'a, Message> {
                 iced::keyboard::key::Named::ArrowRight => Key::Named(NamedKey::ArrowRight),
                 iced::keyboard::key::Named::Backspace => Key::Named(NamedKey::Backspace),
                 iced::keyboard::key::Named::Delete => Key::Named(NamedKey::Delete),
+                iced::keyboard::key::Named::Insert => Key::Named(NamedKey::Insert),
                 iced::keyboard::key::Named::Enter => Key::Named(NamedKey::Enter),
                 iced::keyboard::key::Named::Tab => Key::Named(NamedKey::Tab),
                 iced::keyboard::key::Named::Space => Key::Named(NamedKey::Space),
@@ -656,6 +1357,18 @@ impl<'a, Message> EditorWidget<'a, Message> {
                 iced::keyboard::key::Named::PageUp => Key::Named(NamedKey::PageUp),
                 iced::keyboard::key::Named::PageDown => Key::Named(NamedKey::PageDown),
                 iced::keyboard::key::Named::Escape => Key::Named(NamedKey::Escape),
+                iced::keyboard::key::Named::F1 => Key::Named(NamedKey::F1),
+                iced::keyboard::key::Named::F2 => Key::Named(NamedKey::F2),
+                iced::keyboard::key::Named::F3 => Key::Named(NamedKey::F3),
+                iced::keyboard::key::Named::F4 => Key::Named(NamedKey::F4),
+                iced::keyboard::key::Named::F5 => Key::Named(NamedKey::F5),
+                iced::keyboard::key::Named::F6 => Key::Named(NamedKey::F6),
+                iced::keyboard::key::Named::F7 => Key::Named(NamedKey::F7),
+                iced::keyboard::key::Named::F8 => Key::Named(NamedKey::F8),
+                iced::keyboard::key::Named::F9 => Key::Named(NamedKey::F9),
+                iced::keyboard::key::Named::F10 => Key::Named(NamedKey::F10),
+                iced::keyboard::key::Named::F11 => Key::Named(NamedKey::F11),
+                iced::keyboard::key::Named::F12 => Key::Named(NamedKey::F12),
                 _ => return None,
             },
             _ => return None,
@@ -723,10 +1436,7 @@ impl<'a, Message> EditorWidget<'a, Message> {
             );
 
             // Clamp scroll offset to reasonable bounds
-            let buffer = self.editor.current_buffer();
-            let line_count = buffer.line_count();
-            let content_height = line_count as f32 * self.line_height;
-            let max_scroll_y = (content_height - bounds.height).max(0.0);
+            let max_scroll_y = (self.content_height() - bounds.height).max(0.0);
             let max_content_width = self.calculate_max_content_width();
             let max_scroll_x = (max_content_width - bounds.width).max(0.0);
 